@@ -1,12 +1,35 @@
-use crate::types::KnownDeviceCache;
+use crate::engine::format_iso;
+use crate::types::{DeviceCategory, KnownDevice, KnownDeviceCache, CACHE_VERSION};
+use chrono::{Local, NaiveDateTime};
 
 const CACHE_FILE: &str = "device-history-cache.json";
 
+/// How timestamps were written before cache version 3: local time, no
+/// offset.
+const LEGACY_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
 pub fn load_cache() -> KnownDeviceCache {
-    std::fs::read_to_string(CACHE_FILE)
-        .ok()
-        .and_then(|s| serde_json::from_str(&s).ok())
-        .unwrap_or_else(KnownDeviceCache::new)
+    parse_cache(&std::fs::read_to_string(CACHE_FILE).unwrap_or_default())
+}
+
+/// A cache file's contents, brought up to the current version. Anything
+/// unreadable is an empty cache.
+fn parse_cache(text: &str) -> KnownDeviceCache {
+    let mut cache = serde_json::from_str(text).unwrap_or_else(|_| KnownDeviceCache::new());
+    if cache.version < CACHE_VERSION {
+        for kd in cache.devices.values_mut() {
+            migrate_timestamps(kd);
+        }
+        cache.version = CACHE_VERSION;
+    }
+    // Records saved before categories existed get a best guess from their
+    // PnP class until the device is seen again.
+    for kd in cache.devices.values_mut() {
+        if kd.category == DeviceCategory::Other {
+            kd.category = DeviceCategory::from_pnp_class(&kd.class, &kd.name);
+        }
+    }
+    cache
 }
 
 pub fn save_cache(cache: &KnownDeviceCache) {
@@ -14,3 +37,95 @@ pub fn save_cache(cache: &KnownDeviceCache) {
         let _ = std::fs::write(CACHE_FILE, json);
     }
 }
+
+/// Rewrites a version 2 record's timestamps as RFC 3339 in UTC, taking
+/// them to be in the current time zone. Any that don't parse are left
+/// alone.
+fn migrate_timestamps(kd: &mut KnownDevice) {
+    let stamps = [&mut kd.first_seen, &mut kd.last_seen]
+        .into_iter()
+        .chain(kd.speed_history.iter_mut().map(|s| &mut s.connected_at))
+        .chain(kd.volume_history.iter_mut().map(|s| &mut s.timestamp));
+    for ts in stamps {
+        let local = NaiveDateTime::parse_from_str(ts, LEGACY_FORMAT)
+            .ok()
+            .and_then(|t| t.and_local_timezone(Local).earliest());
+        if let Some(t) = local {
+            *ts = format_iso(t);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::parse_iso;
+    use crate::types::LinkSpeed;
+    use chrono::TimeZone;
+
+    const DEVICE: &str = "USB\\VID_0781&PID_5581\\4C530001";
+
+    /// A cache file holding one drive, seen at `first_seen` with a speed
+    /// sample from then.
+    fn cache(version: u32, first_seen: &str, last_seen: &str) -> String {
+        serde_json::json!({
+            "version": version,
+            "devices": {
+                DEVICE: {
+                    "device_id": DEVICE,
+                    "name": "Ultra",
+                    "vid_pid": "0781:5581",
+                    "class": "DiskDrive",
+                    "manufacturer": "SanDisk",
+                    "description": "Ultra",
+                    "first_seen": first_seen,
+                    "last_seen": last_seen,
+                    "times_seen": 2,
+                    "currently_connected": false,
+                    "speed_history": [{ "connected_at": first_seen, "speed": "super" }],
+                }
+            }
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn version_2_timestamps_move_to_utc() {
+        let cache = parse_cache(&cache(2, "2024-03-09 15:00:00", "2024-03-10 09:30:15"));
+        assert_eq!(cache.version, CACHE_VERSION);
+        let kd = &cache.devices[DEVICE];
+        let local = |d, h, m, s| Local.with_ymd_and_hms(2024, 3, d, h, m, s).unwrap();
+        assert_eq!(parse_iso(&kd.first_seen), Some(local(9, 15, 0, 0)));
+        assert_eq!(parse_iso(&kd.last_seen), Some(local(10, 9, 30, 15)));
+        assert!(kd.first_seen.ends_with('Z'));
+        assert_eq!(kd.speed_history[0].connected_at, kd.first_seen);
+        assert_eq!(kd.speed_history[0].speed, LinkSpeed::Super);
+    }
+
+    #[test]
+    fn version_3_timestamps_are_left_alone() {
+        let stamp = "2024-03-09T14:00:00.000Z";
+        let cache = parse_cache(&cache(3, stamp, stamp));
+        let kd = &cache.devices[DEVICE];
+        assert_eq!(
+            (kd.first_seen.as_str(), kd.last_seen.as_str()),
+            (stamp, stamp)
+        );
+        // Categories are still filled in from the PnP class.
+        assert_eq!(kd.category, DeviceCategory::Storage);
+    }
+
+    #[test]
+    fn unparsable_timestamps_are_kept_as_they_are() {
+        let cache = parse_cache(&cache(2, "last Tuesday", "2024-03-09 15:00:00"));
+        let kd = &cache.devices[DEVICE];
+        assert_eq!(kd.first_seen, "last Tuesday");
+        assert!(parse_iso(&kd.last_seen).is_some());
+        assert_eq!(cache.version, CACHE_VERSION);
+
+        // A file that isn't a cache at all starts over.
+        let cache = parse_cache("{not json");
+        assert!(cache.devices.is_empty());
+        assert_eq!(cache.version, CACHE_VERSION);
+    }
+}
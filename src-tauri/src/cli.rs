@@ -1,28 +1,23 @@
-use crate::logging::log_to_file;
-use crate::types::UsbDevice;
-use chrono::Local;
+use crate::cache::load_cache;
+use crate::descriptors;
+use crate::engine::{parse_iso, Engine};
+use crate::instance_id::DeviceInstanceId;
+use crate::logging::{log_event, log_to_file};
+use crate::rules;
+use crate::source::SourceSpec;
+use crate::storage::format_bytes;
+use crate::topology;
+use crate::types::{
+    Bus, DeviceEvent, DeviceInterface, EventKind, KnownDevice, TopologyNode, VolumeHistory,
+};
+use crate::usb_ids::{self, UsbIds};
+use crate::volumes;
 use colored::*;
 use std::collections::HashMap;
-use std::thread;
-use std::time::Duration;
-use wmi::{COMLibrary, WMIConnection};
-
-fn query_devices(wmi: &WMIConnection) -> Option<HashMap<String, UsbDevice>> {
-    let results: Vec<UsbDevice> = wmi
-        .raw_query(
-            "SELECT Name, DeviceID, Description, Manufacturer, PNPClass \
-             FROM Win32_PnPEntity WHERE DeviceID LIKE 'USB%'",
-        )
-        .ok()?;
-    Some(
-        results
-            .into_iter()
-            .filter_map(|d| Some((d.DeviceID.clone()?, d)))
-            .collect(),
-    )
-}
+use std::fs;
+use std::path::Path;
 
-pub fn run_cli() {
+fn attach_console() {
     #[cfg(windows)]
     unsafe {
         extern "system" {
@@ -33,6 +28,11 @@ pub fn run_cli() {
             AllocConsole();
         }
     }
+}
+
+/// `expand` lists each composite device's interfaces under it.
+pub fn run_cli(spec: SourceSpec, expand: bool) {
+    attach_console();
 
     let ver = env!("CARGO_PKG_VERSION");
     let title = format!("Device History v{}", ver);
@@ -56,95 +56,407 @@ pub fn run_cli() {
     );
     println!();
 
-    let com = COMLibrary::new().expect("Failed to initialize COM library");
-    let wmi = WMIConnection::new(com).expect("Failed to connect to WMI");
-    let mut devices = query_devices(&wmi).expect("Failed to query USB devices");
+    let mut source = spec.open().expect("Failed to open device source");
+    let devices = source.enumerate().expect("Failed to query USB devices");
+
+    // The CLI keeps no history, so the engine starts with nothing known.
+    let mut engine = Engine::new(
+        HashMap::new(),
+        devices,
+        usb_ids::current(),
+        rules::current(),
+        source.now(),
+    );
 
     println!(
-        "{} {} USB devices currently connected:\n",
+        "{} {} devices currently connected:\n",
         "*".green(),
-        devices.len().to_string().bold()
+        engine.attached().len().to_string().bold()
     );
 
-    let mut sorted: Vec<_> = devices.values().collect();
-    sorted.sort_by_key(|d| d.display_name().to_lowercase());
-    for dev in &sorted {
+    for dev in engine.snapshot() {
         let vid_pid = dev
-            .vid_pid()
+            .vid_pid
+            .as_ref()
             .map(|vp| format!(" [{}]", vp))
             .unwrap_or_default();
         let mfr = dev
-            .Manufacturer
-            .as_deref()
+            .manufacturer
+            .as_ref()
+            .or(dev.vendor_name.as_ref())
             .map(|m| format!(" ({})", m))
             .unwrap_or_default();
+        let product = dev
+            .product_name
+            .as_ref()
+            .filter(|p| **p != dev.name)
+            .map(|p| format!(" \u{2014} {}", p))
+            .unwrap_or_default();
         println!(
-            "  {} {} {}{}{}",
+            "  {} {} {}{}{}{}",
             "|".dimmed(),
-            dev.class().dimmed(),
-            dev.display_name(),
+            dev.class.dimmed(),
+            dev.name,
             vid_pid.dimmed(),
-            mfr.dimmed()
+            mfr.dimmed(),
+            product.dimmed()
         );
+        if expand {
+            print_interfaces(&dev.interfaces, "    ");
+        }
     }
 
     log_to_file(&format!(
         "Started monitoring (CLI) — {} devices",
-        devices.len()
+        engine.attached().len()
     ));
     println!("\n{}", "Watching for changes... (Ctrl+C to quit)".dimmed());
     println!("{}\n", "\u{2500}".repeat(60).dimmed());
 
-    loop {
-        thread::sleep(Duration::from_millis(500));
-        let Some(current) = query_devices(&wmi) else {
-            continue;
-        };
-
-        for (id, dev) in &devices {
-            if !current.contains_key(id) {
-                let ts = Local::now().format("%H:%M:%S").to_string();
-                let vp = dev
-                    .vid_pid()
-                    .map(|v| format!(" [{}]", v))
-                    .unwrap_or_default();
-                println!(
-                    "{} {} {} {}",
-                    format!("[{}]", ts).dimmed(),
-                    "\u{25BC} DISCONNECT".red().bold(),
-                    dev.display_name().red(),
-                    vp.yellow()
-                );
-                log_to_file(&format!(
-                    "DISCONNECT: {} {} | {}",
-                    dev.display_name(),
-                    vp,
-                    id
-                ));
+    let mut last_resync = source.now();
+    while !source.finished() {
+        let changes = source.wait_for_changes(source.poll_interval());
+        let had_hotplug = !changes.is_empty();
+        let now = source.now();
+        let mut out = engine.hotplug(changes, now);
+
+        let since_resync = (now - last_resync).to_std().unwrap_or_default();
+        if !had_hotplug || since_resync >= source.poll_interval() {
+            match source.enumerate() {
+                Ok(current) => {
+                    last_resync = now;
+                    out.merge(engine.enumerated(current, now));
+                }
+                Err(e) => out.merge(engine.enumeration_failed(&e, now)),
+            }
+        }
+
+        for event in &out.events {
+            print_event(event, engine.usb_ids());
+            log_event(event);
+            let connected = matches!(event.kind, EventKind::Connect | EventKind::Reconnect { .. });
+            if let Some(dev) = engine
+                .attached()
+                .get(&event.device_id)
+                .filter(|_| expand && connected)
+            {
+                print_interfaces(&dev.Interfaces, "             ");
             }
         }
-        for (id, dev) in &current {
-            if !devices.contains_key(id) {
-                let ts = Local::now().format("%H:%M:%S").to_string();
-                let vp = dev
-                    .vid_pid()
-                    .map(|v| format!(" [{}]", v))
-                    .unwrap_or_default();
-                println!(
-                    "{} {} {} {}",
-                    format!("[{}]", ts).dimmed(),
-                    "\u{25B2} CONNECT   ".green().bold(),
-                    dev.display_name().green(),
-                    vp.yellow()
-                );
-                log_to_file(&format!(
-                    "CONNECT: {} {} | {}",
-                    dev.display_name(),
-                    vp,
-                    id
-                ));
+    }
+    println!("\n{}", "End of recording.".dimmed());
+}
+
+/// `--cli tree`: prints the attached devices as a hub/port tree, like
+/// `lsusb -t`, and exits.
+pub fn run_tree(spec: SourceSpec) {
+    attach_console();
+
+    let mut source = spec.open().expect("Failed to open device source");
+    let devices = source.enumerate().expect("Failed to query USB devices");
+    let engine = Engine::new(
+        HashMap::new(),
+        devices,
+        usb_ids::current(),
+        rules::current(),
+        source.now(),
+    );
+
+    #[allow(unused_mut)]
+    let mut snapshot = engine.snapshot();
+    #[cfg(windows)]
+    topology::resolve_locations(&mut snapshot);
+    for node in topology::build(snapshot) {
+        print_node(&node, "", None);
+    }
+}
+
+/// `--cli describe <id>`: prints a device's descriptors like `lsusb -v`.
+/// `id` is a device ID, a location (`1-2.3`) or a VID:PID; a path to a
+/// file is decoded as a captured descriptor blob instead.
+pub fn run_describe(spec: SourceSpec, target: Option<&str>) {
+    attach_console();
+
+    let Some(target) = target else {
+        eprintln!("Usage: --cli describe <device id | location | vid:pid | file>");
+        return;
+    };
+    let ids = usb_ids::current();
+    if Path::new(target).is_file() {
+        match fs::read(target)
+            .map_err(|e| e.to_string())
+            .and_then(|b| descriptors::parse(&b))
+        {
+            Ok(d) => print!("{}", descriptors::describe(&d, &ids)),
+            Err(e) => eprintln!("{} {}: {}", "Error:".red().bold(), target, e),
+        }
+        return;
+    }
+
+    let mut source = spec.open().expect("Failed to open device source");
+    let devices = source.enumerate().expect("Failed to query USB devices");
+    let engine = Engine::new(
+        HashMap::new(),
+        devices,
+        ids.clone(),
+        rules::current(),
+        source.now(),
+    );
+    let snapshot = engine.snapshot();
+    let Some(dev) = snapshot.iter().find(|d| {
+        d.device_id.eq_ignore_ascii_case(target)
+            || d.location_path.as_deref() == Some(target)
+            || d.vid_pid
+                .as_deref()
+                .is_some_and(|vp| vp.eq_ignore_ascii_case(target))
+    }) else {
+        eprintln!(
+            "{} no attached device matches {}",
+            "Error:".red().bold(),
+            target
+        );
+        return;
+    };
+
+    println!("{} {}\n", dev.name.bold(), dev.device_id.dimmed());
+    let result = dev
+        .location_path
+        .as_deref()
+        .ok_or_else(|| "Device location is unknown".to_string())
+        .and_then(descriptors::read_device);
+    match result {
+        Ok(d) => print!("{}", descriptors::describe(&d, &ids)),
+        Err(e) => eprintln!("{} {}", "Error:".red().bold(), e),
+    }
+}
+
+/// `--cli volumes [device]`: free space over time for the drives in the
+/// cache, with each volume's fill rate and how long until it's full.
+/// Reads the cache only; nothing is enumerated.
+pub fn run_volumes(target: Option<&str>) {
+    attach_console();
+
+    let cache = load_cache();
+    let mut devices: Vec<&KnownDevice> = cache
+        .devices
+        .values()
+        .filter(|kd| !kd.volume_history.is_empty())
+        .filter(|kd| {
+            target.is_none_or(|t| {
+                kd.device_id.eq_ignore_ascii_case(t)
+                    || kd.vid_pid.eq_ignore_ascii_case(t)
+                    || kd.nickname.as_deref() == Some(t)
+            })
+        })
+        .collect();
+    if devices.is_empty() {
+        println!("{}", "No volume history yet.".dimmed());
+        return;
+    }
+    devices.sort_by_cached_key(|kd| kd.name.to_lowercase());
+
+    for kd in devices {
+        let alert = kd
+            .fill_alert
+            .map(|p| format!("  alert at {}%", p))
+            .unwrap_or_default();
+        println!(
+            "{} {}{}",
+            kd.nickname.as_deref().unwrap_or(&kd.name).bold(),
+            kd.device_id.dimmed(),
+            alert.dimmed()
+        );
+        for vol in volumes::history(kd) {
+            let used = vol.total_bytes.saturating_sub(vol.free_bytes);
+            let name = match vol.volume_name.as_str() {
+                "" => "(no label)",
+                name => name,
+            };
+            let usage = format!(
+                "{} of {} used ({:.0}%)",
+                format_bytes(used),
+                format_bytes(vol.total_bytes),
+                used as f64 * 100.0 / vol.total_bytes.max(1) as f64
+            );
+            println!(
+                "  {} {}  {}",
+                name,
+                vol.volume_serial.dimmed(),
+                if vol.alert {
+                    usage.red().bold()
+                } else {
+                    usage.normal()
+                }
+            );
+            println!("    {} {}", sparkline(&vol).cyan(), trend(&vol).dimmed());
+        }
+        println!();
+    }
+}
+
+/// Used space across the last samples, as block characters.
+fn sparkline(vol: &VolumeHistory) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let recent = &vol.samples[vol.samples.len().saturating_sub(40)..];
+    recent
+        .iter()
+        .map(|s| {
+            let used =
+                s.total_bytes.saturating_sub(s.free_bytes) as f64 / s.total_bytes.max(1) as f64;
+            BLOCKS[((used * 7.0).round() as usize).min(7)]
+        })
+        .collect()
+}
+
+fn trend(vol: &VolumeHistory) -> String {
+    let samples = format!("{} samples", vol.samples.len());
+    let rate = format_bytes(vol.bytes_per_day.abs() as u64);
+    match vol.days_until_full {
+        Some(days) => format!(
+            "{}, filling {}/day, full in ~{:.0} days",
+            samples, rate, days
+        ),
+        None if vol.bytes_per_day < 0.0 => format!("{}, freeing {}/day", samples, rate),
+        None => format!("{}, steady", samples),
+    }
+}
+
+/// One tree line plus everything below it. `last` is None for a root,
+/// otherwise whether this is its parent's last child.
+fn print_node(node: &TopologyNode, prefix: &str, last: Option<bool>) {
+    let (branch, below) = match last {
+        None => ("", String::new()),
+        Some(false) => ("\u{251C}\u{2500} ", format!("{}\u{2502}  ", prefix)),
+        Some(true) => ("\u{2514}\u{2500} ", format!("{}   ", prefix)),
+    };
+    let dev = &node.device;
+    let vid_pid = dev
+        .vid_pid
+        .as_ref()
+        .map(|vp| format!(" [{}]", vp))
+        .unwrap_or_default();
+    let location = dev
+        .location_path
+        .as_ref()
+        .map(|l| format!("  {}", l))
+        .unwrap_or_default();
+    let speed = dev
+        .speed
+        .map(|s| format!("  {}", s.label()))
+        .unwrap_or_default();
+    let power = match (&dev.hub_power, &dev.power) {
+        (Some(hub), _) => {
+            let text = format!("  {}/{} mA", hub.used_ma, hub.budget_ma);
+            if hub.exceeded {
+                text.red().bold()
+            } else {
+                text.dimmed()
             }
         }
-        devices = current;
+        (None, Some(p)) if p.max_power_ma > 0 => format!("  {} mA", p.max_power_ma).dimmed(),
+        _ => "".normal(),
+    };
+    println!(
+        "{}{}{}{}{}{}{}",
+        prefix.dimmed(),
+        branch.dimmed(),
+        dev.name,
+        vid_pid.yellow(),
+        location.dimmed(),
+        speed.dimmed(),
+        power
+    );
+    for (i, child) in node.children.iter().enumerate() {
+        print_node(child, &below, Some(i + 1 == node.children.len()));
+    }
+}
+
+/// An event's timestamp as local `14:05:22`.
+fn clock(timestamp: &str) -> String {
+    parse_iso(timestamp)
+        .map(|t| t.format("%H:%M:%S").to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+/// " thunderbolt", " bluetooth", ... for devices that aren't on USB.
+fn bus_tag(event: &DeviceEvent) -> String {
+    match event.bus {
+        Bus::Usb => String::new(),
+        bus => format!(" {}", bus.name()),
+    }
+}
+
+/// " (Logitech, Inc. Unifying Receiver)" when the USB ID database knows
+/// the device, so generic names like "USB Receiver" can be told apart.
+fn id_names(event: &DeviceEvent, ids: &UsbIds) -> String {
+    match ids.lookup(&DeviceInstanceId::parse(&event.device_id)) {
+        (Some(vendor), Some(product)) => format!(" ({} {})", vendor, product),
+        (Some(vendor), None) => format!(" ({})", vendor),
+        _ => String::new(),
+    }
+}
+
+fn print_interfaces(interfaces: &[DeviceInterface], indent: &str) {
+    for iface in interfaces {
+        let number = iface
+            .number
+            .map(|n| format!("MI_{:02X} ", n))
+            .unwrap_or_default();
+        println!(
+            "{}{} {}{} {}",
+            indent,
+            "\u{2514}".dimmed(),
+            number.dimmed(),
+            iface.name,
+            iface.class.dimmed()
+        );
+    }
+}
+
+/// One line per event, coloured by how much it matters. Connects and
+/// disconnects name the device in full and put any detail underneath;
+/// everything else gives the detail on the same line.
+fn print_event(event: &DeviceEvent, ids: &UsbIds) {
+    let (badge, color) = match &event.kind {
+        EventKind::Connect => ("\u{25B2} CONNECT", Color::Green),
+        EventKind::Reconnect { .. } => ("\u{25B2} RECONNECT", Color::Green),
+        EventKind::Disconnect { .. } => ("\u{25BC} DISCONNECT", Color::Red),
+        EventKind::Warning { .. } => ("\u{26A0} WARNING", Color::Yellow),
+        EventKind::EnrichmentFailed { .. } => ("\u{26A0} NO DETAILS", Color::Yellow),
+        EventKind::MonitorError { .. } => ("\u{26A0} MONITOR", Color::Yellow),
+        EventKind::AttributeChanged { .. } => ("\u{25C6} CHANGED", Color::Cyan),
+        EventKind::StorageEnriched { .. } => ("\u{25C6} STORAGE", Color::Cyan),
+        EventKind::VolumeChanged { .. } => ("\u{25C6} VOLUME", Color::Cyan),
+        EventKind::MonitorResumed { .. } => ("\u{25C6} RESUMED", Color::Cyan),
+    };
+    let head = format!(
+        "{} {}",
+        format!("[{}]", clock(&event.timestamp)).dimmed(),
+        format!("{:<12}", badge).color(color).bold()
+    );
+    let detail = event.kind.detail().unwrap_or_default();
+    let device_line = matches!(
+        event.kind,
+        EventKind::Connect | EventKind::Reconnect { .. } | EventKind::Disconnect { .. }
+    );
+    if !device_line {
+        println!("{} {} {}", head, event.name.color(color), detail);
+        return;
+    }
+    let vp = event
+        .vid_pid
+        .as_ref()
+        .map(|v| format!(" [{}]", v))
+        .unwrap_or_default();
+    println!(
+        "{} {} {}{}{}",
+        head,
+        event.name.color(color),
+        vp.yellow(),
+        bus_tag(event).cyan(),
+        id_names(event, ids).dimmed()
+    );
+    if !detail.is_empty() {
+        println!("{} {}", " ".repeat(23), detail.yellow());
     }
 }
@@ -0,0 +1,33 @@
+use crate::descriptors::{self, DeviceDescriptors};
+use crate::state::AppState;
+use crate::usb_ids;
+use serde::Serialize;
+use std::sync::Arc;
+use tauri::State;
+
+#[derive(Serialize)]
+pub struct DeviceDetails {
+    pub descriptors: DeviceDescriptors,
+    /// The same, rendered like `lsusb -v`.
+    pub text: String,
+}
+
+/// Raw descriptors of a connected device, read fresh from sysfs.
+#[tauri::command]
+pub fn get_device_details(
+    state: State<'_, Arc<AppState>>,
+    device_id: String,
+) -> Result<DeviceDetails, String> {
+    let location = state
+        .devices
+        .read()
+        .iter()
+        .find(|d| d.device_id == device_id)
+        .ok_or("Device isn't connected")?
+        .location_path
+        .clone()
+        .ok_or("Device location is unknown")?;
+    let descriptors = descriptors::read_device(&location)?;
+    let text = descriptors::describe(&descriptors, &usb_ids::current());
+    Ok(DeviceDetails { descriptors, text })
+}
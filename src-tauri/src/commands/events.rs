@@ -2,7 +2,14 @@ use crate::state::AppState;
 use std::sync::Arc;
 use tauri::State;
 
+/// Hides the events so far from the log. They stay in the journal.
 #[tauri::command]
 pub fn clear_events(state: State<'_, Arc<AppState>>) {
-    state.events.write().clear();
+    let mut events = state.events.write();
+    if let Some(last) = events.last() {
+        *state.events_cleared_through.write() = last.seq;
+    }
+    events.clear();
+    drop(events);
+    super::prefs::save_current_prefs(&state);
 }
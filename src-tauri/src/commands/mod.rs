@@ -1,5 +1,9 @@
 pub mod snapshot;
+pub mod descriptors;
 pub mod nicknames;
 pub mod events;
 pub mod prefs;
+pub mod rules;
+pub mod storage;
 pub mod system;
+pub mod usb_ids;
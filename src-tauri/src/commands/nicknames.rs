@@ -18,8 +18,8 @@ pub fn set_nickname(
             Some(nickname.trim().to_string())
         };
         let cache = KnownDeviceCache {
-            version: 2,
             devices: known.clone(),
+            ..KnownDeviceCache::new()
         };
         save_cache(&cache);
     }
@@ -34,8 +34,8 @@ pub fn forget_device(
     known.remove(&device_id);
     state.storage_info.write().remove(&device_id);
     let cache = KnownDeviceCache {
-        version: 2,
         devices: known.clone(),
+        ..KnownDeviceCache::new()
     };
     save_cache(&cache);
 }
@@ -15,6 +15,10 @@ fn load_prefs() -> Prefs {
             match key.trim() {
                 "theme" => prefs.theme = val.trim().to_string(),
                 "active_tab" => prefs.active_tab = val.trim().to_string(),
+                "expand_interfaces" => prefs.expand_interfaces = val.trim() == "true",
+                "events_cleared_through" => {
+                    prefs.events_cleared_through = val.trim().parse().unwrap_or(0)
+                }
                 _ => {}
             }
         }
@@ -23,38 +27,49 @@ fn load_prefs() -> Prefs {
 }
 
 fn save_prefs(prefs: &Prefs) {
-    let content = format!("theme={}\nactive_tab={}\n", prefs.theme, prefs.active_tab);
+    let content = format!(
+        "theme={}\nactive_tab={}\nexpand_interfaces={}\nevents_cleared_through={}\n",
+        prefs.theme, prefs.active_tab, prefs.expand_interfaces, prefs.events_cleared_through
+    );
     let _ = std::fs::write(PREFS_FILE, content);
 }
 
+pub fn save_current_prefs(state: &AppState) {
+    save_prefs(&current_prefs(state));
+}
+
 pub fn load_initial_prefs() -> Prefs {
     load_prefs()
 }
 
-#[tauri::command]
-pub fn get_prefs(state: State<'_, Arc<AppState>>) -> Prefs {
+fn current_prefs(state: &AppState) -> Prefs {
     Prefs {
         theme: state.prefs_theme.read().clone(),
         active_tab: state.prefs_tab.read().clone(),
+        expand_interfaces: *state.prefs_expand_interfaces.read(),
+        events_cleared_through: *state.events_cleared_through.read(),
     }
 }
 
+#[tauri::command]
+pub fn get_prefs(state: State<'_, Arc<AppState>>) -> Prefs {
+    current_prefs(&state)
+}
+
 #[tauri::command]
 pub fn set_theme(state: State<'_, Arc<AppState>>, theme: String) {
-    *state.prefs_theme.write() = theme.clone();
-    let tab = state.prefs_tab.read().clone();
-    save_prefs(&Prefs {
-        theme,
-        active_tab: tab,
-    });
+    *state.prefs_theme.write() = theme;
+    save_prefs(&current_prefs(&state));
 }
 
 #[tauri::command]
 pub fn set_tab(state: State<'_, Arc<AppState>>, tab: String) {
-    *state.prefs_tab.write() = tab.clone();
-    let theme = state.prefs_theme.read().clone();
-    save_prefs(&Prefs {
-        theme,
-        active_tab: tab,
-    });
+    *state.prefs_tab.write() = tab;
+    save_prefs(&current_prefs(&state));
+}
+
+#[tauri::command]
+pub fn set_expand_interfaces(state: State<'_, Arc<AppState>>, expand: bool) {
+    *state.prefs_expand_interfaces.write() = expand;
+    save_prefs(&current_prefs(&state));
 }
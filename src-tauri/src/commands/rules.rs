@@ -0,0 +1,13 @@
+use crate::rules;
+use crate::types::RulesInfo;
+
+/// Re-reads `device-rules.toml`. The monitor re-applies the rules to the
+/// attached devices on its next tick.
+#[tauri::command]
+pub fn reload_rules() -> Result<RulesInfo, String> {
+    let rules = rules::reload()?;
+    Ok(RulesInfo {
+        source: rules.source().to_string(),
+        rules: rules.count(),
+    })
+}
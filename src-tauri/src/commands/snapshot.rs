@@ -1,5 +1,6 @@
 use crate::state::AppState;
-use crate::types::AppSnapshot;
+use crate::topology;
+use crate::types::{AppSnapshot, TopologyNode};
 use std::sync::Arc;
 use tauri::State;
 
@@ -7,3 +8,14 @@ use tauri::State;
 pub fn get_snapshot(state: State<'_, Arc<AppState>>) -> AppSnapshot {
     state.snapshot()
 }
+
+/// The attached devices as a hub/port tree. Async because resolving
+/// locations on Windows shells out to PowerShell.
+#[tauri::command]
+pub async fn get_topology(state: State<'_, Arc<AppState>>) -> Result<Vec<TopologyNode>, String> {
+    #[allow(unused_mut)]
+    let mut devices = state.devices.read().clone();
+    #[cfg(windows)]
+    topology::resolve_locations(&mut devices);
+    Ok(topology::build(devices))
+}
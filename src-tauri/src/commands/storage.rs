@@ -0,0 +1,42 @@
+use crate::state::AppState;
+use crate::types::VolumeHistory;
+use crate::volumes;
+use std::sync::Arc;
+use tauri::State;
+
+/// Looks a connected drive up again without waiting for its periodic
+/// refresh. The monitor picks the request up on its next tick.
+#[tauri::command]
+pub fn refresh_storage(state: State<'_, Arc<AppState>>, device_id: String) {
+    state.storage_refresh.write().push(device_id);
+}
+
+/// Free space over time for each volume a known drive has had, with the
+/// fill rate and projected days until full.
+#[tauri::command]
+pub fn get_volume_history(
+    state: State<'_, Arc<AppState>>,
+    device_id: String,
+) -> Result<Vec<VolumeHistory>, String> {
+    let known = state.known_devices.read();
+    let kd = known.get(&device_id).ok_or("Device isn't known")?;
+    Ok(volumes::history(kd))
+}
+
+/// Warns when a volume on the drive gets fuller than `percent`. None
+/// turns the alert off. The monitor picks the change up on its next tick
+/// and saves the cache along with everything else it knows.
+#[tauri::command]
+pub fn set_fill_alert(
+    state: State<'_, Arc<AppState>>,
+    device_id: String,
+    percent: Option<u8>,
+) -> Result<(), String> {
+    if percent.is_some_and(|p| p == 0 || p > 100) {
+        return Err("Alert threshold must be between 1 and 100%".to_string());
+    }
+    let mut known = state.known_devices.write();
+    let kd = known.get_mut(&device_id).ok_or("Device isn't known")?;
+    kd.fill_alert = percent;
+    Ok(())
+}
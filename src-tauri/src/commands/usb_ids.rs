@@ -0,0 +1,16 @@
+use crate::types::UsbIdsInfo;
+use crate::usb_ids;
+use std::path::Path;
+
+/// Reloads the USB ID database from `path`, or from the `usb.ids` next to
+/// the app when no path is given. The monitor picks the new names up on
+/// its next tick.
+#[tauri::command]
+pub fn reload_usb_ids(path: Option<String>) -> Result<UsbIdsInfo, String> {
+    let ids = usb_ids::reload(path.as_deref().map(Path::new))?;
+    Ok(UsbIdsInfo {
+        source: ids.source().to_string(),
+        vendors: ids.vendor_count(),
+        products: ids.product_count(),
+    })
+}
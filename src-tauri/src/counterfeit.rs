@@ -0,0 +1,295 @@
+use crate::types::StorageInfo;
+
+/// Marketed capacities are decimal and flash comes in powers of two;
+/// usable space is what's left after spare blocks and firmware, or a
+/// little over when the label was in GiB or TiB.
+const MIN_USABLE: f64 = 0.88;
+const MAX_USABLE: f64 = 1.1;
+
+const GB: u64 = 1_000_000_000;
+
+/// Sizes no-name controllers are rarely honest about.
+const GENERIC_CLAIM_LIMIT: u64 = 512 * GB;
+
+/// Product strings of generic controllers that fake-capacity sticks
+/// ship with instead of a brand.
+const GENERIC_MODELS: &[&str] = &[
+    "usb flash disk",
+    "usb flash drive",
+    "usb disk",
+    "udisk",
+    "flash disk",
+    "mass storage",
+    "generic",
+];
+
+/// Serials shared by whole batches of cheap controllers.
+const BOGUS_SERIALS: &[&str] = &[
+    "0123456789ABCDEF",
+    "123456789ABCDEF",
+    "0123456789",
+    "1234567890",
+    "AA00000000000489",
+    "DEADBEEF",
+];
+
+/// Heuristics for counterfeit and fake-capacity drives. `sizes_seen`
+/// holds every capacity reported under the same serial before, by this
+/// device or another. Each finding is a sentence for the UI; none of them
+/// proves anything on its own.
+pub fn analyze(info: &StorageInfo, sizes_seen: &[u64]) -> Vec<String> {
+    let mut findings = Vec::new();
+    let total = info.total_bytes;
+
+    // Hard disks come in sizes that aren't powers of two, and Windows
+    // doesn't say which drives spin; flash sticks and cards report as
+    // removable. Sticks under a gigabyte predate the fakes.
+    if total >= GB && info.removable && !info.rotational {
+        let nominal = nominal_size(total);
+        if !plausible(total, nominal) {
+            findings.push(format!(
+                "Reported capacity ({}) isn't close to any flash size",
+                decimal(total)
+            ));
+        }
+        if let Some(claimed) = model_capacity(&info.model) {
+            if !plausible(total, claimed) {
+                findings.push(format!(
+                    "Model says {} but the drive reports {}",
+                    decimal(claimed),
+                    decimal(total)
+                ));
+            }
+        }
+        if total >= GENERIC_CLAIM_LIMIT && is_generic(&info.model) {
+            findings.push(format!(
+                "Claims {} with a generic controller ({})",
+                decimal(total),
+                info.model
+            ));
+        }
+    }
+
+    if total > 0 {
+        if let Some((p, end)) = info
+            .partitions
+            .iter()
+            .map(|p| (p, p.start_bytes.saturating_add(p.size_bytes)))
+            .find(|(_, end)| *end > total)
+        {
+            findings.push(format!(
+                "Partition {} ends at {}, past the end of the drive",
+                p.number,
+                decimal(end)
+            ));
+        }
+    }
+
+    if is_bogus_serial(&info.serial_number) {
+        findings.push(format!(
+            "Serial {} is shared by many cheap drives",
+            info.serial_number
+        ));
+    }
+
+    let mut others: Vec<u64> = sizes_seen
+        .iter()
+        .copied()
+        .filter(|s| *s > 0 && *s != total)
+        .collect();
+    others.sort_unstable();
+    others.dedup();
+    if total > 0 && !others.is_empty() {
+        findings.push(format!(
+            "Same serial has been seen as {}",
+            others
+                .iter()
+                .map(|s| decimal(*s))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    findings
+}
+
+fn plausible(total: u64, nominal: u64) -> bool {
+    (MIN_USABLE..=MAX_USABLE).contains(&(total as f64 / nominal as f64))
+}
+
+/// The power-of-two decimal size the drive would be sold as.
+fn nominal_size(bytes: u64) -> u64 {
+    let mut size = GB;
+    while (size as f64) * MAX_USABLE < bytes as f64 {
+        size *= 2;
+    }
+    size
+}
+
+/// A capacity named in the model string, like "SanDisk 64GB" or
+/// "Flash Drive 2 TB". One too big to count in bytes is no claim at all.
+fn model_capacity(model: &str) -> Option<u64> {
+    let upper = model.to_ascii_uppercase();
+    let words: Vec<&str> = upper.split([' ', '_', '-']).collect();
+    for (i, word) in words.iter().enumerate() {
+        let (digits, unit) = match word.find(|c: char| !c.is_ascii_digit()) {
+            Some(0) | None => (*word, words.get(i + 1).copied().unwrap_or("")),
+            Some(at) => word.split_at(at),
+        };
+        let Ok(n) = digits.parse::<u64>() else {
+            continue;
+        };
+        match unit {
+            "GB" | "G" => return n.checked_mul(GB),
+            "TB" | "T" => return n.checked_mul(1000 * GB),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn is_generic(model: &str) -> bool {
+    let lower = model.to_lowercase();
+    lower.trim().is_empty() || GENERIC_MODELS.iter().any(|g| lower.contains(g))
+}
+
+fn is_bogus_serial(serial: &str) -> bool {
+    let serial = serial.trim().to_ascii_uppercase();
+    if serial.is_empty() {
+        return false;
+    }
+    let mut chars = serial.chars();
+    let first = chars.next();
+    serial.len() < 4 || chars.all(|c| Some(c) == first) || BOGUS_SERIALS.contains(&serial.as_str())
+}
+
+/// Sizes the way they're printed on the packaging.
+fn decimal(bytes: u64) -> String {
+    if bytes >= 1000 * GB {
+        format!("{:.1} TB", bytes as f64 / (1000 * GB) as f64)
+    } else {
+        format!("{:.1} GB", bytes as f64 / GB as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::PartitionInfo;
+
+    /// A removable flash drive reporting `total_bytes`.
+    fn stick(model: &str, total_bytes: u64) -> StorageInfo {
+        StorageInfo {
+            model: model.to_string(),
+            serial_number: "4C530001230913117325".to_string(),
+            total_bytes,
+            removable: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn honest_drives_have_no_findings() {
+        // 64 GB sold, 61.5 GB usable; 128 GiB is a little over 128 GB.
+        assert!(analyze(&stick("SanDisk Ultra 64GB", 61_530_439_680), &[]).is_empty());
+        assert!(analyze(&stick("Kingston DataTraveler", 137_438_953_472), &[]).is_empty());
+        // Hard disks come in any size.
+        let disk = StorageInfo {
+            rotational: true,
+            ..stick("WD Elements", 3_000_592_982_016)
+        };
+        assert!(analyze(&disk, &[]).is_empty());
+    }
+
+    #[test]
+    fn fake_capacity() {
+        let findings = analyze(&stick("USB Flash Disk 2TB", 2_048_000_000_000), &[]);
+        assert_eq!(
+            findings,
+            ["Claims 2.0 TB with a generic controller (USB Flash Disk 2TB)"]
+        );
+
+        let findings = analyze(&stick("Ultra 256 GB", 31_000_000_000), &[]);
+        assert_eq!(
+            findings,
+            ["Model says 256.0 GB but the drive reports 31.0 GB"]
+        );
+
+        let findings = analyze(&stick("Stick", 45_000_000_000), &[]);
+        assert_eq!(
+            findings,
+            ["Reported capacity (45.0 GB) isn't close to any flash size"]
+        );
+    }
+
+    #[test]
+    fn model_capacities() {
+        assert_eq!(model_capacity("SanDisk 64GB"), Some(64 * GB));
+        assert_eq!(model_capacity("Flash Drive 2 TB"), Some(2000 * GB));
+        assert_eq!(model_capacity("CRUZER_16G"), Some(16 * GB));
+        assert_eq!(model_capacity("Extreme-1t"), Some(1000 * GB));
+        assert_eq!(model_capacity("USB 3.0"), None);
+        assert_eq!(model_capacity(""), None);
+        // Too big for a u64 of bytes.
+        assert_eq!(model_capacity("Mega 99999999999GB"), None);
+        assert_eq!(model_capacity("Mega 18446744074 GB"), None);
+        assert_eq!(model_capacity("Mega 18446745TB"), None);
+        assert_eq!(
+            model_capacity("Mega 18446744TB"),
+            Some(18_446_744 * 1000 * GB)
+        );
+
+        let findings = analyze(&stick("Mega 99999999999999GB", 64_000_000_000), &[]);
+        assert!(findings.is_empty(), "{:?}", findings);
+    }
+
+    #[test]
+    fn partitions_past_the_end() {
+        let mut info = stick("SanDisk Ultra 64GB", 61_530_439_680);
+        info.partitions = vec![
+            PartitionInfo {
+                number: 1,
+                start_bytes: 1 << 20,
+                size_bytes: 61_000_000_000,
+                ..Default::default()
+            },
+            PartitionInfo {
+                number: 2,
+                start_bytes: 61_500_000_000,
+                size_bytes: 2_000_000_000,
+                ..Default::default()
+            },
+        ];
+        assert_eq!(
+            analyze(&info, &[]),
+            ["Partition 2 ends at 63.5 GB, past the end of the drive"]
+        );
+
+        // A corrupt table can put the end past what a u64 holds.
+        info.partitions[1].start_bytes = u64::MAX - 10;
+        assert_eq!(
+            analyze(&info, &[]),
+            ["Partition 2 ends at 18446744.1 TB, past the end of the drive"]
+        );
+    }
+
+    #[test]
+    fn serials() {
+        for serial in ["0123456789ABCDEF", "deadbeef", "000000000000", "AB1"] {
+            let info = StorageInfo {
+                serial_number: serial.to_string(),
+                ..stick("SanDisk Ultra 64GB", 61_530_439_680)
+            };
+            assert_eq!(
+                analyze(&info, &[]),
+                [format!("Serial {} is shared by many cheap drives", serial)]
+            );
+        }
+        assert!(!is_bogus_serial(""));
+
+        let info = stick("SanDisk Ultra 64GB", 61_530_439_680);
+        assert_eq!(
+            analyze(&info, &[0, 61_530_439_680, 7_751_073_792, 7_751_073_792]),
+            ["Same serial has been seen as 7.8 GB"]
+        );
+    }
+}
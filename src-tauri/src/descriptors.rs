@@ -0,0 +1,989 @@
+use crate::usb_ids::UsbIds;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+#[cfg(not(windows))]
+use std::path::Path;
+
+const DEVICE: u8 = 0x01;
+const CONFIGURATION: u8 = 0x02;
+const STRING: u8 = 0x03;
+const INTERFACE: u8 = 0x04;
+const ENDPOINT: u8 = 0x05;
+const BOS: u8 = 0x0F;
+const DEVICE_CAPABILITY: u8 = 0x10;
+const SS_ENDPOINT_COMPANION: u8 = 0x30;
+
+#[cfg(not(windows))]
+const SYSFS_DEVICES: &str = "/sys/bus/usb/devices";
+
+// ── Descriptor types ───────────────────────────────────────────
+
+/// Everything a device reports about itself, decoded from the raw
+/// descriptor bytes.
+#[derive(Clone, Debug, Serialize)]
+pub struct DeviceDescriptors {
+    pub device: DeviceDescriptor,
+    pub configurations: Vec<ConfigDescriptor>,
+    pub bos: Option<Bos>,
+    /// String descriptors by index. Index 0 (the language table) is never
+    /// stored.
+    pub strings: BTreeMap<u8, String>,
+    /// Offset of a descriptor whose `bLength` is under 2 or runs past the
+    /// end of the blob. It and everything after it are left out.
+    pub truncated_at: Option<usize>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DeviceDescriptor {
+    pub bcd_usb: u16,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+    pub max_packet_size0: u8,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub bcd_device: u16,
+    pub i_manufacturer: u8,
+    pub i_product: u8,
+    pub i_serial: u8,
+    pub num_configurations: u8,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ConfigDescriptor {
+    pub total_length: u16,
+    pub num_interfaces: u8,
+    pub value: u8,
+    pub i_configuration: u8,
+    pub attributes: u8,
+    /// `bMaxPower` scaled to mA: 2 mA units, or 8 mA on SuperSpeed.
+    pub max_power_ma: u32,
+    pub interfaces: Vec<InterfaceDescriptor>,
+    /// Descriptors between the configuration and its first interface,
+    /// such as interface associations.
+    pub extra: Vec<RawDescriptor>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct InterfaceDescriptor {
+    pub number: u8,
+    pub alternate: u8,
+    pub num_endpoints: u8,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+    pub i_interface: u8,
+    pub endpoints: Vec<EndpointDescriptor>,
+    /// Class-specific descriptors (HID, audio, CDC...) left undecoded.
+    pub extra: Vec<RawDescriptor>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct EndpointDescriptor {
+    pub address: u8,
+    pub attributes: u8,
+    pub max_packet_size: u16,
+    pub interval: u8,
+    pub companion: Option<SsCompanion>,
+}
+
+/// SuperSpeed endpoint companion.
+#[derive(Clone, Debug, Serialize)]
+pub struct SsCompanion {
+    pub max_burst: u8,
+    pub attributes: u8,
+    pub bytes_per_interval: u16,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RawDescriptor {
+    pub descriptor_type: u8,
+    pub data: Vec<u8>,
+}
+
+/// Binary Object Store: the capabilities a USB 2.1+ device advertises.
+#[derive(Clone, Debug, Serialize)]
+pub struct Bos {
+    pub capabilities: Vec<Capability>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Capability {
+    Usb2Extension {
+        attributes: u32,
+    },
+    SuperSpeed {
+        attributes: u8,
+        speeds_supported: u16,
+        functionality_support: u8,
+        u1_exit_latency: u8,
+        u2_exit_latency: u16,
+    },
+    ContainerId {
+        uuid: String,
+    },
+    SuperSpeedPlus {
+        attributes: u32,
+        functionality_support: u16,
+        sublink_speeds: Vec<u32>,
+    },
+    Other {
+        capability_type: u8,
+        data: Vec<u8>,
+    },
+}
+
+// ── Parsing ────────────────────────────────────────────────────
+
+/// Decodes a descriptor blob laid out like sysfs `descriptors`: the
+/// device descriptor, then every configuration with its interfaces and
+/// endpoints. A BOS and string descriptors may follow; captured fixtures
+/// append the strings in index order, starting with the language table.
+/// A blob that's cut short keeps what came before the cut.
+pub fn parse(bytes: &[u8]) -> Result<DeviceDescriptors, String> {
+    let (chunks, truncated_at) = split(bytes);
+    let mut chunks = chunks.into_iter();
+    let device = match chunks.next() {
+        Some((DEVICE, d)) if d.len() >= 18 => parse_device(d),
+        _ => return Err("Doesn't start with a device descriptor".to_string()),
+    };
+
+    let mut descriptors = DeviceDescriptors {
+        device,
+        configurations: Vec::new(),
+        bos: None,
+        strings: BTreeMap::new(),
+        truncated_at,
+    };
+    let superspeed = descriptors.device.bcd_usb >= 0x0300;
+    let mut string_index = 0u8;
+    for (kind, d) in chunks {
+        match kind {
+            CONFIGURATION if d.len() >= 9 => {
+                descriptors.configurations.push(ConfigDescriptor {
+                    total_length: u16_at(d, 2),
+                    num_interfaces: d[4],
+                    value: d[5],
+                    i_configuration: d[6],
+                    attributes: d[7],
+                    max_power_ma: d[8] as u32 * if superspeed { 8 } else { 2 },
+                    interfaces: Vec::new(),
+                    extra: Vec::new(),
+                });
+            }
+            INTERFACE if d.len() >= 9 => {
+                let Some(config) = descriptors.configurations.last_mut() else {
+                    return Err("Interface descriptor outside a configuration".to_string());
+                };
+                config.interfaces.push(InterfaceDescriptor {
+                    number: d[2],
+                    alternate: d[3],
+                    num_endpoints: d[4],
+                    class: d[5],
+                    subclass: d[6],
+                    protocol: d[7],
+                    i_interface: d[8],
+                    endpoints: Vec::new(),
+                    extra: Vec::new(),
+                });
+            }
+            ENDPOINT if d.len() >= 7 => {
+                let Some(iface) = current_interface(&mut descriptors) else {
+                    return Err("Endpoint descriptor outside an interface".to_string());
+                };
+                iface.endpoints.push(EndpointDescriptor {
+                    address: d[2],
+                    attributes: d[3],
+                    max_packet_size: u16_at(d, 4),
+                    interval: d[6],
+                    companion: None,
+                });
+            }
+            SS_ENDPOINT_COMPANION if d.len() >= 6 => {
+                let endpoint = current_interface(&mut descriptors)
+                    .and_then(|iface| iface.endpoints.last_mut());
+                if let Some(endpoint) = endpoint {
+                    endpoint.companion = Some(SsCompanion {
+                        max_burst: d[2],
+                        attributes: d[3],
+                        bytes_per_interval: u16_at(d, 4),
+                    });
+                }
+            }
+            BOS => {
+                descriptors.bos = Some(Bos {
+                    capabilities: Vec::new(),
+                })
+            }
+            DEVICE_CAPABILITY if d.len() >= 3 => {
+                if let Some(bos) = &mut descriptors.bos {
+                    bos.capabilities.push(parse_capability(d));
+                }
+            }
+            STRING => {
+                if string_index > 0 {
+                    descriptors.strings.insert(string_index, decode_string(d));
+                }
+                string_index = string_index.saturating_add(1);
+            }
+            _ => {
+                let raw = RawDescriptor {
+                    descriptor_type: kind,
+                    data: d.to_vec(),
+                };
+                if let Some(iface) = current_interface(&mut descriptors) {
+                    iface.extra.push(raw);
+                } else if let Some(config) = descriptors.configurations.last_mut() {
+                    config.extra.push(raw);
+                }
+            }
+        }
+    }
+    Ok(descriptors)
+}
+
+/// Splits a blob on `bLength` into (bDescriptorType, whole descriptor),
+/// stopping at the first one that doesn't fit and returning its offset.
+fn split(bytes: &[u8]) -> (Vec<(u8, &[u8])>, Option<usize>) {
+    let mut chunks = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let len = bytes[offset] as usize;
+        if len < 2 || offset + len > bytes.len() {
+            return (chunks, Some(offset));
+        }
+        chunks.push((bytes[offset + 1], &bytes[offset..offset + len]));
+        offset += len;
+    }
+    (chunks, None)
+}
+
+fn parse_device(d: &[u8]) -> DeviceDescriptor {
+    DeviceDescriptor {
+        bcd_usb: u16_at(d, 2),
+        class: d[4],
+        subclass: d[5],
+        protocol: d[6],
+        max_packet_size0: d[7],
+        vendor_id: u16_at(d, 8),
+        product_id: u16_at(d, 10),
+        bcd_device: u16_at(d, 12),
+        i_manufacturer: d[14],
+        i_product: d[15],
+        i_serial: d[16],
+        num_configurations: d[17],
+    }
+}
+
+fn parse_capability(d: &[u8]) -> Capability {
+    match d[2] {
+        0x02 if d.len() >= 7 => Capability::Usb2Extension {
+            attributes: u32_at(d, 3),
+        },
+        0x03 if d.len() >= 10 => Capability::SuperSpeed {
+            attributes: d[3],
+            speeds_supported: u16_at(d, 4),
+            functionality_support: d[6],
+            u1_exit_latency: d[7],
+            u2_exit_latency: u16_at(d, 8),
+        },
+        0x04 if d.len() >= 20 => Capability::ContainerId {
+            uuid: format_uuid(&d[4..20]),
+        },
+        0x0A if d.len() >= 12 => {
+            let attributes = u32_at(d, 4);
+            // bmAttributes[4:0] is the sublink speed attribute count - 1.
+            let count = (attributes & 0x1F) as usize + 1;
+            Capability::SuperSpeedPlus {
+                attributes,
+                functionality_support: u16_at(d, 8),
+                sublink_speeds: (0..count)
+                    .map(|i| 12 + i * 4)
+                    .filter(|at| at + 4 <= d.len())
+                    .map(|at| u32_at(d, at))
+                    .collect(),
+            }
+        }
+        kind => Capability::Other {
+            capability_type: kind,
+            data: d[3..].to_vec(),
+        },
+    }
+}
+
+/// The interface descriptors that follow belong to, if any.
+fn current_interface(descriptors: &mut DeviceDescriptors) -> Option<&mut InterfaceDescriptor> {
+    descriptors.configurations.last_mut()?.interfaces.last_mut()
+}
+
+/// String descriptors are UTF-16LE after the two header bytes.
+fn decode_string(d: &[u8]) -> String {
+    let units: Vec<u16> = d[2..]
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn u16_at(d: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([d[at], d[at + 1]])
+}
+
+fn u32_at(d: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([d[at], d[at + 1], d[at + 2], d[at + 3]])
+}
+
+/// Container IDs are stored as a little-endian GUID.
+fn format_uuid(b: &[u8]) -> String {
+    format!(
+        "{{{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{}}}",
+        u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        u16::from_le_bytes([b[4], b[5]]),
+        u16::from_le_bytes([b[6], b[7]]),
+        b[8],
+        b[9],
+        b[10..16]
+            .iter()
+            .map(|x| format!("{:02x}", x))
+            .collect::<String>()
+    )
+}
+
+// ── Reading from sysfs ─────────────────────────────────────────
+
+/// Reads the descriptors of the attached device at `location` (`1-2.3`).
+#[cfg(not(windows))]
+pub fn read_device(location: &str) -> Result<DeviceDescriptors, String> {
+    read_sysfs(&Path::new(SYSFS_DEVICES).join(location))
+}
+
+/// Windows only hands out descriptors through a driver IOCTL on the
+/// parent hub, which the app doesn't open.
+#[cfg(windows)]
+pub fn read_device(_location: &str) -> Result<DeviceDescriptors, String> {
+    Err("Raw descriptors are only available on Linux".to_string())
+}
+
+/// Parses `descriptors` and `bos_descriptors` from a sysfs device
+/// directory. Sysfs has no raw string descriptors, so the strings come
+/// from the attributes the kernel decoded them into.
+#[cfg(not(windows))]
+fn read_sysfs(dir: &Path) -> Result<DeviceDescriptors, String> {
+    let path = dir.join("descriptors");
+    let mut bytes =
+        std::fs::read(&path).map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+    if let Ok(bos) = std::fs::read(dir.join("bos_descriptors")) {
+        bytes.extend(bos);
+    }
+    let mut descriptors = parse(&bytes)?;
+
+    let read = |dir: &Path, attr: &str| {
+        std::fs::read_to_string(dir.join(attr))
+            .ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    };
+    let device = &descriptors.device;
+    let mut strings: Vec<(u8, String)> = [
+        (device.i_manufacturer, "manufacturer"),
+        (device.i_product, "product"),
+        (device.i_serial, "serial"),
+    ]
+    .into_iter()
+    .filter_map(|(index, attr)| Some((index, read(dir, attr)?)))
+    .collect();
+
+    // Only the active configuration and its current alternate settings
+    // have their strings exposed.
+    let active: Option<u8> = read(dir, "bConfigurationValue").and_then(|v| v.parse().ok());
+    if let Some(config) = descriptors
+        .configurations
+        .iter()
+        .find(|c| Some(c.value) == active)
+    {
+        if let Some(name) = read(dir, "configuration") {
+            strings.push((config.i_configuration, name));
+        }
+        let kernel_name = dir.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        for iface in &config.interfaces {
+            let iface_dir = dir.join(format!("{}:{}.{}", kernel_name, config.value, iface.number));
+            let alternate: Option<u8> =
+                read(&iface_dir, "bAlternateSetting").and_then(|a| a.parse().ok());
+            if alternate == Some(iface.alternate) {
+                if let Some(name) = read(&iface_dir, "interface") {
+                    strings.push((iface.i_interface, name));
+                }
+            }
+        }
+    }
+
+    for (index, name) in strings {
+        if index != 0 {
+            descriptors.strings.insert(index, name);
+        }
+    }
+    Ok(descriptors)
+}
+
+// ── lsusb -v style text ────────────────────────────────────────
+
+/// Renders the descriptors the way `lsusb -v` does, with names from the
+/// USB ID database.
+pub fn describe(d: &DeviceDescriptors, ids: &UsbIds) -> String {
+    let mut out = String::new();
+    let string = |index: u8| {
+        d.strings
+            .get(&index)
+            .map(String::as_str)
+            .unwrap_or_default()
+    };
+    let dev = &d.device;
+
+    out.push_str("Device Descriptor:\n");
+    let i = "  ";
+    field(&mut out, i, "bcdUSB", bcd(dev.bcd_usb), "");
+    field(
+        &mut out,
+        i,
+        "bDeviceClass",
+        dev.class,
+        &class_name(ids, dev.class, dev.subclass),
+    );
+    field(&mut out, i, "bDeviceSubClass", dev.subclass, "");
+    field(&mut out, i, "bDeviceProtocol", dev.protocol, "");
+    field(&mut out, i, "bMaxPacketSize0", dev.max_packet_size0, "");
+    field(
+        &mut out,
+        i,
+        "idVendor",
+        format!("0x{:04x}", dev.vendor_id),
+        ids.vendor(dev.vendor_id).unwrap_or_default(),
+    );
+    field(
+        &mut out,
+        i,
+        "idProduct",
+        format!("0x{:04x}", dev.product_id),
+        ids.product(dev.vendor_id, dev.product_id)
+            .unwrap_or_default(),
+    );
+    field(&mut out, i, "bcdDevice", bcd(dev.bcd_device), "");
+    field(
+        &mut out,
+        i,
+        "iManufacturer",
+        dev.i_manufacturer,
+        string(dev.i_manufacturer),
+    );
+    field(
+        &mut out,
+        i,
+        "iProduct",
+        dev.i_product,
+        string(dev.i_product),
+    );
+    field(&mut out, i, "iSerial", dev.i_serial, string(dev.i_serial));
+    field(
+        &mut out,
+        i,
+        "bNumConfigurations",
+        dev.num_configurations,
+        "",
+    );
+
+    for config in &d.configurations {
+        let i = "    ";
+        out.push_str("  Configuration Descriptor:\n");
+        field(
+            &mut out,
+            i,
+            "wTotalLength",
+            format!("0x{:04x}", config.total_length),
+            "",
+        );
+        field(&mut out, i, "bNumInterfaces", config.num_interfaces, "");
+        field(&mut out, i, "bConfigurationValue", config.value, "");
+        field(
+            &mut out,
+            i,
+            "iConfiguration",
+            config.i_configuration,
+            string(config.i_configuration),
+        );
+        field(
+            &mut out,
+            i,
+            "bmAttributes",
+            format!("0x{:02x}", config.attributes),
+            "",
+        );
+        if config.attributes & 0x40 != 0 {
+            out.push_str("      Self Powered\n");
+        } else {
+            out.push_str("      (Bus Powered)\n");
+        }
+        if config.attributes & 0x20 != 0 {
+            out.push_str("      Remote Wakeup\n");
+        }
+        field(
+            &mut out,
+            i,
+            "MaxPower",
+            format!("{}mA", config.max_power_ma),
+            "",
+        );
+        raw(&mut out, "    ", &config.extra);
+
+        for iface in &config.interfaces {
+            let i = "      ";
+            out.push_str("    Interface Descriptor:\n");
+            field(&mut out, i, "bInterfaceNumber", iface.number, "");
+            field(&mut out, i, "bAlternateSetting", iface.alternate, "");
+            field(&mut out, i, "bNumEndpoints", iface.num_endpoints, "");
+            field(
+                &mut out,
+                i,
+                "bInterfaceClass",
+                iface.class,
+                &class_name(ids, iface.class, iface.subclass),
+            );
+            field(&mut out, i, "bInterfaceSubClass", iface.subclass, "");
+            field(&mut out, i, "bInterfaceProtocol", iface.protocol, "");
+            field(
+                &mut out,
+                i,
+                "iInterface",
+                iface.i_interface,
+                string(iface.i_interface),
+            );
+            raw(&mut out, "      ", &iface.extra);
+
+            for ep in &iface.endpoints {
+                endpoint(&mut out, ep);
+            }
+        }
+    }
+
+    if let Some(bos) = &d.bos {
+        out.push_str("Binary Object Store Descriptor:\n");
+        field(&mut out, "  ", "bNumDeviceCaps", bos.capabilities.len(), "");
+        for cap in &bos.capabilities {
+            capability(&mut out, cap);
+        }
+    }
+    if let Some(offset) = d.truncated_at {
+        let _ = writeln!(
+            out,
+            "** TRUNCATED: descriptor at byte {} is cut short",
+            offset
+        );
+    }
+    out
+}
+
+/// One `name  value note` line, lined up like lsusb's columns.
+fn field(out: &mut String, indent: &str, name: &str, value: impl std::fmt::Display, note: &str) {
+    let line = format!("{}{:<20}{:>6} {}", indent, name, value.to_string(), note);
+    out.push_str(line.trim_end());
+    out.push('\n');
+}
+
+fn bcd(v: u16) -> String {
+    format!("{:x}.{:02x}", v >> 8, v & 0xFF)
+}
+
+fn class_name(ids: &UsbIds, class: u8, subclass: u8) -> String {
+    match class {
+        0x00 => "(Defined at Interface level)".to_string(),
+        _ => ids.class_name(class, subclass).unwrap_or_default(),
+    }
+}
+
+fn endpoint(out: &mut String, ep: &EndpointDescriptor) {
+    let i = "        ";
+    let direction = if ep.address & 0x80 != 0 { "IN" } else { "OUT" };
+    out.push_str("      Endpoint Descriptor:\n");
+    field(
+        out,
+        i,
+        "bEndpointAddress",
+        format!("0x{:02x}", ep.address),
+        &format!(" EP {} {}", ep.address & 0x0F, direction),
+    );
+    field(out, i, "bmAttributes", ep.attributes, "");
+    let transfer = ["Control", "Isochronous", "Bulk", "Interrupt"][(ep.attributes & 0x03) as usize];
+    let _ = writeln!(out, "{}  Transfer Type          {}", i, transfer);
+    if ep.attributes & 0x03 == 0x01 {
+        let sync = ["None", "Asynchronous", "Adaptive", "Synchronous"];
+        let usage = ["Data", "Feedback", "Implicit feedback Data", "Reserved"];
+        let _ = writeln!(
+            out,
+            "{}  Synch Type             {}",
+            i,
+            sync[((ep.attributes >> 2) & 0x03) as usize]
+        );
+        let _ = writeln!(
+            out,
+            "{}  Usage Type             {}",
+            i,
+            usage[((ep.attributes >> 4) & 0x03) as usize]
+        );
+    }
+    // wMaxPacketSize[12:11] is the number of extra transactions per
+    // microframe for high-bandwidth endpoints.
+    let size = ep.max_packet_size & 0x07FF;
+    let per_frame = ((ep.max_packet_size >> 11) & 0x03) + 1;
+    field(
+        out,
+        i,
+        "wMaxPacketSize",
+        format!("0x{:04x}", ep.max_packet_size),
+        &format!(" {}x {} bytes", per_frame, size),
+    );
+    field(out, i, "bInterval", ep.interval, "");
+    if let Some(c) = &ep.companion {
+        field(out, i, "bMaxBurst", c.max_burst, "");
+        // Bulk endpoints put log2(MaxStreams) in bmAttributes[4:0].
+        let streams = c.attributes & 0x1F;
+        if ep.attributes & 0x03 == 0x02 && streams > 0 {
+            field(out, i, "MaxStreams", 1u32 << streams, "");
+        }
+        field(
+            out,
+            i,
+            "wBytesPerInterval",
+            format!("0x{:04x}", c.bytes_per_interval),
+            "",
+        );
+    }
+}
+
+fn capability(out: &mut String, cap: &Capability) {
+    let i = "    ";
+    match cap {
+        Capability::Usb2Extension { attributes } => {
+            out.push_str("  USB 2.0 Extension Device Capability:\n");
+            field(out, i, "bmAttributes", format!("0x{:08x}", attributes), "");
+            if attributes & 0x02 != 0 {
+                out.push_str("      Link Power Management (LPM) Supported\n");
+            }
+        }
+        Capability::SuperSpeed {
+            attributes,
+            speeds_supported,
+            functionality_support,
+            u1_exit_latency,
+            u2_exit_latency,
+        } => {
+            out.push_str("  SuperSpeed USB Device Capability:\n");
+            field(out, i, "bmAttributes", format!("0x{:02x}", attributes), "");
+            if attributes & 0x02 != 0 {
+                out.push_str("      Latency Tolerance Messages (LTM) Supported\n");
+            }
+            field(
+                out,
+                i,
+                "wSpeedsSupported",
+                format!("0x{:04x}", speeds_supported),
+                "",
+            );
+            let speeds = [
+                "Low Speed (1Mbps)",
+                "Full Speed (12Mbps)",
+                "High Speed (480Mbps)",
+                "SuperSpeed (5Gbps)",
+            ];
+            for (bit, name) in speeds.iter().enumerate() {
+                if speeds_supported & (1 << bit) != 0 {
+                    let _ = writeln!(out, "      Device can operate at {}", name);
+                }
+            }
+            field(out, i, "bFunctionalitySupport", functionality_support, "");
+            field(out, i, "bU1DevExitLat", u1_exit_latency, "micro seconds");
+            field(out, i, "bU2DevExitLat", u2_exit_latency, "micro seconds");
+        }
+        Capability::ContainerId { uuid } => {
+            out.push_str("  Container ID Device Capability:\n");
+            let _ = writeln!(out, "{}ContainerID          {}", i, uuid);
+        }
+        Capability::SuperSpeedPlus {
+            attributes,
+            functionality_support,
+            sublink_speeds,
+        } => {
+            out.push_str("  SuperSpeedPlus USB Device Capability:\n");
+            field(out, i, "bmAttributes", format!("0x{:08x}", attributes), "");
+            field(
+                out,
+                i,
+                "wFunctionalitySupport",
+                format!("0x{:04x}", functionality_support),
+                "",
+            );
+            for speed in sublink_speeds {
+                field(out, i, "bmSublinkSpeedAttr", format!("0x{:08x}", speed), "");
+            }
+        }
+        Capability::Other {
+            capability_type,
+            data,
+        } => {
+            let _ = writeln!(
+                out,
+                "  ** UNRECOGNIZED: capability 0x{:02x}: {}",
+                capability_type,
+                hex(data)
+            );
+        }
+    }
+}
+
+/// Class-specific and other undecoded descriptors, as a hex dump.
+fn raw(out: &mut String, indent: &str, descriptors: &[RawDescriptor]) {
+    for d in descriptors {
+        let name = match d.descriptor_type {
+            0x0B => "Interface Association",
+            0x21 => "HID Device",
+            0x24 => "Class-specific Interface",
+            0x25 => "Class-specific Endpoint",
+            _ => "Unknown",
+        };
+        let _ = writeln!(
+            out,
+            "{}{} Descriptor (0x{:02x}): {}",
+            indent,
+            name,
+            d.descriptor_type,
+            hex(&d.data)
+        );
+    }
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPOSITE: &[u8] = include_bytes!("../tests/fixtures/descriptors-composite.bin");
+    const TRUNCATED_CONFIG: &[u8] =
+        include_bytes!("../tests/fixtures/descriptors-truncated-config.bin");
+    const ZERO_LENGTH: &[u8] = include_bytes!("../tests/fixtures/descriptors-zero-length.bin");
+
+    fn ids() -> UsbIds {
+        UsbIds::parse(
+            "1209  Generic\n\
+             \t2301  Composite Gadget\n\
+             C 02  Communications\n\
+             \t02  Abstract (modem)\n\
+             C 03  Human Interface Device\n\
+             \t01  Boot Interface Subclass\n\
+             C 0a  CDC Data\n\
+             \t00  Unused\n",
+            "test",
+        )
+    }
+
+    #[test]
+    fn composite_device() {
+        let d = parse(COMPOSITE).unwrap();
+        assert_eq!((d.device.vendor_id, d.device.product_id), (0x1209, 0x2301));
+        assert_eq!(d.device.bcd_usb, 0x0201);
+        assert_eq!(d.device.class, 0xEF);
+        assert_eq!(d.truncated_at, None);
+
+        let [config] = &d.configurations[..] else {
+            panic!("expected one configuration: {:?}", d.configurations);
+        };
+        assert_eq!(config.total_length, 86);
+        assert_eq!(config.max_power_ma, 100);
+        let association: Vec<u8> = config.extra.iter().map(|r| r.descriptor_type).collect();
+        assert_eq!(association, [0x0B]);
+
+        let shape: Vec<(u8, u8, Vec<u8>, Vec<u8>)> = config
+            .interfaces
+            .iter()
+            .map(|i| {
+                (
+                    i.number,
+                    i.class,
+                    i.endpoints.iter().map(|e| e.address).collect(),
+                    i.extra.iter().map(|r| r.descriptor_type).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            shape,
+            [
+                (0, 0x02, vec![0x81], vec![0x24]),
+                (1, 0x0A, vec![0x02, 0x82], vec![]),
+                (2, 0x03, vec![0x83], vec![0x21]),
+            ]
+        );
+        assert_eq!(config.interfaces[1].endpoints[0].max_packet_size, 512);
+
+        let caps = &d.bos.as_ref().unwrap().capabilities;
+        assert!(matches!(
+            caps[..],
+            [Capability::Usb2Extension { attributes: 0x02 }]
+        ));
+
+        let strings: Vec<(u8, &str)> = d.strings.iter().map(|(i, s)| (*i, s.as_str())).collect();
+        assert_eq!(
+            strings,
+            [
+                (1, "Acme"),
+                (2, "Composite Gadget"),
+                (3, "0001"),
+                (4, "CDC Serial"),
+                (5, "Keyboard"),
+            ]
+        );
+
+        let expected = "\
+Device Descriptor:
+  bcdUSB                2.01
+  bDeviceClass           239
+  bDeviceSubClass          2
+  bDeviceProtocol          1
+  bMaxPacketSize0         64
+  idVendor            0x1209 Generic
+  idProduct           0x2301 Composite Gadget
+  bcdDevice             1.00
+  iManufacturer            1 Acme
+  iProduct                 2 Composite Gadget
+  iSerial                  3 0001
+  bNumConfigurations       1
+  Configuration Descriptor:
+    wTotalLength        0x0056
+    bNumInterfaces           3
+    bConfigurationValue      1
+    iConfiguration           0
+    bmAttributes          0xa0
+      (Bus Powered)
+      Remote Wakeup
+    MaxPower             100mA
+    Interface Association Descriptor (0x0b): 08 0b 00 02 02 02 01 00
+    Interface Descriptor:
+      bInterfaceNumber         0
+      bAlternateSetting        0
+      bNumEndpoints            1
+      bInterfaceClass          2 Communications / Abstract (modem)
+      bInterfaceSubClass       2
+      bInterfaceProtocol       1
+      iInterface               4 CDC Serial
+      Class-specific Interface Descriptor (0x24): 05 24 00 10 01
+      Endpoint Descriptor:
+        bEndpointAddress      0x81  EP 1 IN
+        bmAttributes             3
+          Transfer Type          Interrupt
+        wMaxPacketSize      0x0008  1x 8 bytes
+        bInterval               16
+    Interface Descriptor:
+      bInterfaceNumber         1
+      bAlternateSetting        0
+      bNumEndpoints            2
+      bInterfaceClass         10 CDC Data
+      bInterfaceSubClass       0
+      bInterfaceProtocol       0
+      iInterface               0
+      Endpoint Descriptor:
+        bEndpointAddress      0x02  EP 2 OUT
+        bmAttributes             2
+          Transfer Type          Bulk
+        wMaxPacketSize      0x0200  1x 512 bytes
+        bInterval                0
+      Endpoint Descriptor:
+        bEndpointAddress      0x82  EP 2 IN
+        bmAttributes             2
+          Transfer Type          Bulk
+        wMaxPacketSize      0x0200  1x 512 bytes
+        bInterval                0
+    Interface Descriptor:
+      bInterfaceNumber         2
+      bAlternateSetting        0
+      bNumEndpoints            1
+      bInterfaceClass          3 Human Interface Device / Boot Interface Subclass
+      bInterfaceSubClass       1
+      bInterfaceProtocol       1
+      iInterface               5 Keyboard
+      HID Device Descriptor (0x21): 09 21 11 01 00 01 22 3f 00
+      Endpoint Descriptor:
+        bEndpointAddress      0x83  EP 3 IN
+        bmAttributes             3
+          Transfer Type          Interrupt
+        wMaxPacketSize      0x0008  1x 8 bytes
+        bInterval               10
+Binary Object Store Descriptor:
+  bNumDeviceCaps           1
+  USB 2.0 Extension Device Capability:
+    bmAttributes        0x00000002
+      Link Power Management (LPM) Supported
+";
+        assert_eq!(describe(&d, &ids()), expected);
+    }
+
+    #[test]
+    fn truncated_configuration_keeps_what_came_before() {
+        let d = parse(TRUNCATED_CONFIG).unwrap();
+        assert_eq!((d.device.vendor_id, d.device.product_id), (0x0781, 0x5581));
+        assert_eq!(d.truncated_at, Some(43));
+        let config = &d.configurations[0];
+        assert_eq!(config.total_length, 32);
+        let iface = &config.interfaces[0];
+        assert_eq!((iface.class, iface.num_endpoints), (0x08, 2));
+        let endpoints: Vec<u8> = iface.endpoints.iter().map(|e| e.address).collect();
+        assert_eq!(endpoints, [0x81]);
+
+        let text = describe(&d, &ids());
+        assert!(text.contains("        bEndpointAddress      0x81  EP 1 IN\n"));
+        assert!(!text.contains("EP 2 OUT"));
+        assert!(text.ends_with(
+            "        bInterval                0\n\
+             ** TRUNCATED: descriptor at byte 43 is cut short\n"
+        ));
+    }
+
+    #[test]
+    fn zero_length_descriptor_stops_the_walk() {
+        let d = parse(ZERO_LENGTH).unwrap();
+        assert_eq!((d.device.vendor_id, d.device.product_id), (0x046D, 0xC07C));
+        assert_eq!(d.truncated_at, Some(36));
+        let iface = &d.configurations[0].interfaces[0];
+        assert_eq!((iface.class, iface.protocol), (0x03, 0x02));
+        assert!(iface.endpoints.is_empty());
+
+        let text = describe(&d, &ids());
+        assert!(!text.contains("Endpoint Descriptor"));
+        assert!(text.ends_with(
+            "      iInterface               0\n\
+             ** TRUNCATED: descriptor at byte 36 is cut short\n"
+        ));
+    }
+
+    #[test]
+    fn needs_a_whole_device_descriptor() {
+        let err = "Doesn't start with a device descriptor";
+        assert_eq!(parse(&[]).unwrap_err(), err);
+        assert_eq!(parse(&[0, 1, 2]).unwrap_err(), err);
+        assert_eq!(parse(&COMPOSITE[..17]).unwrap_err(), err);
+        assert_eq!(parse(&COMPOSITE[18..]).unwrap_err(), err);
+    }
+
+    #[test]
+    fn interfaces_need_a_configuration() {
+        let mut blob = COMPOSITE[..18].to_vec();
+        blob.extend([9, INTERFACE, 0, 0, 0, 3, 0, 0, 0]);
+        assert_eq!(
+            parse(&blob).unwrap_err(),
+            "Interface descriptor outside a configuration"
+        );
+    }
+}
@@ -0,0 +1,1629 @@
+use crate::counterfeit;
+use crate::instance_id::DeviceInstanceId;
+use crate::power;
+use crate::rules::Rules;
+use crate::source::{DeviceMap, Hotplug};
+use crate::types::{
+    Attribute, Bus, DeviceCategory, DeviceEvent, DeviceInterface, DeviceSnapshot, EnrichStatus,
+    EventKind, HubPower, KnownDevice, KnownDeviceCache, LinkSpeed, OverBudget, SpeedSample,
+    StorageInfo, UsbDevice,
+};
+use crate::usb_ids::UsbIds;
+use crate::volumes;
+use chrono::{DateTime, Local, SecondsFormat, TimeDelta, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Give a freshly connected drive time to mount before enriching it.
+pub const ENRICH_DELAY: TimeDelta = TimeDelta::seconds(2);
+
+/// Wait after a lookup that found no volumes, doubling with every try up
+/// to `RETRY_MAX`. Drives that haven't mounted a minute after the first
+/// try are given up on.
+const RETRY_BASE: TimeDelta = TimeDelta::seconds(1);
+const RETRY_MAX: TimeDelta = TimeDelta::seconds(16);
+const ENRICH_TIMEOUT: TimeDelta = TimeDelta::seconds(60);
+
+/// How often a connected drive is looked up again once its first lookup
+/// has settled, to catch free space, relabels and reformats.
+const REFRESH_INTERVAL: TimeDelta = TimeDelta::seconds(60);
+
+/// A device that comes back this soon after leaving gets a reconnect
+/// event rather than a connect.
+const RECONNECT_WINDOW: TimeDelta = TimeDelta::seconds(10);
+
+/// Connections kept in each device's speed history.
+const SPEED_HISTORY_LEN: usize = 20;
+
+// ── Engine ─────────────────────────────────────────────────────
+
+/// What a single step of the engine changed.
+#[derive(Default, Debug)]
+pub struct Outcome {
+    /// New events, in order.
+    pub events: Vec<DeviceEvent>,
+    /// The attached set or a device's attributes changed.
+    pub devices_changed: bool,
+    /// Known-device records changed and should be persisted.
+    pub known_changed: bool,
+}
+
+impl Outcome {
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty() && !self.devices_changed && !self.known_changed
+    }
+
+    /// Folds a later step's outcome into this one.
+    pub fn merge(&mut self, later: Outcome) {
+        self.events.extend(later.events);
+        self.devices_changed |= later.devices_changed;
+        self.known_changed |= later.known_changed;
+    }
+}
+
+/// A storage device's lookups so far.
+struct Enrichment {
+    status: EnrichStatus,
+    attempts: u32,
+    /// When the first attempt was due. Retries stop `ENRICH_TIMEOUT` later.
+    since: DateTime<Local>,
+    /// When the next attempt or refresh is due.
+    due: Option<DateTime<Local>>,
+    running: bool,
+}
+
+impl Enrichment {
+    fn new(due: DateTime<Local>) -> Self {
+        Self {
+            status: EnrichStatus::Pending { attempts: 0 },
+            attempts: 0,
+            since: due,
+            due: Some(due),
+            running: false,
+        }
+    }
+
+    /// Still on the first lookup, before it succeeded or timed out.
+    fn settling(&self) -> bool {
+        matches!(self.status, EnrichStatus::Pending { .. })
+    }
+
+    /// Schedules the next attempt, or settles on `give_up` once the
+    /// backoff would run past the timeout.
+    fn retry(&mut self, now: DateTime<Local>, give_up: EnrichStatus) {
+        let doublings = self.attempts.saturating_sub(1).min(8);
+        let backoff = (RETRY_BASE * 2i32.pow(doublings)).min(RETRY_MAX);
+        if now + backoff > self.since + ENRICH_TIMEOUT {
+            self.status = give_up;
+            self.due = Some(now + REFRESH_INTERVAL);
+        } else {
+            self.due = Some(now + backoff);
+        }
+    }
+}
+
+/// The device diff state machine, free of I/O and of the system clock.
+///
+/// Drivers feed it hotplug notifications and enumerations along with the
+/// time they happened, and act on what comes back: emit the events, save
+/// the cache, run the storage lookups it asks for.
+pub struct Engine {
+    attached: DeviceMap,
+    known: HashMap<String, KnownDevice>,
+    storage: HashMap<String, StorageInfo>,
+    /// Lookup state of every attached storage device.
+    enrichment: HashMap<String, Enrichment>,
+    ids: Arc<UsbIds>,
+    rules: Arc<Rules>,
+    /// Power accounting for each hub in `attached`.
+    power: BTreeMap<String, HubPower>,
+    /// When devices that may still count as reconnecting left.
+    departed: HashMap<String, DateTime<Local>>,
+    /// When the source last started failing, while it still is.
+    down_since: Option<DateTime<Local>>,
+    /// Sequence number of the last event handed out.
+    seq: u64,
+}
+
+impl Engine {
+    /// Starts from the devices attached at launch. They're merged into
+    /// `known` without events, and any storage among them is due for
+    /// enrichment right away.
+    ///
+    /// Here and in every other entry point, the user's rules are applied
+    /// and the interface entries of a composite device are folded into
+    /// the device itself.
+    pub fn new(
+        known: HashMap<String, KnownDevice>,
+        attached: DeviceMap,
+        ids: Arc<UsbIds>,
+        rules: Arc<Rules>,
+        now: DateTime<Local>,
+    ) -> Self {
+        let mut engine = Self {
+            attached: DeviceMap::new(),
+            known,
+            storage: HashMap::new(),
+            enrichment: HashMap::new(),
+            ids,
+            rules,
+            power: BTreeMap::new(),
+            departed: HashMap::new(),
+            down_since: None,
+            seq: 0,
+        };
+        for dev in engine.known.values_mut() {
+            dev.currently_connected = false;
+        }
+        let attached = group_interfaces(engine.rules.apply_all(attached));
+        let now_iso = format_iso(now);
+        for (id, dev) in &attached {
+            let kd = engine.upsert_known(id, dev, &now_iso, 1);
+            kd.best_speed = kd.best_speed.max(dev.Speed);
+            if dev.is_storage() {
+                engine.enrichment.insert(id.clone(), Enrichment::new(now));
+            }
+        }
+        engine.power = power::hub_power(&attached);
+        engine.attached = attached;
+        engine
+    }
+
+    pub fn attached(&self) -> &DeviceMap {
+        &self.attached
+    }
+
+    pub fn known(&self) -> &HashMap<String, KnownDevice> {
+        &self.known
+    }
+
+    pub fn storage(&self) -> &HashMap<String, StorageInfo> {
+        &self.storage
+    }
+
+    pub fn usb_ids(&self) -> &Arc<UsbIds> {
+        &self.ids
+    }
+
+    pub fn rules(&self) -> &Arc<Rules> {
+        &self.rules
+    }
+
+    pub fn cache(&self) -> KnownDeviceCache {
+        KnownDeviceCache {
+            devices: self.known.clone(),
+            ..KnownDeviceCache::new()
+        }
+    }
+
+    /// Attached devices for the frontend, sorted by name.
+    pub fn snapshot(&self) -> Vec<DeviceSnapshot> {
+        let mut sorted: Vec<_> = self
+            .attached
+            .iter()
+            .map(|(id, d)| {
+                let mut snapshot = usb_to_snapshot(id, d, &self.ids);
+                snapshot.hub_power = self.power.get(id).cloned();
+                snapshot.enrichment = self.enrichment.get(id).map(|e| e.status.clone());
+                snapshot
+            })
+            .collect();
+        sorted.sort_by_cached_key(|s| s.name.to_lowercase());
+        sorted
+    }
+
+    /// Applies hotplug notifications in order, so a blip shorter than an
+    /// enumeration still yields its connect and disconnect.
+    ///
+    /// Interface entries are applied after the rest of the batch, so they
+    /// find a parent that arrives alongside them.
+    pub fn hotplug(&mut self, changes: Vec<Hotplug>, now: DateTime<Local>) -> Outcome {
+        let mut out = Outcome::default();
+        let (interfaces, devices): (Vec<_>, Vec<_>) = changes.into_iter().partition(|change| {
+            let id = match change {
+                Hotplug::Added { id, .. } | Hotplug::Removed { id } => id,
+            };
+            DeviceInstanceId::parse(id).interface.is_some()
+        });
+        for change in devices.into_iter().chain(interfaces) {
+            match change {
+                Hotplug::Added { id, mut device } => {
+                    self.rules.apply(&id, &mut device);
+                    if !self.attached.contains_key(&id)
+                        && self.add_interface(&id, &device, &mut out)
+                    {
+                        continue;
+                    }
+                    match self.attached.get(&id) {
+                        Some(old) if *old != *device => {
+                            out.events.extend(attribute_changes(&id, old, &device, now));
+                            self.update(&id, *device, &mut out);
+                        }
+                        Some(_) => {}
+                        None => self.connect(id, *device, now, &mut out),
+                    }
+                }
+                Hotplug::Removed { id } => {
+                    if self.attached.contains_key(&id) {
+                        self.disconnect(&id, now, &mut out);
+                    } else {
+                        self.remove_interface(&id, &mut out);
+                    }
+                }
+            }
+        }
+        self.refresh_power(now, &mut out);
+        self.numbered(out)
+    }
+
+    /// Diffs a full enumeration against the attached set. Disconnects
+    /// come first, then connects; devices whose name, driver or
+    /// manufacturer changed (a driver install renaming them, say) get an
+    /// `attribute_changed` event. The first enumeration to work after
+    /// `enumeration_failed` also says the monitor is back.
+    pub fn enumerated(&mut self, devices: DeviceMap, now: DateTime<Local>) -> Outcome {
+        let devices = group_interfaces(self.rules.apply_all(devices));
+        let mut out = Outcome::default();
+        if let Some(since) = self.down_since.take() {
+            let down_ms = (now - since).num_milliseconds().max(0) as u64;
+            out.events
+                .push(monitor_event(EventKind::MonitorResumed { down_ms }, now));
+        }
+        let gone: Vec<String> = self
+            .attached
+            .keys()
+            .filter(|id| !devices.contains_key(*id))
+            .cloned()
+            .collect();
+        for id in gone {
+            self.disconnect(&id, now, &mut out);
+        }
+        for (id, dev) in devices {
+            match self.attached.get(&id) {
+                Some(old) if *old != dev => {
+                    out.events.extend(attribute_changes(&id, old, &dev, now));
+                    self.update(&id, dev, &mut out);
+                }
+                Some(_) => {}
+                None => self.connect(id, dev, now, &mut out),
+            }
+        }
+        self.refresh_power(now, &mut out);
+        self.numbered(out)
+    }
+
+    /// Records that the source couldn't enumerate. Only the first failure
+    /// in a row yields an event.
+    pub fn enumeration_failed(&mut self, error: &str, now: DateTime<Local>) -> Outcome {
+        let mut out = Outcome::default();
+        if self.down_since.is_none() {
+            self.down_since = Some(now);
+            let error = error.to_string();
+            out.events
+                .push(monitor_event(EventKind::MonitorError { error }, now));
+        }
+        self.numbered(out)
+    }
+
+    /// Switches to a reloaded USB ID database and re-resolves the names
+    /// of every known device.
+    pub fn set_usb_ids(&mut self, ids: Arc<UsbIds>) -> Outcome {
+        self.ids = ids;
+        for kd in self.known.values_mut() {
+            let (vendor, product) = self.ids.lookup(&DeviceInstanceId::parse(&kd.device_id));
+            kd.vendor_name = vendor;
+            kd.product_name = product;
+            if let Some(dev) = self.attached.get(&kd.device_id) {
+                kd.class_name = class_name(dev, &self.ids);
+            }
+        }
+        Outcome {
+            devices_changed: true,
+            known_changed: true,
+            ..Outcome::default()
+        }
+    }
+
+    /// Switches to a reloaded rules file and re-applies it to everything
+    /// attached. A device the rules now call storage is enriched at `now`.
+    pub fn set_rules(&mut self, rules: Arc<Rules>, now: DateTime<Local>) -> Outcome {
+        self.rules = rules;
+        let mut out = Outcome::default();
+        let ids: Vec<String> = self.attached.keys().cloned().collect();
+        for id in ids {
+            let mut dev = self.attached[&id].clone();
+            self.rules.apply(&id, &mut dev);
+            if dev == self.attached[&id] {
+                continue;
+            }
+            if dev.is_storage() && !self.enrichment.contains_key(&id) {
+                self.enrichment.insert(id.clone(), Enrichment::new(now));
+            }
+            self.update(&id, dev, &mut out);
+        }
+        out
+    }
+
+    /// When the next enrichment or refresh falls due, if any.
+    pub fn next_enrichment(&self) -> Option<DateTime<Local>> {
+        self.enrichment.values().filter_map(|e| e.due).min()
+    }
+
+    /// True while a lookup handed out by `due_enrichments` hasn't come
+    /// back yet.
+    pub fn enriching(&self) -> bool {
+        self.enrichment.values().any(|e| e.running)
+    }
+
+    /// True once every drive's first lookup has finished one way or the
+    /// other and nothing is running. Refreshes carry on after that.
+    pub fn settled(&self) -> bool {
+        !self.enrichment.values().any(|e| e.running || e.settling())
+    }
+
+    /// Returns the devices due for enrichment at `now` and marks their
+    /// lookups as running.
+    pub fn due_enrichments(&mut self, now: DateTime<Local>) -> Vec<String> {
+        let mut due = Vec::new();
+        for (id, e) in &mut self.enrichment {
+            if e.due.is_some_and(|at| at <= now) {
+                e.due = None;
+                e.running = true;
+                if e.settling() {
+                    e.attempts += 1;
+                    e.status = EnrichStatus::Pending {
+                        attempts: e.attempts,
+                    };
+                }
+                due.push(id.clone());
+            }
+        }
+        due.sort();
+        due
+    }
+
+    /// Looks a connected drive up again at `now` instead of waiting for
+    /// its next refresh. Anything that isn't attached storage is ignored.
+    pub fn refresh(&mut self, id: &str, now: DateTime<Local>) {
+        if let Some(e) = self.enrichment.get_mut(id).filter(|e| !e.running) {
+            e.due = Some(now);
+        }
+    }
+
+    /// Records the result of a storage lookup, with a warning if the
+    /// drive looks counterfeit. A lookup that found nothing, or a drive
+    /// with no volumes yet, is retried with backoff until the timeout;
+    /// the first lookup ends in `storage_enriched` or `enrichment_failed`.
+    /// After that the drive is refreshed periodically, and volumes that
+    /// come, go or change between lookups yield `volume_changed` events.
+    /// Results for devices that have left again in the meantime are
+    /// dropped.
+    pub fn enriched(
+        &mut self,
+        id: &str,
+        info: Option<StorageInfo>,
+        now: DateTime<Local>,
+    ) -> Outcome {
+        let out = self.lookup_finished(id, info, now);
+        self.numbered(out)
+    }
+
+    fn lookup_finished(
+        &mut self,
+        id: &str,
+        info: Option<StorageInfo>,
+        now: DateTime<Local>,
+    ) -> Outcome {
+        let mut out = Outcome::default();
+        let Some(dev) = self.attached.get(id) else {
+            return out;
+        };
+        let Some(e) = self.enrichment.get_mut(id) else {
+            return out;
+        };
+        e.running = false;
+        let first = e.settling();
+        let before = e.status.clone();
+        let found = info.as_ref().is_some_and(|i| !i.volumes.is_empty());
+        if found {
+            e.status = EnrichStatus::Ok;
+            e.due = Some(now + REFRESH_INTERVAL);
+        } else if first {
+            let reason = match info {
+                Some(_) => format!("No volumes after {}s", ENRICH_TIMEOUT.num_seconds()),
+                None => format!("Drive not found after {} attempts", e.attempts),
+            };
+            e.retry(now, EnrichStatus::Failed { reason });
+            if let EnrichStatus::Failed { reason } = &e.status {
+                let reason = reason.clone();
+                out.events.push(device_event(
+                    EventKind::EnrichmentFailed { reason },
+                    id,
+                    dev,
+                    now,
+                ));
+            }
+        } else {
+            e.due = Some(now + REFRESH_INTERVAL);
+        }
+        out.devices_changed = first || e.status != before;
+        // A refresh that comes back empty keeps what was found before.
+        let Some(mut info) = info else {
+            return out;
+        };
+
+        info.warnings = counterfeit::analyze(&info, &self.sizes_seen(id, &info.serial_number));
+        if first && found {
+            let kind = EventKind::StorageEnriched {
+                model: info.model.clone(),
+                total_bytes: info.total_bytes,
+                drives: info
+                    .volumes
+                    .iter()
+                    .map(|v| v.drive_letter.clone())
+                    .filter(|d| !d.is_empty())
+                    .collect(),
+            };
+            out.events.push(device_event(kind, id, dev, now));
+        }
+        // Retries and refreshes find the same drive; don't warn twice.
+        let warned = self
+            .storage
+            .get(id)
+            .is_some_and(|old| old.warnings == info.warnings);
+        if !info.warnings.is_empty() && !warned {
+            let message = format!("Possible fake drive: {}", info.warnings.join("; "));
+            out.events
+                .push(device_event(EventKind::Warning { message }, id, dev, now));
+        }
+        if let Some(old) = self.storage.get(id) {
+            if *old == info {
+                return out;
+            }
+            // Volumes turning up while the drive mounts aren't changes.
+            if !first {
+                for change in volumes::changes(&old.volumes, &info.volumes) {
+                    out.events.push(device_event(
+                        EventKind::VolumeChanged { change },
+                        id,
+                        dev,
+                        now,
+                    ));
+                }
+            }
+        }
+        out.devices_changed = true;
+        if let Some(kd) = self.known.get_mut(id) {
+            if let Some(percent) = kd.fill_alert {
+                let old = self.storage.get(id).map_or(&[][..], |o| &o.volumes);
+                for message in volumes::fill_alerts(old, &info.volumes, percent) {
+                    out.events
+                        .push(device_event(EventKind::Warning { message }, id, dev, now));
+                }
+            }
+            volumes::record(&mut kd.volume_history, &info.volumes, now);
+            if info.total_bytes > 0 && !kd.capacities_seen.contains(&info.total_bytes) {
+                kd.capacities_seen.push(info.total_bytes);
+            }
+            kd.storage_info = Some(info.clone());
+            out.known_changed = true;
+        }
+        self.storage.insert(id.to_string(), info);
+        out
+    }
+
+    /// Carries on numbering events after `seq`, the last one an earlier
+    /// run handed out.
+    pub fn continue_numbering(&mut self, seq: u64) {
+        self.seq = self.seq.max(seq);
+    }
+
+    /// Gives the events leaving the engine their sequence numbers.
+    fn numbered(&mut self, mut out: Outcome) -> Outcome {
+        for event in &mut out.events {
+            self.seq += 1;
+            event.seq = self.seq;
+        }
+        out
+    }
+
+    /// Capacities reported before under `serial`: by this device, and by
+    /// any other known drive with the same disk serial.
+    fn sizes_seen(&self, id: &str, serial: &str) -> Vec<u64> {
+        let mut sizes = self
+            .known
+            .get(id)
+            .map(|kd| kd.capacities_seen.clone())
+            .unwrap_or_default();
+        if serial.trim().is_empty() {
+            return sizes;
+        }
+        for (other, kd) in &self.known {
+            let Some(info) = kd.storage_info.as_ref().filter(|_| other != id) else {
+                continue;
+            };
+            if info
+                .serial_number
+                .trim()
+                .eq_ignore_ascii_case(serial.trim())
+            {
+                sizes.extend(&kd.capacities_seen);
+                sizes.push(info.total_bytes);
+            }
+        }
+        sizes
+    }
+
+    /// Picks up edits made through commands: forgotten devices, nicknames
+    /// and fill alerts. A device forgotten while attached stays attached, and
+    /// comes back as new the next time it connects.
+    pub fn sync_known(&mut self, edited: &HashMap<String, KnownDevice>) -> Outcome {
+        let mut out = Outcome::default();
+        let forgotten: Vec<String> = self
+            .known
+            .keys()
+            .filter(|id| !edited.contains_key(*id))
+            .cloned()
+            .collect();
+        for id in forgotten {
+            self.known.remove(&id);
+            self.storage.remove(&id);
+            out.known_changed = true;
+        }
+        for (id, edit) in edited {
+            if let Some(kd) = self.known.get_mut(id) {
+                if kd.nickname != edit.nickname {
+                    kd.nickname = edit.nickname.clone();
+                    out.known_changed = true;
+                }
+                if kd.fill_alert != edit.fill_alert {
+                    kd.fill_alert = edit.fill_alert;
+                    out.known_changed = true;
+                }
+            }
+        }
+        out
+    }
+
+    fn connect(&mut self, id: String, dev: UsbDevice, now: DateTime<Local>, out: &mut Outcome) {
+        let kind = match self.departed.remove(&id) {
+            Some(left) if now - left <= RECONNECT_WINDOW => EventKind::Reconnect {
+                away_ms: (now - left).num_milliseconds().max(0) as u64,
+            },
+            _ => EventKind::Connect,
+        };
+        out.events.push(device_event(kind, &id, &dev, now));
+        let now_iso = format_iso(now);
+        let kd = self.upsert_known(&id, &dev, &now_iso, 0);
+        kd.times_seen += 1;
+        if let Some((speed, best)) = record_speed(kd, dev.Speed, &now_iso) {
+            let message = format!(
+                "Slow port: running at {}, has run at {} before",
+                speed.label(),
+                best.label()
+            );
+            out.events
+                .push(device_event(EventKind::Warning { message }, &id, &dev, now));
+        }
+        if dev.is_storage() {
+            self.enrichment
+                .insert(id.clone(), Enrichment::new(now + ENRICH_DELAY));
+        }
+        self.attached.insert(id, dev);
+        out.devices_changed = true;
+        out.known_changed = true;
+    }
+
+    fn disconnect(&mut self, id: &str, now: DateTime<Local>, out: &mut Outcome) {
+        let Some(dev) = self.attached.remove(id) else {
+            return;
+        };
+        let over_budget = self.over_budget_hub(id, &dev).map(|(hub, p)| OverBudget {
+            hub,
+            used_ma: p.used_ma,
+            budget_ma: p.budget_ma,
+        });
+        out.events.push(device_event(
+            EventKind::Disconnect { over_budget },
+            id,
+            &dev,
+            now,
+        ));
+        self.departed
+            .retain(|_, left| now - *left <= RECONNECT_WINDOW);
+        self.departed.insert(id.to_string(), now);
+        if let Some(kd) = self.known.get_mut(id) {
+            kd.last_seen = format_iso(now);
+            kd.currently_connected = false;
+            out.known_changed = true;
+        }
+        self.storage.remove(id);
+        self.enrichment.remove(id);
+        out.devices_changed = true;
+    }
+
+    /// Recomputes hub power after a change, with a warning for every hub
+    /// that has just gone over its budget.
+    fn refresh_power(&mut self, now: DateTime<Local>, out: &mut Outcome) {
+        let power = power::hub_power(&self.attached);
+        if power == self.power {
+            return;
+        }
+        for (id, p) in &power {
+            let was_exceeded = self.power.get(id).is_some_and(|old| old.exceeded);
+            if !p.exceeded || was_exceeded {
+                continue;
+            }
+            if let Some(hub) = self.attached.get(id) {
+                let message = format!(
+                    "Power budget exceeded: drawing {} of {} mA",
+                    p.used_ma, p.budget_ma
+                );
+                out.events
+                    .push(device_event(EventKind::Warning { message }, id, hub, now));
+            }
+        }
+        self.power = power;
+        out.devices_changed = true;
+    }
+
+    /// The nearest hub at or above `dev` that is over its power budget,
+    /// by name.
+    fn over_budget_hub(&self, id: &str, dev: &UsbDevice) -> Option<(String, HubPower)> {
+        let mut current = Some((id.to_string(), dev));
+        for _ in 0..=power::MAX_TIERS {
+            let (id, dev) = current?;
+            if let Some(p) = self.power.get(&id).filter(|p| p.exceeded) {
+                return Some((dev.display_name().to_string(), p.clone()));
+            }
+            current = dev
+                .Parent
+                .as_ref()
+                .and_then(|parent| Some((parent.clone(), self.attached.get(parent)?)));
+        }
+        None
+    }
+
+    /// Folds an interface entry into its attached parent. Returns false
+    /// when it isn't an interface or the parent can't be told apart.
+    fn add_interface(&mut self, id: &str, dev: &UsbDevice, out: &mut Outcome) -> bool {
+        let Some(parent_id) = find_parent(&self.attached, id) else {
+            return false;
+        };
+        let mut parent = self.attached[&parent_id].clone();
+        attach_interface(&mut parent, id, dev);
+        if parent != self.attached[&parent_id] {
+            self.update(&parent_id, parent, out);
+        }
+        true
+    }
+
+    fn remove_interface(&mut self, id: &str, out: &mut Outcome) {
+        let Some((parent_id, parent)) = self
+            .attached
+            .iter()
+            .find(|(_, d)| d.Interfaces.iter().any(|i| i.device_id == id))
+        else {
+            return;
+        };
+        let parent_id = parent_id.clone();
+        let mut parent = parent.clone();
+        parent.Interfaces.retain(|i| i.device_id != id);
+        self.update(&parent_id, parent, out);
+    }
+
+    fn update(&mut self, id: &str, dev: UsbDevice, out: &mut Outcome) {
+        if let Some(kd) = self.known.get_mut(id) {
+            copy_attributes(kd, &dev, &self.ids);
+            out.known_changed = true;
+        }
+        self.attached.insert(id.to_string(), dev);
+        out.devices_changed = true;
+    }
+
+    /// Marks `id` connected at `now_iso`, creating its record with
+    /// `times_seen` if it's new and refreshing its attributes otherwise.
+    fn upsert_known(
+        &mut self,
+        id: &str,
+        dev: &UsbDevice,
+        now_iso: &str,
+        times_seen: u32,
+    ) -> &mut KnownDevice {
+        let kd = self
+            .known
+            .entry(id.to_string())
+            .or_insert_with(|| KnownDevice {
+                device_id: id.to_string(),
+                name: String::new(),
+                vid_pid: String::new(),
+                class: String::new(),
+                manufacturer: String::new(),
+                description: String::new(),
+                first_seen: now_iso.to_string(),
+                last_seen: now_iso.to_string(),
+                times_seen,
+                currently_connected: true,
+                nickname: None,
+                storage_info: None,
+                vendor_name: None,
+                product_name: None,
+                class_name: None,
+                serial: None,
+                bus: Bus::Usb,
+                category: DeviceCategory::Other,
+                icon: None,
+                interfaces: Vec::new(),
+                speed: None,
+                usb_version: None,
+                best_speed: None,
+                capacities_seen: Vec::new(),
+                speed_history: Vec::new(),
+                volume_history: Vec::new(),
+                fill_alert: None,
+            });
+        copy_attributes(kd, dev, &self.ids);
+        kd.last_seen = now_iso.to_string();
+        kd.currently_connected = true;
+        kd
+    }
+}
+
+// ── Helpers ────────────────────────────────────────────────────
+
+/// `2024-03-09T14:05:22.317Z`: RFC 3339 in UTC with milliseconds. Stored
+/// timestamps sort as text and don't jump when the clocks change; only
+/// the CLI, the log and the UI show them in local time.
+pub fn format_iso(t: DateTime<Local>) -> String {
+    t.with_timezone(&Utc)
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Reads back a timestamp written by `format_iso`, in local time.
+pub fn parse_iso(s: &str) -> Option<DateTime<Local>> {
+    DateTime::parse_from_rfc3339(s)
+        .ok()
+        .map(|t| t.with_timezone(&Local))
+}
+
+/// Folds Windows' `MI_xx` interface entries into the physical device they
+/// belong to, so a composite device is attached, logged and remembered
+/// once. Interfaces whose parent isn't there, or can't be told apart from
+/// an identical device, stay devices of their own.
+fn group_interfaces(devices: DeviceMap) -> DeviceMap {
+    let (children, mut grouped): (DeviceMap, DeviceMap) = devices
+        .into_iter()
+        .partition(|(id, _)| DeviceInstanceId::parse(id).interface.is_some());
+    for (id, dev) in children {
+        match find_parent(&grouped, &id) {
+            Some(parent) => {
+                if let Some(parent) = grouped.get_mut(&parent) {
+                    attach_interface(parent, &id, &dev);
+                }
+            }
+            None => {
+                grouped.insert(id, dev);
+            }
+        }
+    }
+    grouped
+}
+
+/// The one device in `devices` with the same VID/PID as the interface
+/// `child_id` and no interface number of its own.
+fn find_parent(devices: &DeviceMap, child_id: &str) -> Option<String> {
+    let child = DeviceInstanceId::parse(child_id);
+    child.interface?;
+    child.vid?;
+    let mut candidates = devices.keys().filter(|id| {
+        let p = DeviceInstanceId::parse(id);
+        p.interface.is_none()
+            && p.enumerator == child.enumerator
+            && p.vid == child.vid
+            && p.pid == child.pid
+    });
+    match (candidates.next(), candidates.next()) {
+        (Some(parent), None) => Some(parent.clone()),
+        _ => None,
+    }
+}
+
+fn attach_interface(parent: &mut UsbDevice, id: &str, dev: &UsbDevice) {
+    let iface = DeviceInterface {
+        device_id: id.to_string(),
+        number: DeviceInstanceId::parse(id).interface,
+        name: dev.display_name().to_string(),
+        class: dev.class().to_string(),
+        category: dev.category(),
+    };
+    parent.Interfaces.retain(|i| i.device_id != id);
+    parent.Interfaces.push(iface);
+    parent.Interfaces.sort_by_key(|i| i.number);
+}
+
+fn class_name(dev: &UsbDevice, ids: &UsbIds) -> Option<String> {
+    let (class, subclass, _) = dev.usb_class()?;
+    ids.class_name(class, subclass)
+}
+
+fn copy_attributes(kd: &mut KnownDevice, dev: &UsbDevice, ids: &UsbIds) {
+    let parsed = DeviceInstanceId::parse(&kd.device_id);
+    kd.name = dev.display_name().to_string();
+    kd.vid_pid = parsed.vid_pid().unwrap_or_default();
+    kd.class = dev.class().to_string();
+    kd.manufacturer = dev.Manufacturer.clone().unwrap_or_default();
+    kd.description = dev.Description.clone().unwrap_or_default();
+    kd.serial = parsed.serial().map(str::to_string);
+    (kd.vendor_name, kd.product_name) = ids.lookup(&parsed);
+    kd.class_name = class_name(dev, ids);
+    kd.bus = dev.bus();
+    kd.category = dev.category();
+    kd.icon = dev.icon();
+    kd.interfaces = dev.Interfaces.clone();
+    kd.speed = dev.Speed;
+    kd.usb_version = dev.UsbVersion.clone();
+}
+
+/// Adds this connection's speed to the history. Returns it along with
+/// the best speed seen before when a device that has run at SuperSpeed
+/// came up at High Speed or slower.
+fn record_speed(
+    kd: &mut KnownDevice,
+    speed: Option<LinkSpeed>,
+    at: &str,
+) -> Option<(LinkSpeed, LinkSpeed)> {
+    let speed = speed?;
+    kd.speed_history.push(SpeedSample {
+        connected_at: at.to_string(),
+        speed,
+    });
+    if kd.speed_history.len() > SPEED_HISTORY_LEN {
+        kd.speed_history.remove(0);
+    }
+    let best = kd.best_speed;
+    kd.best_speed = best.max(Some(speed));
+    best.filter(|b| b.is_superspeed() && speed <= LinkSpeed::High)
+        .map(|b| (speed, b))
+}
+
+fn usb_to_snapshot(id: &str, dev: &UsbDevice, ids: &UsbIds) -> DeviceSnapshot {
+    let parsed = DeviceInstanceId::parse(id);
+    let (vendor_name, product_name) = ids.lookup(&parsed);
+    DeviceSnapshot {
+        device_id: id.to_string(),
+        name: dev.display_name().to_string(),
+        vid_pid: parsed.vid_pid(),
+        manufacturer: dev.Manufacturer.clone(),
+        class: dev.class().to_string(),
+        bus: dev.bus(),
+        category: dev.category(),
+        icon: dev.icon(),
+        vendor_name,
+        product_name,
+        class_name: class_name(dev, ids),
+        interfaces: dev.Interfaces.clone(),
+        parent_id: dev.Parent.clone(),
+        location_path: dev.LocationPath.clone(),
+        speed: dev.Speed,
+        usb_version: dev.UsbVersion.clone(),
+        power: dev.Power.clone(),
+        hub_power: None,
+        enrichment: None,
+    }
+}
+
+/// One `attribute_changed` event for each of the name, driver and
+/// manufacturer that differ between `before` and `dev`. A driver binding
+/// where there was none is just the device finishing its arrival.
+fn attribute_changes(
+    id: &str,
+    before: &UsbDevice,
+    dev: &UsbDevice,
+    now: DateTime<Local>,
+) -> Vec<DeviceEvent> {
+    let name = |d: &UsbDevice| Some(d.display_name().to_string());
+    let compared = [
+        (Attribute::Name, name(before), name(dev)),
+        (
+            Attribute::Driver,
+            before.Service.clone(),
+            dev.Service.clone(),
+        ),
+        (
+            Attribute::Manufacturer,
+            before.Manufacturer.clone(),
+            dev.Manufacturer.clone(),
+        ),
+    ];
+    compared
+        .into_iter()
+        .filter(|(attribute, old, new)| {
+            old != new && !(*attribute == Attribute::Driver && old.is_none())
+        })
+        .map(|(attribute, old, new)| {
+            let kind = EventKind::AttributeChanged {
+                attribute,
+                old,
+                new,
+            };
+            device_event(kind, id, dev, now)
+        })
+        .collect()
+}
+
+/// An event about `dev`. Its sequence number is filled in as it leaves
+/// the engine.
+fn device_event(kind: EventKind, id: &str, dev: &UsbDevice, now: DateTime<Local>) -> DeviceEvent {
+    DeviceEvent {
+        timestamp: format_iso(now),
+        seq: 0,
+        kind,
+        name: dev.display_name().to_string(),
+        vid_pid: dev.vid_pid(),
+        manufacturer: dev.Manufacturer.clone(),
+        class: dev.class().to_string(),
+        bus: dev.bus(),
+        category: dev.category(),
+        device_id: id.to_string(),
+    }
+}
+
+/// An event about the monitor itself rather than any device.
+fn monitor_event(kind: EventKind, now: DateTime<Local>) -> DeviceEvent {
+    DeviceEvent {
+        timestamp: format_iso(now),
+        seq: 0,
+        kind,
+        name: "Device monitor".to_string(),
+        vid_pid: None,
+        manufacturer: None,
+        class: String::new(),
+        bus: Bus::default(),
+        category: DeviceCategory::default(),
+        device_id: String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DevicePower, VolumeInfo};
+    use chrono::TimeZone;
+
+    const MOUSE: &str = "USB\\VID_046D&PID_C077\\5&1A2B3C4D&0&1";
+    const STICK: &str = "USB\\VID_0781&PID_5581\\4C530001230101103413";
+    const RECEIVER: &str = "USB\\VID_046D&PID_C52B\\6&2D7E9F6&0&2";
+
+    /// The engine's only clock: seconds after a fixed instant.
+    fn at(secs: f64) -> DateTime<Local> {
+        let start = Utc.with_ymd_and_hms(2024, 3, 9, 14, 0, 0).unwrap();
+        (start + TimeDelta::milliseconds((secs * 1000.0) as i64)).with_timezone(&Local)
+    }
+
+    fn device(id: &str, name: &str) -> (String, UsbDevice) {
+        let dev = UsbDevice {
+            Name: Some(name.to_string()),
+            DeviceID: Some(id.to_string()),
+            Description: Some(name.to_string()),
+            Manufacturer: None,
+            PNPClass: Some("USB".to_string()),
+            Service: None,
+            CompatibleID: None,
+            Interfaces: Vec::new(),
+            Parent: None,
+            LocationPath: None,
+            Speed: None,
+            UsbVersion: None,
+            Power: None,
+            Rule: None,
+        };
+        (id.to_string(), dev)
+    }
+
+    /// A flash drive, which the engine looks up storage details for.
+    fn drive() -> (String, UsbDevice) {
+        let mut drive = device(STICK, "Ultra");
+        drive.1.PNPClass = Some("DiskDrive".to_string());
+        drive
+    }
+
+    fn volume(letter: &str, label: &str, free_bytes: u64) -> VolumeInfo {
+        VolumeInfo {
+            drive_letter: letter.to_string(),
+            volume_name: label.to_string(),
+            total_bytes: 61_530_439_680,
+            free_bytes,
+            file_system: "exFAT".to_string(),
+            volume_serial: "1A2B-3C4D".to_string(),
+        }
+    }
+
+    fn storage(volumes: &[VolumeInfo]) -> StorageInfo {
+        StorageInfo {
+            model: "SanDisk Ultra 64GB".to_string(),
+            serial_number: "4C530001230101103413".to_string(),
+            total_bytes: 61_530_439_680,
+            removable: true,
+            volumes: volumes.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    fn status(engine: &Engine, id: &str) -> Option<EnrichStatus> {
+        engine
+            .snapshot()
+            .into_iter()
+            .find(|d| d.device_id == id)?
+            .enrichment
+    }
+
+    fn devices(list: &[(String, UsbDevice)]) -> DeviceMap {
+        list.iter().cloned().collect()
+    }
+
+    fn engine(attached: &[(String, UsbDevice)]) -> Engine {
+        Engine::new(
+            HashMap::new(),
+            devices(attached),
+            Arc::new(UsbIds::default()),
+            Arc::new(Rules::empty()),
+            at(0.0),
+        )
+    }
+
+    fn added(dev: &(String, UsbDevice)) -> Hotplug {
+        Hotplug::Added {
+            id: dev.0.clone(),
+            device: Box::new(dev.1.clone()),
+        }
+    }
+
+    fn removed(id: &str) -> Hotplug {
+        Hotplug::Removed { id: id.to_string() }
+    }
+
+    /// Each event as its kind and device name.
+    fn events(out: &Outcome) -> Vec<(&'static str, &str)> {
+        out.events
+            .iter()
+            .map(|e| (e.kind.name(), e.name.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn devices_attached_at_startup_are_known_without_events() {
+        let mut engine = engine(&[device(MOUSE, "Mouse")]);
+        assert_eq!(engine.attached().len(), 1);
+        let kd = &engine.known()[MOUSE];
+        assert_eq!(kd.times_seen, 1);
+        assert!(kd.currently_connected);
+        assert_eq!(kd.first_seen, format_iso(at(0.0)));
+
+        let out = engine.enumerated(devices(&[device(MOUSE, "Mouse")]), at(1.0));
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn simultaneous_connect_and_disconnect() {
+        let mut engine = engine(&[device(MOUSE, "Mouse")]);
+        let out = engine.enumerated(devices(&[device(STICK, "Ultra")]), at(5.0));
+        // Disconnects come before connects.
+        assert_eq!(
+            events(&out),
+            [("disconnect", "Mouse"), ("connect", "Ultra")]
+        );
+        assert!(out.devices_changed && out.known_changed);
+        assert_eq!(out.events.iter().map(|e| e.seq).collect::<Vec<_>>(), [1, 2]);
+
+        let mouse = &engine.known()[MOUSE];
+        assert!(!mouse.currently_connected);
+        assert_eq!(mouse.last_seen, format_iso(at(5.0)));
+        let stick = &engine.known()[STICK];
+        assert!(stick.currently_connected);
+        assert_eq!(stick.times_seen, 1);
+        assert_eq!(stick.first_seen, format_iso(at(5.0)));
+        assert_eq!(engine.attached().keys().collect::<Vec<_>>(), [STICK]);
+    }
+
+    #[test]
+    fn hotplug_batches_keep_their_order() {
+        let mut engine = engine(&[device(MOUSE, "Mouse")]);
+        let stick = device(STICK, "Ultra");
+        let out = engine.hotplug(vec![removed(MOUSE), added(&stick)], at(1.0));
+        assert_eq!(
+            events(&out),
+            [("disconnect", "Mouse"), ("connect", "Ultra")]
+        );
+
+        // A blip shorter than an enumeration still shows up.
+        let out = engine.hotplug(vec![removed(STICK), added(&stick)], at(2.0));
+        assert_eq!(
+            events(&out),
+            [("disconnect", "Ultra"), ("reconnect", "Ultra")]
+        );
+        assert_eq!(engine.known()[STICK].times_seen, 2);
+
+        // Removing something that isn't attached does nothing.
+        assert!(engine.hotplug(vec![removed(MOUSE)], at(3.0)).is_empty());
+    }
+
+    #[test]
+    fn renames_and_driver_changes() {
+        let mut engine = engine(&[device(MOUSE, "USB Input Device")]);
+        let mut renamed = device(MOUSE, "MX Master 3");
+        let out = engine.enumerated(devices(&[renamed.clone()]), at(1.0));
+        assert_eq!(events(&out), [("attribute_changed", "MX Master 3")]);
+        assert!(matches!(
+            &out.events[0].kind,
+            EventKind::AttributeChanged { attribute: Attribute::Name, old, new }
+                if old.as_deref() == Some("USB Input Device")
+                    && new.as_deref() == Some("MX Master 3")
+        ));
+        assert_eq!(engine.known()[MOUSE].name, "MX Master 3");
+        assert_eq!(engine.known()[MOUSE].times_seen, 1);
+
+        // A driver binding after arrival is no change...
+        renamed.1.Service = Some("HidUsb".to_string());
+        let out = engine.enumerated(devices(&[renamed.clone()]), at(2.0));
+        assert!(out.events.is_empty());
+        assert!(out.devices_changed);
+
+        // ...but swapping it for another is.
+        renamed.1.Service = Some("LGSHidFilt".to_string());
+        let out = engine.enumerated(devices(&[renamed]), at(3.0));
+        assert!(matches!(
+            &out.events[0].kind,
+            EventKind::AttributeChanged { attribute: Attribute::Driver, old, new }
+                if old.as_deref() == Some("HidUsb") && new.as_deref() == Some("LGSHidFilt")
+        ));
+    }
+
+    #[test]
+    fn forgetting_a_connected_device() {
+        let mut engine = engine(&[device(MOUSE, "Mouse"), device(STICK, "Ultra")]);
+        let mut edited = engine.known().clone();
+        edited.remove(MOUSE);
+        let out = engine.sync_known(&edited);
+        assert!(out.known_changed);
+        assert!(out.events.is_empty());
+        assert!(!engine.known().contains_key(MOUSE));
+        // Still attached, and not brought back by the next enumeration.
+        assert!(engine.attached().contains_key(MOUSE));
+        let out = engine.enumerated(
+            devices(&[device(MOUSE, "Mouse"), device(STICK, "Ultra")]),
+            at(1.0),
+        );
+        assert!(out.is_empty());
+        assert!(!engine.known().contains_key(MOUSE));
+
+        // Leaving still yields an event, without recreating the record.
+        let out = engine.enumerated(devices(&[device(STICK, "Ultra")]), at(2.0));
+        assert_eq!(events(&out), [("disconnect", "Mouse")]);
+        assert!(!engine.known().contains_key(MOUSE));
+
+        // Coming back later, it's new.
+        let out = engine.enumerated(
+            devices(&[device(MOUSE, "Mouse"), device(STICK, "Ultra")]),
+            at(60.0),
+        );
+        assert_eq!(events(&out), [("connect", "Mouse")]);
+        let kd = &engine.known()[MOUSE];
+        assert_eq!(kd.times_seen, 1);
+        assert_eq!(kd.first_seen, format_iso(at(60.0)));
+    }
+
+    #[test]
+    fn nicknames_sync_without_events() {
+        let mut engine = engine(&[device(MOUSE, "Mouse")]);
+        let mut edited = engine.known().clone();
+        edited.get_mut(MOUSE).unwrap().nickname = Some("Desk mouse".to_string());
+        let out = engine.sync_known(&edited);
+        assert!(out.known_changed && out.events.is_empty());
+        assert_eq!(
+            engine.known()[MOUSE].nickname.as_deref(),
+            Some("Desk mouse")
+        );
+        assert!(engine.sync_known(&edited).is_empty());
+    }
+
+    #[test]
+    fn reconnect_window_is_ten_seconds() {
+        let mouse = device(MOUSE, "Mouse");
+        let mut engine = engine(std::slice::from_ref(&mouse));
+
+        engine.hotplug(vec![removed(MOUSE)], at(10.0));
+        let out = engine.hotplug(vec![added(&mouse)], at(20.0));
+        assert_eq!(events(&out), [("reconnect", "Mouse")]);
+        assert!(matches!(
+            out.events[0].kind,
+            EventKind::Reconnect { away_ms: 10_000 }
+        ));
+
+        engine.hotplug(vec![removed(MOUSE)], at(30.0));
+        let out = engine.hotplug(vec![added(&mouse)], at(40.001));
+        assert_eq!(events(&out), [("connect", "Mouse")]);
+
+        // Through enumerations too.
+        engine.enumerated(DeviceMap::new(), at(50.0));
+        let out = engine.enumerated(devices(&[mouse]), at(52.5));
+        assert!(matches!(
+            out.events[0].kind,
+            EventKind::Reconnect { away_ms: 2_500 }
+        ));
+        assert_eq!(engine.known()[MOUSE].times_seen, 4);
+    }
+
+    #[test]
+    fn slow_ports_are_warned_about() {
+        let mut stick = device(STICK, "Ultra");
+        stick.1.Speed = Some(LinkSpeed::Super);
+        let mut engine = engine(&[]);
+        let out = engine.hotplug(vec![added(&stick)], at(1.0));
+        assert_eq!(events(&out), [("connect", "Ultra")]);
+
+        // Back at High Speed, on a USB 2 port or a bad cable.
+        stick.1.Speed = Some(LinkSpeed::High);
+        engine.hotplug(vec![removed(STICK)], at(2.0));
+        let out = engine.hotplug(vec![added(&stick)], at(3.0));
+        assert_eq!(events(&out), [("reconnect", "Ultra"), ("warning", "Ultra")]);
+        assert!(matches!(
+            &out.events[1].kind,
+            EventKind::Warning { message } if message
+                == "Slow port: running at High Speed (480 Mbps), has run at SuperSpeed (5 Gbps) before"
+        ));
+        let kd = &engine.known()[STICK];
+        assert_eq!(kd.best_speed, Some(LinkSpeed::Super));
+        assert_eq!(
+            kd.speed_history.iter().map(|s| s.speed).collect::<Vec<_>>(),
+            [LinkSpeed::Super, LinkSpeed::High]
+        );
+
+        // Devices that never ran faster aren't slow.
+        let mut mouse = device(MOUSE, "Mouse");
+        mouse.1.Speed = Some(LinkSpeed::Low);
+        let out = engine.hotplug(vec![added(&mouse)], at(4.0));
+        assert_eq!(events(&out), [("connect", "Mouse")]);
+    }
+
+    #[test]
+    fn overloaded_hubs_are_warned_about() {
+        const HUB: &str = "USB\\VID_05E3&PID_0610\\1-2";
+        let mut hub = device(HUB, "Travel Hub");
+        hub.1.Power = Some(DevicePower {
+            max_power_ma: 100,
+            self_powered: false,
+            hub_ports: Some(4),
+        });
+        let mut stick = device(STICK, "Ultra");
+        stick.1.Parent = Some(HUB.to_string());
+        stick.1.Power = Some(DevicePower {
+            max_power_ma: 896,
+            self_powered: false,
+            hub_ports: None,
+        });
+        let mut engine = engine(&[hub]);
+
+        let out = engine.hotplug(vec![added(&stick)], at(1.0));
+        assert_eq!(
+            events(&out),
+            [("connect", "Ultra"), ("warning", "Travel Hub")]
+        );
+        assert!(matches!(
+            &out.events[1].kind,
+            EventKind::Warning { message }
+                if message == "Power budget exceeded: drawing 896 of 400 mA"
+        ));
+        let snapshot = engine.snapshot();
+        let hub = snapshot.iter().find(|d| d.device_id == HUB).unwrap();
+        assert!(hub.hub_power.as_ref().is_some_and(|p| p.exceeded));
+
+        // Leaving says which hub it was too much for.
+        let out = engine.hotplug(vec![removed(STICK)], at(2.0));
+        assert!(matches!(
+            &out.events[..],
+            [DeviceEvent {
+                kind: EventKind::Disconnect {
+                    over_budget: Some(OverBudget { hub, used_ma: 896, budget_ma: 400 })
+                },
+                ..
+            }] if hub == "Travel Hub"
+        ));
+
+        // Back within budget, the next overload warns again.
+        let out = engine.hotplug(vec![added(&stick)], at(30.0));
+        assert_eq!(
+            events(&out),
+            [("connect", "Ultra"), ("warning", "Travel Hub")]
+        );
+        assert!(engine.hotplug(vec![removed(MOUSE)], at(31.0)).is_empty());
+    }
+
+    #[test]
+    fn reloaded_rules_recategorise_attached_devices() {
+        const PROBE: &str = "USB\\VID_1366&PID_0105\\000260112233";
+        let mut engine = engine(&[device(PROBE, "BULK interface")]);
+        assert_eq!(engine.next_enrichment(), None);
+
+        let rules = Rules::parse(
+            "[[rule]]\nvid_pid = \"1366:0105\"\nname = \"J-Link\"\nstorage = true\n",
+            "test",
+        )
+        .unwrap();
+        let out = engine.set_rules(Arc::new(rules), at(5.0));
+        assert!(out.devices_changed && out.known_changed);
+        assert!(out.events.is_empty());
+        assert_eq!(engine.known()[PROBE].name, "J-Link");
+        assert_eq!(engine.snapshot()[0].name, "J-Link");
+        // Now storage, it's looked up straight away.
+        assert_eq!(engine.next_enrichment(), Some(at(5.0)));
+        assert_eq!(engine.due_enrichments(at(5.0)), [PROBE]);
+
+        // Dropping the rules puts the reported name back.
+        let out = engine.set_rules(Arc::new(Rules::empty()), at(6.0));
+        assert!(out.devices_changed);
+        assert_eq!(engine.snapshot()[0].name, "BULK interface");
+        // Unchanged rules change nothing.
+        assert!(engine
+            .set_rules(Arc::new(Rules::empty()), at(7.0))
+            .is_empty());
+    }
+
+    #[test]
+    fn drives_are_looked_up_once_they_have_had_time_to_mount() {
+        let mut engine = engine(&[]);
+        engine.hotplug(vec![added(&drive())], at(1.0));
+        assert_eq!(
+            status(&engine, STICK),
+            Some(EnrichStatus::Pending { attempts: 0 })
+        );
+        assert_eq!(engine.next_enrichment(), Some(at(3.0)));
+        assert!(engine.due_enrichments(at(2.0)).is_empty());
+        assert!(!engine.settled());
+
+        assert_eq!(engine.due_enrichments(at(3.0)), [STICK]);
+        assert!(engine.enriching());
+        assert_eq!(
+            status(&engine, STICK),
+            Some(EnrichStatus::Pending { attempts: 1 })
+        );
+        // Not handed out twice while it runs.
+        assert!(engine.due_enrichments(at(3.0)).is_empty());
+
+        let out = engine.enriched(STICK, Some(storage(&[volume("E:", "STICK", 0)])), at(3.5));
+        assert_eq!(events(&out), [("storage_enriched", "Ultra")]);
+        assert!(out.devices_changed && out.known_changed);
+        assert_eq!(status(&engine, STICK), Some(EnrichStatus::Ok));
+        assert!(engine.settled() && !engine.enriching());
+        assert_eq!(engine.storage()[STICK].volumes.len(), 1);
+    }
+
+    #[test]
+    fn lookups_back_off_until_the_timeout() {
+        let mut engine = engine(&[drive()]);
+        let mut tries = Vec::new();
+        let mut now = at(0.0);
+        let out = loop {
+            assert_eq!(engine.due_enrichments(now), [STICK]);
+            tries.push((now - at(0.0)).num_seconds());
+            let out = engine.enriched(STICK, None, now);
+            match engine.next_enrichment() {
+                Some(next) if engine.settled() => {
+                    // Given up; it's refreshed from now on.
+                    assert_eq!(next, now + REFRESH_INTERVAL);
+                    break out;
+                }
+                Some(next) => {
+                    assert!(out.events.is_empty());
+                    now = next;
+                }
+                None => unreachable!(),
+            }
+        };
+        // 1s doubling up to 16s, and no try past a minute in.
+        assert_eq!(tries, [0, 1, 3, 7, 15, 31, 47]);
+        assert_eq!(events(&out), [("enrichment_failed", "Ultra")]);
+        let failed = Some(EnrichStatus::Failed {
+            reason: "Drive not found after 7 attempts".to_string(),
+        });
+        assert_eq!(status(&engine, STICK), failed);
+        assert!(matches!(
+            &out.events[0].kind,
+            EventKind::EnrichmentFailed { reason } if reason == "Drive not found after 7 attempts"
+        ));
+
+        // A later refresh that finds it still fills the details in, but
+        // the first lookup is over.
+        let refresh = engine.next_enrichment().unwrap();
+        engine.due_enrichments(refresh);
+        let out = engine.enriched(STICK, Some(storage(&[volume("E:", "STICK", 0)])), refresh);
+        assert!(out.events.is_empty());
+        assert_eq!(status(&engine, STICK), Some(EnrichStatus::Ok));
+    }
+
+    #[test]
+    fn drives_that_never_mount_say_so() {
+        let mut engine = engine(&[drive()]);
+        let mut now = at(0.0);
+        let out = loop {
+            engine.due_enrichments(now);
+            let out = engine.enriched(STICK, Some(storage(&[])), now);
+            if engine.settled() {
+                break out;
+            }
+            now = engine.next_enrichment().unwrap();
+        };
+        assert!(matches!(
+            &out.events[0].kind,
+            EventKind::EnrichmentFailed { reason } if reason == "No volumes after 60s"
+        ));
+        // What was found is still shown.
+        assert_eq!(engine.storage()[STICK].model, "SanDisk Ultra 64GB");
+
+        // Results for a drive that has left are dropped.
+        engine.hotplug(vec![removed(STICK)], at(70.0));
+        assert!(engine.enriched(STICK, None, at(71.0)).is_empty());
+        assert_eq!(engine.next_enrichment(), None);
+    }
+
+    #[test]
+    fn settled_drives_are_refreshed_for_volume_changes() {
+        let mut engine = engine(&[drive()]);
+        engine.due_enrichments(at(0.0));
+        engine.enriched(STICK, Some(storage(&[volume("E:", "STICK", 0)])), at(0.0));
+        assert_eq!(engine.next_enrichment(), Some(at(60.0)));
+
+        // Asked for early, and nothing new.
+        engine.refresh(STICK, at(10.0));
+        assert_eq!(engine.next_enrichment(), Some(at(10.0)));
+        assert_eq!(engine.due_enrichments(at(10.0)), [STICK]);
+        let out = engine.enriched(STICK, Some(storage(&[volume("E:", "STICK", 0)])), at(10.0));
+        assert!(out.is_empty());
+        assert_eq!(engine.next_enrichment(), Some(at(70.0)));
+
+        // A refresh asked for while one runs changes nothing.
+        assert_eq!(engine.due_enrichments(at(70.0)), [STICK]);
+        engine.refresh(STICK, at(71.0));
+        assert_eq!(engine.next_enrichment(), None);
+        let out = engine.enriched(STICK, Some(storage(&[volume("E:", "BACKUP", 0)])), at(72.0));
+        assert_eq!(events(&out), [("volume_changed", "Ultra")]);
+        assert!(matches!(
+            &out.events[0].kind,
+            EventKind::VolumeChanged { change } if change.contains("BACKUP")
+        ));
+        assert_eq!(engine.storage()[STICK].volumes[0].volume_name, "BACKUP");
+
+        // One that comes back empty keeps what was there.
+        engine.due_enrichments(at(132.0));
+        let out = engine.enriched(STICK, None, at(132.0));
+        assert!(out.events.is_empty());
+        assert_eq!(engine.storage()[STICK].volumes[0].volume_name, "BACKUP");
+        assert_eq!(status(&engine, STICK), Some(EnrichStatus::Ok));
+
+        // Anything that isn't attached storage is ignored.
+        engine.refresh(MOUSE, at(133.0));
+        assert_eq!(engine.next_enrichment(), Some(at(192.0)));
+    }
+
+    #[test]
+    fn volumes_filling_past_the_alert_level_warn_once() {
+        const GB: u64 = 1_000_000_000;
+        let mut engine = engine(&[drive()]);
+        engine.due_enrichments(at(0.0));
+        engine.enriched(
+            STICK,
+            Some(storage(&[volume("E:", "STICK", 30 * GB)])),
+            at(0.0),
+        );
+
+        let mut edited = engine.known().clone();
+        edited.get_mut(STICK).unwrap().fill_alert = Some(90);
+        assert!(engine.sync_known(&edited).known_changed);
+
+        let mut refresh = |free: u64, secs: f64| {
+            engine.due_enrichments(at(secs));
+            engine.enriched(
+                STICK,
+                Some(storage(&[volume("E:", "STICK", free)])),
+                at(secs),
+            )
+        };
+        assert!(refresh(20 * GB, 60.0).events.is_empty());
+        let out = refresh(4 * GB, 120.0);
+        assert_eq!(events(&out), [("warning", "Ultra")]);
+        assert!(matches!(
+            &out.events[0].kind,
+            EventKind::Warning { message } if message.ends_with("is 93% full (alert at 90%)")
+        ));
+        // Still over it: no second warning until it has dropped back below.
+        assert!(refresh(2 * GB, 180.0).events.is_empty());
+        assert!(refresh(20 * GB, 240.0).events.is_empty());
+        assert_eq!(events(&refresh(GB, 300.0)), [("warning", "Ultra")]);
+    }
+
+    #[test]
+    fn interfaces_fold_into_their_device() {
+        let receiver = device(RECEIVER, "USB Composite Device");
+        let keyboard = device(
+            "USB\\VID_046D&PID_C52B&MI_00\\7&3A1B2C3D&0&0000",
+            "Keyboard",
+        );
+        let mouse = device("USB\\VID_046D&PID_C52B&MI_01\\7&3A1B2C3D&0&0001", "Mouse");
+        let mut engine = engine(&[]);
+        let out = engine.enumerated(
+            devices(&[receiver.clone(), keyboard.clone(), mouse.clone()]),
+            at(1.0),
+        );
+        assert_eq!(events(&out), [("connect", "USB Composite Device")]);
+        assert_eq!(engine.attached().len(), 1);
+        let interfaces = &engine.attached()[RECEIVER].Interfaces;
+        assert_eq!(
+            interfaces.iter().map(|i| i.number).collect::<Vec<_>>(),
+            [Some(0), Some(1)]
+        );
+        assert_eq!(interfaces[1].name, "Mouse");
+        assert_eq!(engine.known()[RECEIVER].interfaces.len(), 2);
+
+        // Interfaces coming and going on their own aren't events.
+        let out = engine.hotplug(vec![removed(&mouse.0)], at(2.0));
+        assert!(out.events.is_empty());
+        assert_eq!(engine.attached()[RECEIVER].Interfaces.len(), 1);
+        let out = engine.hotplug(vec![added(&mouse)], at(3.0));
+        assert!(out.events.is_empty());
+        assert_eq!(engine.attached()[RECEIVER].Interfaces.len(), 2);
+
+        // A parent arriving in the same batch as its interfaces is found.
+        let mut engine = self::engine(&[]);
+        let out = engine.hotplug(vec![added(&keyboard), added(&receiver)], at(1.0));
+        assert_eq!(events(&out), [("connect", "USB Composite Device")]);
+        assert_eq!(engine.attached()[RECEIVER].Interfaces.len(), 1);
+    }
+
+    #[test]
+    fn interfaces_without_a_clear_parent_stay_devices() {
+        let first = device(RECEIVER, "Receiver");
+        let second = device("USB\\VID_046D&PID_C52B\\6&2D7E9F6&0&3", "Receiver");
+        let iface = device(
+            "USB\\VID_046D&PID_C52B&MI_00\\7&3A1B2C3D&0&0000",
+            "Keyboard",
+        );
+        let mut engine = engine(&[]);
+        let out = engine.enumerated(devices(&[first, second, iface]), at(1.0));
+        assert_eq!(out.events.len(), 3);
+        assert_eq!(engine.attached().len(), 3);
+    }
+
+    #[test]
+    fn monitor_failures_and_recovery() {
+        let mut engine = engine(&[device(MOUSE, "Mouse")]);
+        let out = engine.enumeration_failed("WMI went away", at(1.0));
+        assert_eq!(events(&out), [("monitor_error", "Device monitor")]);
+        assert_eq!(out.events[0].device_id, "");
+        // Only the first failure in a row is reported.
+        assert!(engine
+            .enumeration_failed("WMI went away", at(2.0))
+            .events
+            .is_empty());
+
+        let out = engine.enumerated(devices(&[device(MOUSE, "Mouse")]), at(8.0));
+        assert!(matches!(
+            out.events[..],
+            [DeviceEvent {
+                kind: EventKind::MonitorResumed { down_ms: 7_000 },
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn numbering_carries_on() {
+        let mut engine = engine(&[]);
+        engine.continue_numbering(41);
+        let out = engine.hotplug(vec![added(&device(MOUSE, "Mouse"))], at(1.0));
+        assert_eq!(out.events[0].seq, 42);
+        // Never backwards.
+        engine.continue_numbering(7);
+        let out = engine.hotplug(vec![removed(MOUSE)], at(2.0));
+        assert_eq!(out.events[0].seq, 43);
+        assert_eq!(out.events[0].timestamp, format_iso(at(2.0)));
+    }
+}
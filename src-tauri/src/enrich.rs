@@ -0,0 +1,48 @@
+use crate::logging::log_to_file;
+use crate::source::Opener;
+use crate::types::StorageInfo;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+/// Runs storage lookups on a thread of their own. A PowerShell call or a
+/// slow disk then only delays that drive's details, not the detection of
+/// everything else.
+pub struct EnrichWorker {
+    requests: Sender<String>,
+    results: Receiver<(String, Option<StorageInfo>)>,
+}
+
+impl EnrichWorker {
+    /// Opens the backend on the new thread, for sources like WMI whose
+    /// connections can't be moved between threads. If it fails to open,
+    /// every lookup comes back empty.
+    pub fn spawn(open: Opener) -> Self {
+        let (requests, inbox) = mpsc::channel::<String>();
+        let (outbox, results) = mpsc::channel();
+        thread::spawn(move || {
+            let mut source = match open() {
+                Ok(s) => Some(s),
+                Err(e) => {
+                    log_to_file(&format!("ENRICH FAIL: worker couldn't open source: {}", e));
+                    None
+                }
+            };
+            for id in inbox {
+                let info = source.as_mut().and_then(|s| s.query_storage_info(&id));
+                if outbox.send((id, info)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { requests, results }
+    }
+
+    pub fn request(&self, id: &str) {
+        let _ = self.requests.send(id.to_string());
+    }
+
+    /// Lookups finished since the last call, without waiting.
+    pub fn finished(&self) -> Vec<(String, Option<StorageInfo>)> {
+        self.results.try_iter().collect()
+    }
+}
@@ -0,0 +1,303 @@
+use crate::types::Bus;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A PnP device instance ID such as
+/// `USB\VID_046D&PID_C52B&MI_00\7&2A3B4C5D&0&0000`, split into its parts.
+///
+/// Parsing is total: any string yields a value, with the parts that
+/// couldn't be recognised left empty. IDs from the Linux backends follow
+/// the same layout, with the sysfs port name (`1-2.3`) as the instance.
+/// PCI and Thunderbolt IDs carry `VEN_`/`DEV_` instead of `VID_`/`PID_`,
+/// which end up in the same fields.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceInstanceId {
+    /// `USB`, `USBSTOR`, `HID`, `PCI`, ...
+    pub enumerator: String,
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+    /// `REV_xxxx`; hex for USB, free text for `USBSTOR`.
+    pub revision: Option<String>,
+    /// `MI_xx` interface number of a composite device's child.
+    pub interface: Option<u8>,
+    /// Last path segment: the device serial, or an ID Windows (or the
+    /// kernel) made up from the port when there isn't one.
+    pub instance: String,
+    /// True when `instance` is derived from the port rather than being a
+    /// real serial, so it changes when the device moves ports.
+    pub port_derived: bool,
+}
+
+impl DeviceInstanceId {
+    pub fn parse(id: &str) -> Self {
+        let mut parts = id.splitn(3, '\\');
+        let enumerator = parts.next().unwrap_or("").to_uppercase();
+        let hardware = parts.next().unwrap_or("");
+        let instance = parts.next().unwrap_or("");
+
+        let mut parsed = Self {
+            enumerator,
+            ..Self::default()
+        };
+        for field in hardware.split('&') {
+            let Some((key, value)) = field.split_once('_') else {
+                continue;
+            };
+            match key.to_uppercase().as_str() {
+                "VID" | "VEN" => parsed.vid = parse_hex(value),
+                "PID" | "DEV" => parsed.pid = parse_hex(value),
+                "REV" => parsed.revision = Some(value.to_string()).filter(|v| !v.is_empty()),
+                "MI" => parsed.interface = u8::from_str_radix(value, 16).ok(),
+                _ => {}
+            }
+        }
+
+        // USBSTOR appends a LUN-ish `&0` to the serial it inherits.
+        let instance = if parsed.enumerator == "USBSTOR" {
+            strip_lun(instance)
+        } else {
+            instance
+        };
+        // Windows' `7&2A3B4C5D&0&1`, sysfs port names, PCI addresses and
+        // HID `phys` paths all name where the device is, not what it is.
+        parsed.port_derived = instance.is_empty()
+            || instance.contains('&')
+            || instance.contains('/')
+            || is_port_name(instance)
+            || is_pci_address(instance);
+        parsed.instance = instance.to_string();
+        parsed
+    }
+
+    /// `046D:C52B`, the format `UsbDevice::vid_pid` has always produced.
+    pub fn vid_pid(&self) -> Option<String> {
+        Some(format!("{:04X}:{:04X}", self.vid?, self.pid?))
+    }
+
+    /// The device's own serial number, if it reported one.
+    pub fn serial(&self) -> Option<&str> {
+        (!self.port_derived).then_some(self.instance.as_str())
+    }
+}
+
+/// Writes the ID back out in its canonical form, which parses to the same
+/// value: known hardware fields in a fixed order, padded hex, and the LUN
+/// `&0` back on a `USBSTOR` serial. Fields the parser ignores are lost.
+impl fmt::Display for DeviceInstanceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (vid_key, pid_key) = if Bus::from_enumerator(&self.enumerator).has_usb_ids() {
+            ("VID", "PID")
+        } else {
+            ("VEN", "DEV")
+        };
+        let mut fields = Vec::new();
+        if let Some(vid) = self.vid {
+            fields.push(format!("{}_{:04X}", vid_key, vid));
+        }
+        if let Some(pid) = self.pid {
+            fields.push(format!("{}_{:04X}", pid_key, pid));
+        }
+        if let Some(rev) = &self.revision {
+            fields.push(format!("REV_{}", rev));
+        }
+        if let Some(mi) = self.interface {
+            fields.push(format!("MI_{:02X}", mi));
+        }
+        write!(
+            f,
+            "{}\\{}\\{}",
+            self.enumerator,
+            fields.join("&"),
+            self.instance
+        )?;
+        if self.enumerator == "USBSTOR" {
+            write!(f, "&0")?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_hex(s: &str) -> Option<u16> {
+    let digits = s.get(..4).unwrap_or(s);
+    u16::from_str_radix(digits, 16).ok()
+}
+
+fn strip_lun(instance: &str) -> &str {
+    match instance.rsplit_once('&') {
+        Some((serial, lun)) if !lun.is_empty() && lun.bytes().all(|b| b.is_ascii_digit()) => serial,
+        _ => instance,
+    }
+}
+
+/// Linux sysfs device names: `1-2`, `3-1.4.2`, or `usb1` for a root hub.
+fn is_port_name(s: &str) -> bool {
+    if let Some(bus) = s.strip_prefix("usb") {
+        return !bus.is_empty() && bus.bytes().all(|b| b.is_ascii_digit());
+    }
+    let Some((bus, ports)) = s.split_once('-') else {
+        return false;
+    };
+    !bus.is_empty()
+        && bus.bytes().all(|b| b.is_ascii_digit())
+        && ports
+            .split('.')
+            .all(|p| !p.is_empty() && p.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Linux PCI addresses: `0000:3c:00.0` (domain:bus:device.function).
+fn is_pci_address(s: &str) -> bool {
+    let hex = |p: &str, n: usize| p.len() == n && p.bytes().all(|b| b.is_ascii_hexdigit());
+    let mut parts = s.split(':');
+    let (Some(domain), Some(bus), Some(slot), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return false;
+    };
+    let Some((device, function)) = slot.split_once('.') else {
+        return false;
+    };
+    hex(domain, 4) && hex(bus, 2) && hex(device, 2) && hex(function, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn usb_ids() {
+        let id = DeviceInstanceId::parse("USB\\VID_046D&PID_C52B&MI_01\\7&2A3B4C5D&0&0001");
+        assert_eq!(id.enumerator, "USB");
+        assert_eq!(id.vid_pid().as_deref(), Some("046D:C52B"));
+        assert_eq!(id.interface, Some(1));
+        assert_eq!(id.instance, "7&2A3B4C5D&0&0001");
+        assert!(id.port_derived);
+        assert_eq!(id.serial(), None);
+
+        let id = DeviceInstanceId::parse("usb\\vid_0781&pid_5581\\4C530001230101103413");
+        assert_eq!(id.enumerator, "USB");
+        assert_eq!(id.vid_pid().as_deref(), Some("0781:5581"));
+        assert_eq!(id.interface, None);
+        assert_eq!(id.serial(), Some("4C530001230101103413"));
+    }
+
+    #[test]
+    fn linux_ids() {
+        let cases = [
+            ("USB\\VID_0781&PID_5581\\1-2.3", true),
+            ("USB\\VID_1D6B&PID_0002\\usb1", true),
+            ("PCI\\VEN_8086&DEV_15EF\\0000:3c:00.0", true),
+            ("HID\\VID_046D&PID_B023\\usb-0000:00:14.0-2/input0", true),
+            ("BTHENUM\\VID_046D&PID_B023\\d4:2e:1f:00:aa:01", false),
+            ("USB\\VID_0781&PID_5581\\usbstick", false),
+        ];
+        for (raw, port_derived) in cases {
+            assert_eq!(
+                DeviceInstanceId::parse(raw).port_derived,
+                port_derived,
+                "{}",
+                raw
+            );
+        }
+        let pci = DeviceInstanceId::parse("PCI\\VEN_8086&DEV_15EF\\0000:3c:00.0");
+        assert_eq!(pci.vid_pid().as_deref(), Some("8086:15EF"));
+    }
+
+    #[test]
+    fn hid_ids() {
+        let id = DeviceInstanceId::parse("HID\\VID_046D&PID_C52B&MI_00\\8&1F2E3D4C&0&0000");
+        assert_eq!(id.enumerator, "HID");
+        assert_eq!(id.vid, Some(0x046D));
+        assert_eq!(id.interface, Some(0));
+        assert!(id.port_derived);
+    }
+
+    #[test]
+    fn usbstor_ids() {
+        let id = DeviceInstanceId::parse(
+            "USBSTOR\\Disk&Ven_SanDisk&Prod_Ultra&Rev_1.00\\4C530001230101103413&0",
+        );
+        assert_eq!(id.enumerator, "USBSTOR");
+        assert_eq!(id.vid, None);
+        assert_eq!(id.revision.as_deref(), Some("1.00"));
+        assert_eq!(id.serial(), Some("4C530001230101103413"));
+        // Windows' own made-up serials keep their `&`.
+        let id = DeviceInstanceId::parse("USBSTOR\\Disk&Ven_Generic\\7&1A2B3C4D&0&000000&0");
+        assert_eq!(id.instance, "7&1A2B3C4D&0&000000");
+        assert!(id.port_derived);
+    }
+
+    #[test]
+    fn malformed_ids() {
+        let empty = DeviceInstanceId::parse("");
+        assert_eq!(
+            empty,
+            DeviceInstanceId {
+                port_derived: true,
+                ..DeviceInstanceId::default()
+            }
+        );
+
+        let id = DeviceInstanceId::parse("ROOT");
+        assert_eq!(id.enumerator, "ROOT");
+        assert_eq!(id.instance, "");
+
+        let id = DeviceInstanceId::parse("USB\\VID_ZZZZ&PID_&MI_xyz&junk\\");
+        assert_eq!((id.vid, id.pid, id.interface), (None, None, None));
+        assert!(id.port_derived);
+
+        // Only the first four digits count; a fifth isn't an overflow.
+        let id = DeviceInstanceId::parse("USB\\VID_046DF&PID_C52B9\\x");
+        assert_eq!(id.vid_pid().as_deref(), Some("046D:C52B"));
+
+        // Backslashes past the second belong to the instance.
+        let id = DeviceInstanceId::parse("SWD\\MMDEVAPI\\{0.0.0}\\extra");
+        assert_eq!(id.instance, "{0.0.0}\\extra");
+    }
+
+    #[test]
+    fn displays_canonical_ids() {
+        for raw in [
+            "USB\\VID_046D&PID_C52B&MI_01\\7&2A3B4C5D&0&0001",
+            "PCI\\VEN_8086&DEV_15EF\\0000:3c:00.0",
+            "THUNDERBOLT\\VEN_8086&DEV_15EF\\d4030000-0070-0e1e-ffff-ffffffffffff",
+            "USBSTOR\\REV_1.00\\4C530001230101103413&0",
+        ] {
+            assert_eq!(DeviceInstanceId::parse(raw).to_string(), raw);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn parse_never_panics(s in "\\PC*") {
+            let _ = DeviceInstanceId::parse(&s);
+        }
+
+        #[test]
+        fn display_round_trips_id_shaped_input(
+            s in "[A-Za-z]{0,8}(\\\\([A-Za-z]{1,3}_[0-9A-Fa-f]{0,6}&?){0,5}(\\\\[ -~]{0,24}){0,2})?"
+        ) {
+            let id = DeviceInstanceId::parse(&s);
+            prop_assert_eq!(DeviceInstanceId::parse(&id.to_string()), id);
+        }
+
+        #[test]
+        fn display_round_trips(s in "\\PC*") {
+            let id = DeviceInstanceId::parse(&s);
+            prop_assert_eq!(DeviceInstanceId::parse(&id.to_string()), id);
+        }
+
+        #[test]
+        fn canonical_ids_print_back_unchanged(
+            enumerator in "USB|HID|BTHENUM",
+            vid in any::<u16>(),
+            pid in any::<u16>(),
+            mi in proptest::option::of(any::<u8>()),
+            instance in "[0-9A-Za-z&.-]{1,24}",
+        ) {
+            let mi = mi.map(|mi| format!("&MI_{:02X}", mi)).unwrap_or_default();
+            let raw = format!("{}\\VID_{:04X}&PID_{:04X}{}\\{}", enumerator, vid, pid, mi, instance);
+            prop_assert_eq!(DeviceInstanceId::parse(&raw).to_string(), raw);
+        }
+    }
+}
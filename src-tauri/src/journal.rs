@@ -0,0 +1,340 @@
+use crate::engine::parse_iso;
+use crate::logging::log_to_file;
+use crate::types::DeviceEvent;
+use chrono::{DateTime, Local, TimeDelta};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+const JOURNAL_FILE: &str = "device-history-events.jsonl";
+
+/// The live file is rotated once it's this big...
+const ROTATE_BYTES: u64 = 4 * 1024 * 1024;
+/// ...or once its first event is this much older than the next one.
+const ROTATE_AGE: TimeDelta = TimeDelta::days(30);
+/// Rotated files kept, `.1` being the newest.
+const KEEP_ROTATED: usize = 5;
+/// Events older than this are dropped when the journal is compacted.
+const MAX_AGE: TimeDelta = TimeDelta::days(365);
+/// How many of the latest events go back into the event log at startup.
+const LOAD_LIMIT: usize = 5000;
+
+/// `device-history-events.3.jsonl`.
+fn rotated(dir: &Path, n: usize) -> PathBuf {
+    dir.join(format!("device-history-events.{}.jsonl", n))
+}
+
+/// The live file, then the rotated ones from newest to oldest.
+fn files(dir: &Path) -> impl Iterator<Item = PathBuf> + '_ {
+    std::iter::once(dir.join(JOURNAL_FILE)).chain((1..=KEEP_ROTATED).map(|n| rotated(dir, n)))
+}
+
+/// Lines that don't parse, like one cut short by a crash, are skipped.
+fn read(path: &Path) -> Vec<DeviceEvent> {
+    let Ok(file) = File::open(path) else {
+        return Vec::new();
+    };
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect()
+}
+
+/// The latest events in the journal, oldest first, hidden ones included.
+pub fn load() -> Vec<DeviceEvent> {
+    load_from(Path::new("."))
+}
+
+/// `load` for a journal kept in `dir`.
+fn load_from(dir: &Path) -> Vec<DeviceEvent> {
+    let mut events = Vec::new();
+    for path in files(dir) {
+        if events.len() >= LOAD_LIMIT {
+            break;
+        }
+        let mut older = read(&path);
+        older.append(&mut events);
+        events = older;
+    }
+    let skip = events.len().saturating_sub(LOAD_LIMIT);
+    events.split_off(skip)
+}
+
+/// Rewrites each file without events older than `MAX_AGE` or lines that
+/// don't parse, and deletes files left empty. Files with nothing to drop
+/// aren't touched.
+fn compact(dir: &Path, now: DateTime<Local>) {
+    let cutoff = now - MAX_AGE;
+    for path in files(dir) {
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let lines: Vec<&str> = content.lines().collect();
+        let kept: Vec<&str> = lines
+            .iter()
+            .copied()
+            .filter(|line| {
+                serde_json::from_str::<DeviceEvent>(line)
+                    .ok()
+                    .and_then(|e| parse_iso(&e.timestamp))
+                    .is_some_and(|t| t >= cutoff)
+            })
+            .collect();
+        if kept.len() == lines.len() {
+            continue;
+        }
+        let result = if kept.is_empty() {
+            fs::remove_file(&path)
+        } else {
+            let tmp = path.with_extension("jsonl.tmp");
+            let mut out = kept.join("\n");
+            out.push('\n');
+            fs::write(&tmp, out).and_then(|_| fs::rename(&tmp, &path))
+        };
+        match result {
+            Ok(()) => log_to_file(&format!(
+                "JOURNAL: compacted {} — dropped {} of {} lines",
+                path.display(),
+                lines.len() - kept.len(),
+                lines.len()
+            )),
+            Err(e) => log_to_file(&format!(
+                "JOURNAL FAIL: compacting {}: {}",
+                path.display(),
+                e
+            )),
+        }
+    }
+}
+
+// ── Journal ────────────────────────────────────────────────────
+
+/// Append-only record of every event the monitor hands out, one JSON
+/// object per line in `device-history-events.jsonl`. Clearing the event
+/// log only hides events; they stay here until they age out.
+pub struct Journal {
+    /// Where the files live: the working directory, bar tests.
+    dir: PathBuf,
+    file: Option<File>,
+    /// Size of the live file.
+    bytes: u64,
+    /// When the live file's first event happened.
+    started: Option<DateTime<Local>>,
+}
+
+impl Journal {
+    /// Compacts the journal and opens the live file for appending.
+    pub fn open(now: DateTime<Local>) -> Self {
+        Self::open_in(".", now)
+    }
+
+    /// `open` for a journal kept in `dir`.
+    fn open_in(dir: impl Into<PathBuf>, now: DateTime<Local>) -> Self {
+        let dir = dir.into();
+        compact(&dir, now);
+        let started = read(&dir.join(JOURNAL_FILE))
+            .first()
+            .and_then(|e| parse_iso(&e.timestamp));
+        let mut journal = Self {
+            dir,
+            file: None,
+            bytes: 0,
+            started,
+        };
+        journal.reopen();
+        journal
+    }
+
+    fn reopen(&mut self) {
+        let path = self.dir.join(JOURNAL_FILE);
+        match OpenOptions::new().create(true).append(true).open(&path) {
+            Ok(file) => {
+                self.bytes = file.metadata().map_or(0, |m| m.len());
+                self.file = Some(file);
+            }
+            Err(e) => log_to_file(&format!("JOURNAL FAIL: opening {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Best effort, like the log: a full disk shouldn't take the monitor
+    /// down with it.
+    pub fn append(&mut self, events: &[DeviceEvent]) {
+        let Some(first) = events.first() else {
+            return;
+        };
+        let next = parse_iso(&first.timestamp);
+        let too_old = matches!((self.started, next), (Some(s), Some(n)) if n - s >= ROTATE_AGE);
+        if self.bytes >= ROTATE_BYTES || too_old {
+            self.rotate();
+        }
+        let Some(file) = self.file.as_mut() else {
+            return;
+        };
+        let mut lines = String::new();
+        for event in events {
+            if let Ok(line) = serde_json::to_string(event) {
+                lines.push_str(&line);
+                lines.push('\n');
+            }
+        }
+        if file.write_all(lines.as_bytes()).is_ok() {
+            self.bytes += lines.len() as u64;
+            self.started = self.started.or(next);
+        }
+    }
+
+    /// Shifts every rotated file up one, dropping the oldest, and starts
+    /// a new live file. The handle is closed first so Windows lets the
+    /// rename through.
+    fn rotate(&mut self) {
+        self.file = None;
+        let dir = &self.dir;
+        let _ = fs::remove_file(rotated(dir, KEEP_ROTATED));
+        for n in (1..KEEP_ROTATED).rev() {
+            let _ = fs::rename(rotated(dir, n), rotated(dir, n + 1));
+        }
+        if let Err(e) = fs::rename(dir.join(JOURNAL_FILE), rotated(dir, 1)) {
+            log_to_file(&format!("JOURNAL FAIL: rotating {}: {}", JOURNAL_FILE, e));
+        }
+        self.started = None;
+        self.reopen();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::format_iso;
+    use crate::types::EventKind;
+    use chrono::{TimeZone, Utc};
+
+    /// Days after a fixed instant; the journal takes every time from its
+    /// caller, like the engine.
+    fn at(days: f64) -> DateTime<Local> {
+        let start = Utc.with_ymd_and_hms(2024, 3, 9, 14, 0, 0).unwrap();
+        (start + TimeDelta::seconds((days * 86_400.0) as i64)).with_timezone(&Local)
+    }
+
+    fn event(seq: u64, days: f64) -> DeviceEvent {
+        DeviceEvent {
+            timestamp: format_iso(at(days)),
+            seq,
+            kind: EventKind::Connect,
+            name: "Ultra".to_string(),
+            vid_pid: Some("0781:5581".to_string()),
+            manufacturer: None,
+            class: "DiskDrive".to_string(),
+            bus: Default::default(),
+            category: Default::default(),
+            device_id: "USB\\VID_0781&PID_5581\\4C530001".to_string(),
+        }
+    }
+
+    fn seqs(events: &[DeviceEvent]) -> Vec<u64> {
+        events.iter().map(|e| e.seq).collect()
+    }
+
+    /// Writes `events` as a journal file the way `append` does.
+    fn write(path: &Path, events: &[DeviceEvent]) {
+        let lines: String = events
+            .iter()
+            .map(|e| serde_json::to_string(e).unwrap() + "\n")
+            .collect();
+        fs::write(path, lines).unwrap();
+    }
+
+    #[test]
+    fn events_come_back_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut journal = Journal::open_in(dir.path(), at(0.0));
+        journal.append(&[event(1, 0.0), event(2, 0.0)]);
+        journal.append(&[]);
+        drop(journal);
+
+        // A line cut short by a crash is skipped.
+        let live = dir.path().join(JOURNAL_FILE);
+        let mut file = OpenOptions::new().append(true).open(&live).unwrap();
+        file.write_all(b"{\"timestamp\":\"2024-").unwrap();
+        drop(file);
+        let mut journal = Journal::open_in(dir.path(), at(1.0));
+        journal.append(&[event(3, 1.0)]);
+        assert_eq!(seqs(&load_from(dir.path())), [1, 2, 3]);
+    }
+
+    #[test]
+    fn rotates_when_the_live_file_is_full() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut journal = Journal::open_in(dir.path(), at(0.0));
+        journal.append(&[event(1, 0.0)]);
+        journal.bytes = ROTATE_BYTES;
+        journal.append(&[event(2, 0.0)]);
+        assert_eq!(seqs(&read(&rotated(dir.path(), 1))), [1]);
+        assert_eq!(seqs(&read(&dir.path().join(JOURNAL_FILE))), [2]);
+        assert!(journal.bytes < ROTATE_BYTES);
+    }
+
+    #[test]
+    fn rotates_when_the_live_file_is_a_month_old() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut journal = Journal::open_in(dir.path(), at(0.0));
+        journal.append(&[event(1, 0.0)]);
+        journal.append(&[event(2, 29.0)]);
+        assert!(!rotated(dir.path(), 1).exists());
+        journal.append(&[event(3, 30.0)]);
+        assert_eq!(seqs(&read(&rotated(dir.path(), 1))), [1, 2]);
+
+        // The live file's age survives a restart.
+        drop(journal);
+        let mut journal = Journal::open_in(dir.path(), at(45.0));
+        journal.append(&[event(4, 60.0)]);
+        assert_eq!(seqs(&read(&rotated(dir.path(), 1))), [3]);
+        assert_eq!(seqs(&read(&rotated(dir.path(), 2))), [1, 2]);
+    }
+
+    #[test]
+    fn keeps_five_rotated_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut journal = Journal::open_in(dir.path(), at(0.0));
+        for seq in 1..=8 {
+            journal.bytes = ROTATE_BYTES;
+            journal.append(&[event(seq, 0.0)]);
+        }
+        // The live file holds 8, then 7 down to 3 in `.1` to `.5`.
+        assert_eq!(seqs(&read(&rotated(dir.path(), 1))), [7]);
+        assert_eq!(seqs(&read(&rotated(dir.path(), KEEP_ROTATED))), [3]);
+        assert!(!rotated(dir.path(), KEEP_ROTATED + 1).exists());
+        assert_eq!(seqs(&load_from(dir.path())), [3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn compaction_drops_events_a_year_old_by_the_callers_clock() {
+        let dir = tempfile::tempdir().unwrap();
+        write(&rotated(dir.path(), 2), &[event(1, 0.0), event(2, 10.0)]);
+        write(&rotated(dir.path(), 1), &[event(3, 20.0), event(4, 100.0)]);
+        write(&dir.path().join(JOURNAL_FILE), &[event(5, 300.0)]);
+
+        // Nothing is old yet, however long ago the dates really are.
+        Journal::open_in(dir.path(), at(300.0));
+        assert_eq!(seqs(&load_from(dir.path())), [1, 2, 3, 4, 5]);
+
+        Journal::open_in(dir.path(), at(390.0));
+        assert!(!rotated(dir.path(), 2).exists());
+        assert_eq!(seqs(&read(&rotated(dir.path(), 1))), [4]);
+        assert_eq!(seqs(&load_from(dir.path())), [4, 5]);
+        assert!(!rotated(dir.path(), 1).with_extension("jsonl.tmp").exists());
+    }
+
+    #[test]
+    fn loads_only_the_latest_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let older: Vec<_> = (1..=4000).map(|seq| event(seq, 0.0)).collect();
+        let newer: Vec<_> = (4001..=6000).map(|seq| event(seq, 1.0)).collect();
+        write(&rotated(dir.path(), 1), &older);
+        write(&dir.path().join(JOURNAL_FILE), &newer);
+        let events = load_from(dir.path());
+        assert_eq!(events.len(), LOAD_LIMIT);
+        assert_eq!(events.first().map(|e| e.seq), Some(1001));
+        assert_eq!(events.last().map(|e| e.seq), Some(6000));
+    }
+}
@@ -1,16 +1,45 @@
 mod cache;
 mod cli;
 mod commands;
+mod counterfeit;
+mod descriptors;
+mod engine;
+mod enrich;
+mod instance_id;
+mod journal;
 mod logging;
 mod monitor;
+mod power;
+mod rules;
+mod source;
 mod state;
 mod storage;
+mod topology;
 mod types;
+mod usb_ids;
+mod volumes;
 
+use source::{SourceKind, SourceSpec};
 use state::AppState;
 
+fn source_spec() -> SourceSpec {
+    let args: Vec<String> = std::env::args().collect();
+    SourceSpec::from_args(&args)
+}
+
 pub fn run_cli_mode() {
-    cli::run_cli();
+    let args: Vec<String> = std::env::args().collect();
+    match source::flag_value(&args, "--cli") {
+        Some("tree") => cli::run_tree(source_spec()),
+        Some("volumes") => cli::run_volumes(source::flag_value(&args, "volumes")),
+        Some("describe") => {
+            cli::run_describe(source_spec(), source::flag_value(&args, "describe"))
+        }
+        _ => {
+            let expand = args.iter().any(|a| a == "--expand-interfaces");
+            cli::run_cli(source_spec(), expand);
+        }
+    }
 }
 use std::sync::Arc;
 use tauri::{
@@ -23,7 +52,13 @@ use tauri::{
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     let prefs = commands::prefs::load_initial_prefs();
-    let app_state = Arc::new(AppState::new(prefs.theme, prefs.active_tab));
+    let spec = source_spec();
+    // The journal is real history; a simulation or replay starts empty.
+    let history = match spec.kind {
+        SourceKind::Native => journal::load(),
+        _ => Vec::new(),
+    };
+    let app_state = Arc::new(AppState::new(prefs, history));
 
     tauri::Builder::default()
         .plugin(tauri_plugin_clipboard_manager::init())
@@ -31,12 +66,20 @@ pub fn run() {
         .manage(app_state.clone())
         .invoke_handler(tauri::generate_handler![
             commands::snapshot::get_snapshot,
+            commands::snapshot::get_topology,
+            commands::descriptors::get_device_details,
             commands::nicknames::set_nickname,
             commands::nicknames::forget_device,
             commands::events::clear_events,
+            commands::usb_ids::reload_usb_ids,
+            commands::rules::reload_rules,
+            commands::storage::refresh_storage,
+            commands::storage::get_volume_history,
+            commands::storage::set_fill_alert,
             commands::prefs::get_prefs,
             commands::prefs::set_theme,
             commands::prefs::set_tab,
+            commands::prefs::set_expand_interfaces,
             commands::system::check_for_updates,
             commands::system::copy_to_clipboard,
             commands::system::open_url,
@@ -92,7 +135,7 @@ pub fn run() {
 
             // ── Start monitor thread ──
             let handle = app.handle().clone();
-            monitor::start_monitor(handle, app_state);
+            monitor::start_monitor(handle, app_state, spec);
 
             Ok(())
         })
@@ -1,11 +1,34 @@
+use crate::types::DeviceEvent;
 use chrono::Local;
 use std::fs::OpenOptions;
 use std::io::Write;
 
 pub fn log_to_file(msg: &str) {
+    // Tests run code that logs; keep them from writing into the tree.
+    if cfg!(test) {
+        return;
+    }
     let path = "device-history.log";
     if let Ok(mut f) = OpenOptions::new().create(true).append(true).open(path) {
         let ts = Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
         let _ = writeln!(f, "[{}] {}", ts, msg);
     }
 }
+
+/// `CONNECT: SanDisk Ultra [0781:5581] | USB\VID_0781...`, with the
+/// event's detail after the VID:PID when it has one.
+pub fn log_event(event: &DeviceEvent) {
+    let detail = event
+        .kind
+        .detail()
+        .map(|d| format!(" — {}", d))
+        .unwrap_or_default();
+    log_to_file(&format!(
+        "{}: {} [{}]{} | {}",
+        event.kind.name().to_uppercase(),
+        event.name,
+        event.vid_pid.as_deref().unwrap_or("?"),
+        detail,
+        event.device_id
+    ));
+}
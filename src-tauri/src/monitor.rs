@@ -1,44 +1,24 @@
 use crate::cache::{load_cache, save_cache};
-use crate::logging::log_to_file;
+use crate::engine::{Engine, Outcome};
+use crate::enrich::EnrichWorker;
+use crate::journal::Journal;
+use crate::logging::{log_event, log_to_file};
+use crate::rules;
+use crate::source::{DeviceSource, SourceKind, SourceSpec};
 use crate::state::AppState;
-use crate::storage::{is_storage_device, query_storage_info};
-use crate::types::{DeviceEvent, DeviceSnapshot, KnownDevice, StorageInfo, UsbDevice};
-use chrono::Local;
-use std::collections::HashMap;
+use crate::usb_ids;
+use chrono::{DateTime, Local};
 use std::sync::Arc;
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 use tauri::{AppHandle, Emitter};
-use wmi::{COMLibrary, WMIConnection};
 
-fn query_devices(wmi: &WMIConnection) -> Option<HashMap<String, UsbDevice>> {
-    let results: Vec<UsbDevice> = wmi
-        .raw_query(
-            "SELECT Name, DeviceID, Description, Manufacturer, PNPClass \
-             FROM Win32_PnPEntity WHERE DeviceID LIKE 'USB%'",
-        )
-        .ok()?;
-    Some(
-        results
-            .into_iter()
-            .filter_map(|d| Some((d.DeviceID.clone()?, d)))
-            .collect(),
-    )
-}
-
-fn usb_to_snapshot(id: &str, dev: &UsbDevice) -> DeviceSnapshot {
-    DeviceSnapshot {
-        device_id: id.to_string(),
-        name: dev.display_name().to_string(),
-        vid_pid: dev.vid_pid(),
-        manufacturer: dev.Manufacturer.clone(),
-        class: dev.class().to_string(),
-    }
-}
+/// How often to check on the enrichment worker while it has lookups out.
+const WORKER_POLL: Duration = Duration::from_millis(250);
 
-pub fn start_monitor(app_handle: AppHandle, state: Arc<AppState>) {
+pub fn start_monitor(app_handle: AppHandle, state: Arc<AppState>, spec: SourceSpec) {
     thread::spawn(move || {
-        monitor_loop(app_handle, state);
+        monitor_loop(app_handle, state, spec);
     });
 }
 
@@ -47,305 +27,204 @@ fn emit_update(app_handle: &AppHandle, state: &AppState) {
     let _ = app_handle.emit("device-update", &snapshot);
 }
 
-fn monitor_loop(app_handle: AppHandle, state: Arc<AppState>) {
-    let com = match COMLibrary::new() {
-        Ok(c) => c,
-        Err(e) => {
-            *state.error.write() = Some(format!("COM init failed: {}", e));
-            emit_update(&app_handle, &state);
-            return;
-        }
-    };
-    let wmi = match WMIConnection::new(com) {
-        Ok(w) => w,
-        Err(e) => {
-            *state.error.write() = Some(format!("WMI connect failed: {}", e));
-            emit_update(&app_handle, &state);
-            return;
-        }
-    };
-
-    let mut prev = match query_devices(&wmi) {
-        Some(d) => d,
-        None => {
-            *state.error.write() = Some("Failed to query USB devices".into());
-            emit_update(&app_handle, &state);
-            return;
-        }
-    };
+fn publish(app_handle: &AppHandle, state: &AppState, engine: &Engine) {
+    *state.devices.write() = engine.snapshot();
+    *state.known_devices.write() = engine.known().clone();
+    *state.storage_info.write() = engine.storage().clone();
+    emit_update(app_handle, state);
+}
 
-    let mut known_cache = load_cache();
-    let mut storage_map: HashMap<String, StorageInfo> = HashMap::new();
-    let mut all_events: Vec<DeviceEvent> = Vec::new();
+/// Logs, journals and stores an outcome's events, and saves the cache if
+/// the known devices changed. Returns whether there's anything new to
+/// publish.
+fn apply_outcome(
+    out: Outcome,
+    state: &AppState,
+    engine: &Engine,
+    journal: Option<&mut Journal>,
+) -> bool {
+    for event in &out.events {
+        log_event(event);
+    }
+    if let Some(journal) = journal {
+        journal.append(&out.events);
+    }
+    if out.known_changed {
+        save_cache(&engine.cache());
+    }
+    let changed = !out.is_empty();
+    state.events.write().extend(out.events);
+    changed
+}
 
-    // Initial snapshot — merge into cache
-    {
-        let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-        for dev in known_cache.devices.values_mut() {
-            dev.currently_connected = false;
-        }
-        for (id, dev) in &prev {
-            let is_new = !known_cache.devices.contains_key(id);
-            let entry = known_cache
-                .devices
-                .entry(id.clone())
-                .or_insert_with(|| KnownDevice {
-                    device_id: id.clone(),
-                    name: dev.display_name().to_string(),
-                    vid_pid: dev.vid_pid().unwrap_or_default(),
-                    class: dev.class().to_string(),
-                    manufacturer: dev.Manufacturer.clone().unwrap_or_default(),
-                    description: dev.Description.clone().unwrap_or_default(),
-                    first_seen: now.clone(),
-                    last_seen: now.clone(),
-                    times_seen: 1,
-                    currently_connected: true,
-                    nickname: None,
-                    storage_info: None,
-                });
-            if !is_new {
-                entry.last_seen = now.clone();
-                entry.currently_connected = true;
-                entry.name = dev.display_name().to_string();
-                entry.vid_pid = dev.vid_pid().unwrap_or_default();
-                entry.class = dev.class().to_string();
-                entry.manufacturer = dev.Manufacturer.clone().unwrap_or_default();
-                entry.description = dev.Description.clone().unwrap_or_default();
+/// Hands the storage lookups the engine has due to the worker, or runs
+/// them here for sources without one, and feeds back whatever finished.
+/// Storage queries are the slow part of a tick, so they stay out of the
+/// engine.
+pub(crate) fn run_enrichments(
+    source: &mut dyn DeviceSource,
+    worker: Option<&EnrichWorker>,
+    engine: &mut Engine,
+) -> Outcome {
+    let mut results = Vec::new();
+    for id in engine.due_enrichments(source.now()) {
+        match worker {
+            Some(worker) => worker.request(&id),
+            None => {
+                let info = source.query_storage_info(&id);
+                results.push((id, info));
             }
         }
-        save_cache(&known_cache);
+    }
+    if let Some(worker) = worker {
+        results.extend(worker.finished());
     }
 
-    // Initial enrichment for connected storage devices
-    for (id, dev) in &prev {
-        if is_storage_device(dev) {
-            if let Some(info) = query_storage_info(&wmi, id) {
-                log_to_file(&format!(
-                    "ENRICHED (startup): {} → {} [{}]",
-                    id,
-                    info.model,
-                    info.volumes
-                        .iter()
-                        .map(|v| v.drive_letter.as_str())
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                ));
-                storage_map.insert(id.clone(), info.clone());
-                if let Some(kd) = known_cache.devices.get_mut(id) {
-                    kd.storage_info = Some(info);
-                }
-                save_cache(&known_cache);
-            }
+    let mut out = Outcome::default();
+    for (id, info) in results {
+        match &info {
+            Some(info) => log_to_file(&format!(
+                "ENRICHED: {} → {} [{}]",
+                id,
+                info.model,
+                info.volumes
+                    .iter()
+                    .map(|v| v.drive_letter.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            None => log_to_file(&format!("ENRICH MISS: {}", id)),
         }
+        out.merge(engine.enriched(&id, info, source.now()));
     }
+    out
+}
 
-    // Push initial state
-    {
-        let mut sorted: Vec<_> = prev.iter().map(|(id, d)| usb_to_snapshot(id, d)).collect();
-        sorted.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-        *state.devices.write() = sorted;
-        *state.known_devices.write() = known_cache.devices.clone();
-        *state.storage_info.write() = storage_map.clone();
-        emit_update(&app_handle, &state);
+/// The part of a tick that needs only the source and the engine: waits
+/// for changes or the next due lookup, then feeds in finished lookups,
+/// hotplug changes and, when due, a fresh enumeration. Replay tests run
+/// recordings through this too.
+pub(crate) fn tick(
+    source: &mut dyn DeviceSource,
+    worker: Option<&EnrichWorker>,
+    engine: &mut Engine,
+    last_resync: &mut DateTime<Local>,
+) -> Outcome {
+    // Don't let a long event-driven wait hold up a due enrichment or a
+    // finished lookup.
+    let mut timeout = engine
+        .next_enrichment()
+        .map(|due| (due - source.now()).to_std().unwrap_or_default())
+        .map_or(source.poll_interval(), |d| d.min(source.poll_interval()));
+    if engine.enriching() {
+        timeout = timeout.min(WORKER_POLL);
     }
-
-    log_to_file(&format!("Started monitoring — {} devices", prev.len()));
-
-    let mut pending_enrichments: Vec<(String, Instant)> = Vec::new();
-
-    loop {
-        thread::sleep(Duration::from_millis(500));
-
-        // Process pending enrichments (2s delay for drives to mount)
-        let now_instant = Instant::now();
-        let ready: Vec<String> = pending_enrichments
-            .iter()
-            .filter(|(_, scheduled)| {
-                now_instant.duration_since(*scheduled) >= Duration::from_secs(2)
-            })
-            .map(|(id, _)| id.clone())
-            .collect();
-        pending_enrichments.retain(|(_, scheduled)| {
-            now_instant.duration_since(*scheduled) < Duration::from_secs(2)
-        });
-        let mut enriched = false;
-        for enrich_id in ready {
-            if let Some(info) = query_storage_info(&wmi, &enrich_id) {
-                log_to_file(&format!(
-                    "ENRICHED: {} → {} [{}]",
-                    enrich_id,
-                    info.model,
-                    info.volumes
-                        .iter()
-                        .map(|v| v.drive_letter.as_str())
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                ));
-                storage_map.insert(enrich_id.clone(), info.clone());
-                if let Some(kd) = known_cache.devices.get_mut(&enrich_id) {
-                    kd.storage_info = Some(info);
-                }
-                save_cache(&known_cache);
-                enriched = true;
+    let changes = source.wait_for_changes(timeout);
+    let had_hotplug = !changes.is_empty();
+    let now = source.now();
+
+    let mut out = run_enrichments(source, worker, engine);
+    out.merge(engine.hotplug(changes, now));
+
+    // Polling sources enumerate every tick; event-driven ones only when
+    // idle or due for a periodic resync.
+    let since_resync = (now - *last_resync).to_std().unwrap_or_default();
+    if !had_hotplug || since_resync >= source.poll_interval() {
+        match source.enumerate() {
+            Ok(enumerated) => {
+                *last_resync = now;
+                out.merge(engine.enumerated(enumerated, now));
             }
+            Err(e) => out.merge(engine.enumeration_failed(&e, now)),
         }
+    }
+    out
+}
 
-        let Some(current) = query_devices(&wmi) else {
-            continue;
-        };
-
-        let mut new_events = Vec::new();
-        let ts = Local::now().format("%H:%M:%S").to_string();
-        let now_iso = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-
-        for (id, dev) in &prev {
-            if !current.contains_key(id) {
-                let event = DeviceEvent {
-                    timestamp: ts.clone(),
-                    kind: "disconnect".to_string(),
-                    name: dev.display_name().to_string(),
-                    vid_pid: dev.vid_pid(),
-                    manufacturer: dev.Manufacturer.clone(),
-                    class: dev.class().to_string(),
-                    device_id: id.clone(),
-                };
-                log_to_file(&format!(
-                    "DISCONNECT: {} [{}] | {}",
-                    event.name,
-                    event.vid_pid.as_deref().unwrap_or("?"),
-                    id
-                ));
-                new_events.push(event);
-            }
+fn monitor_loop(app_handle: AppHandle, state: Arc<AppState>, spec: SourceSpec) {
+    let mut source = match spec.open() {
+        Ok(s) => s,
+        Err(e) => {
+            *state.error.write() = Some(e);
+            emit_update(&app_handle, &state);
+            return;
         }
+    };
 
-        for (id, dev) in &current {
-            if !prev.contains_key(id) {
-                let event = DeviceEvent {
-                    timestamp: ts.clone(),
-                    kind: "connect".to_string(),
-                    name: dev.display_name().to_string(),
-                    vid_pid: dev.vid_pid(),
-                    manufacturer: dev.Manufacturer.clone(),
-                    class: dev.class().to_string(),
-                    device_id: id.clone(),
-                };
-                log_to_file(&format!(
-                    "CONNECT: {} [{}] | {}",
-                    event.name,
-                    event.vid_pid.as_deref().unwrap_or("?"),
-                    id
-                ));
-                new_events.push(event);
-            }
+    let attached = match source.enumerate() {
+        Ok(d) => d,
+        Err(e) => {
+            *state.error.write() = Some(format!("Failed to query USB devices: {}", e));
+            emit_update(&app_handle, &state);
+            return;
         }
+    };
 
-        if !new_events.is_empty() {
-            let enrich_ids: Vec<String> = new_events
-                .iter()
-                .filter(|e| e.kind == "connect")
-                .filter(|e| {
-                    current
-                        .get(&e.device_id)
-                        .map_or(false, |d| is_storage_device(d))
-                })
-                .map(|e| e.device_id.clone())
-                .collect();
-
-            for event in &new_events {
-                match event.kind.as_str() {
-                    "connect" => {
-                        if let Some(dev) = current.get(&event.device_id) {
-                            let is_new =
-                                !known_cache.devices.contains_key(&event.device_id);
-                            let entry = known_cache
-                                .devices
-                                .entry(event.device_id.clone())
-                                .or_insert_with(|| KnownDevice {
-                                    device_id: event.device_id.clone(),
-                                    name: dev.display_name().to_string(),
-                                    vid_pid: dev.vid_pid().unwrap_or_default(),
-                                    class: dev.class().to_string(),
-                                    manufacturer: dev.Manufacturer.clone().unwrap_or_default(),
-                                    description: dev.Description.clone().unwrap_or_default(),
-                                    first_seen: now_iso.clone(),
-                                    last_seen: now_iso.clone(),
-                                    times_seen: 0,
-                                    currently_connected: true,
-                                    nickname: None,
-                                    storage_info: None,
-                                });
-                            entry.times_seen += 1;
-                            entry.last_seen = now_iso.clone();
-                            entry.currently_connected = true;
-                            if !is_new {
-                                entry.name = dev.display_name().to_string();
-                                entry.vid_pid = dev.vid_pid().unwrap_or_default();
-                                entry.class = dev.class().to_string();
-                                entry.manufacturer = dev.Manufacturer.clone().unwrap_or_default();
-                                entry.description = dev.Description.clone().unwrap_or_default();
-                            }
-                        }
-                    }
-                    "disconnect" => {
-                        if let Some(entry) = known_cache.devices.get_mut(&event.device_id) {
-                            entry.last_seen = now_iso.clone();
-                            entry.currently_connected = false;
-                        }
-                        storage_map.remove(&event.device_id);
-                    }
-                    _ => {}
-                }
-            }
-
-            all_events.extend(new_events);
-            save_cache(&known_cache);
-
-            for id in enrich_ids {
-                pending_enrichments.push((id, Instant::now()));
-            }
+    // Times come from the source rather than the system clock, so a
+    // replay schedules enrichments and resyncs exactly as recorded.
+    let mut engine = Engine::new(
+        load_cache().devices,
+        attached,
+        usb_ids::current(),
+        rules::current(),
+        source.now(),
+    );
+    // Number on from the journal, so clearing the log keeps hiding what
+    // was cleared.
+    let last_seq = state.events.read().last().map_or(0, |e| e.seq);
+    engine.continue_numbering(last_seq.max(*state.events_cleared_through.read()));
+    // Simulated and replayed devices never happened, so only the native
+    // source keeps a journal.
+    let mut journal = matches!(spec.kind, SourceKind::Native).then(|| Journal::open(source.now()));
+    save_cache(&engine.cache());
+    let worker = source.storage_source().map(EnrichWorker::spawn);
+    // Sources without a worker finish their lookups right here; the
+    // `storage_enriched` events go to the log like any later ones.
+    let out = run_enrichments(source.as_mut(), worker.as_ref(), &mut engine);
+    apply_outcome(out, &state, &engine, journal.as_mut());
+    publish(&app_handle, &state, &engine);
+
+    log_to_file(&format!(
+        "Started monitoring ({}) — {} devices",
+        source.name(),
+        engine.attached().len()
+    ));
+
+    let mut last_resync = source.now();
+    loop {
+        // Pick up forgets, nicknames and fill alerts from commands first,
+        // while the shared state still matches what the engine last
+        // published.
+        let mut out = engine.sync_known(&state.known_devices.read());
+        for id in std::mem::take(&mut *state.storage_refresh.write()) {
+            engine.refresh(&id, source.now());
         }
-
-        // Check if we need to update the known_devices from external changes (nickname, forget)
-        // We re-read cache periodically to pick up command-side mutations
-        {
-            let cmd_known = state.known_devices.read().clone();
-            // Sync: if a device was forgotten via command, remove from our cache too
-            let our_ids: Vec<String> = known_cache.devices.keys().cloned().collect();
-            for id in &our_ids {
-                if !cmd_known.contains_key(id) {
-                    known_cache.devices.remove(id);
-                    storage_map.remove(id);
-                    save_cache(&known_cache);
-                }
-            }
-            // Sync nicknames from commands
-            for (id, cmd_dev) in &cmd_known {
-                if let Some(our_dev) = known_cache.devices.get_mut(id) {
-                    if our_dev.nickname != cmd_dev.nickname {
-                        our_dev.nickname = cmd_dev.nickname.clone();
-                        save_cache(&known_cache);
-                    }
-                }
-            }
+        let ids = usb_ids::current();
+        if !Arc::ptr_eq(engine.usb_ids(), &ids) {
+            out.merge(engine.set_usb_ids(ids));
+        }
+        let rules = rules::current();
+        if !Arc::ptr_eq(engine.rules(), &rules) {
+            out.merge(engine.set_rules(rules, source.now()));
         }
 
-        let has_changes = !all_events.is_empty() || enriched || prev.len() != current.len();
-
-        if has_changes {
-            let mut sorted: Vec<_> = current
-                .iter()
-                .map(|(id, d)| usb_to_snapshot(id, d))
-                .collect();
-            sorted.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+        out.merge(tick(
+            source.as_mut(),
+            worker.as_ref(),
+            &mut engine,
+            &mut last_resync,
+        ));
 
-            *state.devices.write() = sorted;
-            *state.events.write() = all_events.clone();
-            *state.known_devices.write() = known_cache.devices.clone();
-            *state.storage_info.write() = storage_map.clone();
-            emit_update(&app_handle, &state);
+        if apply_outcome(out, &state, &engine, journal.as_mut()) {
+            publish(&app_handle, &state, &engine);
         }
 
-        prev = current;
+        if source.finished() && engine.settled() {
+            log_to_file(&format!(
+                "Stopped monitoring ({}) — end of input",
+                source.name()
+            ));
+            return;
+        }
     }
 }
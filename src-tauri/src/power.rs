@@ -0,0 +1,211 @@
+use crate::source::DeviceMap;
+use crate::types::{HubPower, UsbDevice};
+use std::collections::{BTreeMap, HashMap};
+
+/// What a USB 2.0 port supplies.
+const USB2_PORT_MA: u32 = 500;
+/// What a USB 3.x port supplies.
+const USB3_PORT_MA: u32 = 900;
+/// USB allows seven tiers; anything deeper is a parent loop.
+pub const MAX_TIERS: usize = 7;
+
+/// Sums what each hub's downstream devices draw and checks it against
+/// what the hub can supply. Root hubs and self-powered hubs give every
+/// port its full allowance; a bus-powered hub shares whatever is left of
+/// its own upstream port once it has taken its own `bMaxPower`.
+///
+/// Only hubs whose power figures the backend reports are included.
+pub fn hub_power(devices: &DeviceMap) -> BTreeMap<String, HubPower> {
+    let mut children: HashMap<&str, Vec<&UsbDevice>> = HashMap::new();
+    for dev in devices.values() {
+        if let Some(parent) = &dev.Parent {
+            children.entry(parent.as_str()).or_default().push(dev);
+        }
+    }
+
+    let mut hubs = BTreeMap::new();
+    for (id, dev) in devices {
+        let Some(power) = &dev.Power else { continue };
+        let Some(ports) = power.hub_ports.filter(|p| *p > 0) else {
+            continue;
+        };
+        let port_ma = port_supply(dev);
+        let draws: Vec<u32> = children
+            .get(id.as_str())
+            .map(|c| c.iter().map(|d| draw(d, &children, 0)).collect())
+            .unwrap_or_default();
+        let used_ma = draws.iter().sum();
+        let (budget_ma, exceeded) = if power.self_powered {
+            let budget = u32::from(ports) * port_ma;
+            (
+                budget,
+                used_ma > budget || draws.iter().any(|d| *d > port_ma),
+            )
+        } else {
+            let budget = port_ma.saturating_sub(power.max_power_ma);
+            (budget, used_ma > budget)
+        };
+        hubs.insert(
+            id.clone(),
+            HubPower {
+                budget_ma,
+                used_ma,
+                exceeded,
+            },
+        );
+    }
+    hubs
+}
+
+/// Current a device pulls from its upstream port: its own `bMaxPower`,
+/// plus everything below it when it's a bus-powered hub.
+fn draw(dev: &UsbDevice, children: &HashMap<&str, Vec<&UsbDevice>>, depth: usize) -> u32 {
+    let Some(power) = &dev.Power else { return 0 };
+    let own = power.max_power_ma;
+    if power.self_powered || power.hub_ports.is_none() || depth >= MAX_TIERS {
+        return own;
+    }
+    let below: u32 = dev
+        .DeviceID
+        .as_deref()
+        .and_then(|id| children.get(id))
+        .map(|c| c.iter().map(|d| draw(d, children, depth + 1)).sum())
+        .unwrap_or(0);
+    own + below
+}
+
+/// Per-port supply of a hub, going by the speed it runs at.
+fn port_supply(hub: &UsbDevice) -> u32 {
+    match hub.Speed {
+        Some(speed) if speed.is_superspeed() => USB3_PORT_MA,
+        _ => USB2_PORT_MA,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DevicePower, LinkSpeed};
+
+    const ROOT: &str = "USB\\VID_1D6B&PID_0002\\usb1";
+    const HUB: &str = "USB\\VID_05E3&PID_0610\\1-2";
+
+    /// A device drawing `max_power_ma`; `ports` makes it a hub.
+    fn device(
+        id: &str,
+        parent: Option<&str>,
+        max_power_ma: u32,
+        self_powered: bool,
+        ports: Option<u8>,
+    ) -> (String, UsbDevice) {
+        let dev = UsbDevice {
+            Name: None,
+            DeviceID: Some(id.to_string()),
+            Description: None,
+            Manufacturer: None,
+            PNPClass: None,
+            Service: None,
+            CompatibleID: None,
+            Interfaces: Vec::new(),
+            Parent: parent.map(str::to_string),
+            LocationPath: None,
+            Speed: None,
+            UsbVersion: None,
+            Power: Some(DevicePower {
+                max_power_ma,
+                self_powered,
+                hub_ports: ports,
+            }),
+            Rule: None,
+        };
+        (id.to_string(), dev)
+    }
+
+    fn root_hub() -> (String, UsbDevice) {
+        device(ROOT, None, 0, true, Some(4))
+    }
+
+    fn budget(used_ma: u32, budget_ma: u32, exceeded: bool) -> HubPower {
+        HubPower {
+            budget_ma,
+            used_ma,
+            exceeded,
+        }
+    }
+
+    #[test]
+    fn self_powered_hubs_give_each_port_its_allowance() {
+        let devices: DeviceMap = [
+            root_hub(),
+            device("a", Some(ROOT), 100, false, None),
+            device("b", Some(ROOT), 500, false, None),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(hub_power(&devices)[ROOT], budget(600, 2000, false));
+
+        // One port can't lend its spare current to another.
+        let devices: DeviceMap = [root_hub(), device("a", Some(ROOT), 900, false, None)]
+            .into_iter()
+            .collect();
+        assert_eq!(hub_power(&devices)[ROOT], budget(900, 2000, true));
+    }
+
+    #[test]
+    fn superspeed_ports_supply_more() {
+        let mut root = root_hub();
+        root.1.Speed = Some(LinkSpeed::Super);
+        let devices: DeviceMap = [root, device("a", Some(ROOT), 896, false, None)]
+            .into_iter()
+            .collect();
+        assert_eq!(hub_power(&devices)[ROOT], budget(896, 3600, false));
+    }
+
+    #[test]
+    fn bus_powered_hubs_share_their_upstream_port() {
+        let devices: DeviceMap = [
+            root_hub(),
+            device(HUB, Some(ROOT), 100, false, Some(4)),
+            device("a", Some(HUB), 200, false, None),
+            device("b", Some(HUB), 300, false, None),
+        ]
+        .into_iter()
+        .collect();
+        let hubs = hub_power(&devices);
+        // 500 mA upstream, less the hub's own 100.
+        assert_eq!(hubs[HUB], budget(500, 400, true));
+        // The root port carries the hub and everything on it.
+        assert_eq!(hubs[ROOT], budget(600, 2000, true));
+    }
+
+    #[test]
+    fn hubs_without_power_figures_are_left_out() {
+        let mut hub = device(HUB, Some(ROOT), 100, false, Some(4));
+        hub.1.Power = None;
+        let devices: DeviceMap = [
+            root_hub(),
+            hub,
+            device("portless", Some(ROOT), 0, true, Some(0)),
+            device("a", Some(HUB), 200, false, None),
+        ]
+        .into_iter()
+        .collect();
+        let hubs = hub_power(&devices);
+        assert_eq!(hubs.keys().collect::<Vec<_>>(), [ROOT]);
+        assert_eq!(hubs[ROOT].used_ma, 0);
+    }
+
+    #[test]
+    fn parent_loops_stop_at_the_tier_limit() {
+        let devices: DeviceMap = [
+            device("x", Some("y"), 100, false, Some(2)),
+            device("y", Some("x"), 100, false, Some(2)),
+        ]
+        .into_iter()
+        .collect();
+        let hubs = hub_power(&devices);
+        // Each hub's draw is counted once per tier until the cut-off.
+        assert_eq!(hubs["x"].used_ma, 100 * (MAX_TIERS as u32 + 1));
+        assert!(hubs["x"].exceeded);
+    }
+}
@@ -0,0 +1,325 @@
+use crate::logging::log_to_file;
+use crate::source::DeviceMap;
+use crate::types::{RuleOverride, UsbDevice};
+use parking_lot::RwLock;
+use regex::Regex;
+use serde::Deserialize;
+use std::sync::{Arc, OnceLock};
+
+/// Overrides for devices that classify wrong, next to the app.
+const RULES_FILE: &str = "device-rules.toml";
+
+// ── Rules ──────────────────────────────────────────────────────
+
+/// One `[[rule]]` entry. Every condition it sets has to match.
+struct Rule {
+    vid_pid: Option<String>,
+    name: Option<Regex>,
+    device_id: Option<Regex>,
+    set: RuleOverride,
+}
+
+impl Rule {
+    fn matches(&self, id: &str, dev: &UsbDevice) -> bool {
+        self.vid_pid
+            .as_ref()
+            .is_none_or(|vp| dev.vid_pid().is_some_and(|d| d.eq_ignore_ascii_case(vp)))
+            && self
+                .name
+                .as_ref()
+                .is_none_or(|re| re.is_match(dev.display_name()))
+            && self.device_id.as_ref().is_none_or(|re| re.is_match(id))
+    }
+}
+
+#[derive(Deserialize)]
+struct RulesFile {
+    #[serde(default)]
+    rule: Vec<RuleEntry>,
+}
+
+#[derive(Deserialize)]
+struct RuleEntry {
+    vid_pid: Option<String>,
+    name_regex: Option<String>,
+    /// `*` and `?` wildcards, case-insensitive.
+    device_id: Option<String>,
+    #[serde(flatten)]
+    set: RuleOverride,
+}
+
+/// The parsed rules file.
+pub struct Rules {
+    rules: Vec<Rule>,
+    /// Where the rules came from, for the UI (a path, or "none").
+    source: String,
+}
+
+impl Rules {
+    /// Parses `device-rules.toml`:
+    ///
+    /// ```toml
+    /// [[rule]]
+    /// vid_pid = "1366:0105"
+    /// name = "J-Link JTAG probe"
+    /// category = "other"
+    /// icon = "🔧"
+    /// ```
+    ///
+    /// A rule matches on one or more of `vid_pid`, `name_regex` and
+    /// `device_id`, and sets any of `name`, `category`, `icon` and
+    /// `storage`.
+    pub fn parse(text: &str, source: &str) -> Result<Self, String> {
+        let file: RulesFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        let mut rules = Vec::new();
+        for (i, entry) in file.rule.into_iter().enumerate() {
+            let n = i + 1;
+            if entry.vid_pid.is_none() && entry.name_regex.is_none() && entry.device_id.is_none() {
+                return Err(format!("Rule {} has nothing to match on", n));
+            }
+            let name = entry
+                .name_regex
+                .map(|re| Regex::new(&re))
+                .transpose()
+                .map_err(|e| format!("Rule {}: {}", n, e))?;
+            let device_id = entry
+                .device_id
+                .map(|pattern| wildcard(&pattern))
+                .transpose()
+                .map_err(|e| format!("Rule {}: {}", n, e))?;
+            rules.push(Rule {
+                vid_pid: entry.vid_pid,
+                name,
+                device_id,
+                set: entry.set,
+            });
+        }
+        Ok(Self {
+            rules,
+            source: source.to_string(),
+        })
+    }
+
+    /// No rules, for when there's no file.
+    pub fn empty() -> Self {
+        Self {
+            rules: Vec::new(),
+            source: "none".to_string(),
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Sets `dev.Rule` from every rule that matches, later rules winning
+    /// field by field. Matching sees the device as the source reported
+    /// it, so re-applying after a reload starts from scratch.
+    pub fn apply(&self, id: &str, dev: &mut UsbDevice) {
+        dev.Rule = None;
+        let mut merged: Option<RuleOverride> = None;
+        for rule in self.rules.iter().filter(|r| r.matches(id, dev)) {
+            let m = merged.get_or_insert_with(RuleOverride::default);
+            let set = rule.set.clone();
+            m.name = set.name.or(m.name.take());
+            m.category = set.category.or(m.category);
+            m.icon = set.icon.or(m.icon.take());
+            m.storage = set.storage.or(m.storage);
+        }
+        dev.Rule = merged;
+    }
+
+    /// `apply` for a whole enumeration.
+    pub fn apply_all(&self, mut devices: DeviceMap) -> DeviceMap {
+        for (id, dev) in devices.iter_mut() {
+            self.apply(id, dev);
+        }
+        devices
+    }
+}
+
+/// Turns a `USB\VID_1366&PID_*` style pattern into an anchored regex.
+fn wildcard(pattern: &str) -> Result<Regex, regex::Error> {
+    let body: String = pattern
+        .chars()
+        .map(|c| match c {
+            '*' => ".*".to_string(),
+            '?' => ".".to_string(),
+            c => regex::escape(&c.to_string()),
+        })
+        .collect();
+    Regex::new(&format!("(?i)^{}$", body))
+}
+
+// ── Process-wide copy ──────────────────────────────────────────
+
+fn slot() -> &'static RwLock<Arc<Rules>> {
+    static RULES: OnceLock<RwLock<Arc<Rules>>> = OnceLock::new();
+    RULES.get_or_init(|| {
+        let rules = match std::fs::read_to_string(RULES_FILE) {
+            Ok(text) => Rules::parse(&text, RULES_FILE).unwrap_or_else(|e| {
+                log_to_file(&format!("RULES: ignoring {}: {}", RULES_FILE, e));
+                Rules::empty()
+            }),
+            Err(_) => Rules::empty(),
+        };
+        RwLock::new(Arc::new(rules))
+    })
+}
+
+/// The rules currently in use. Callers compare the returned `Arc`s to
+/// notice a reload.
+pub fn current() -> Arc<Rules> {
+    slot().read().clone()
+}
+
+/// Re-reads the rules file. A broken file is reported and the rules in
+/// use are kept.
+pub fn reload() -> Result<Arc<Rules>, String> {
+    let rules = match std::fs::read_to_string(RULES_FILE) {
+        Ok(text) => {
+            Rules::parse(&text, RULES_FILE).map_err(|e| format!("{}: {}", RULES_FILE, e))?
+        }
+        // No file is the same as no rules.
+        Err(_) => Rules::empty(),
+    };
+    let rules = Arc::new(rules);
+    *slot().write() = rules.clone();
+    log_to_file(&format!(
+        "RULES: loaded {} from {}",
+        rules.count(),
+        rules.source()
+    ));
+    Ok(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DeviceCategory;
+
+    const PROBE: &str = "USB\\VID_1366&PID_0105\\000260112233";
+
+    fn device(id: &str, name: &str) -> UsbDevice {
+        UsbDevice {
+            Name: Some(name.to_string()),
+            DeviceID: Some(id.to_string()),
+            Description: None,
+            Manufacturer: None,
+            PNPClass: None,
+            Service: None,
+            CompatibleID: None,
+            Interfaces: Vec::new(),
+            Parent: None,
+            LocationPath: None,
+            Speed: None,
+            UsbVersion: None,
+            Power: None,
+            Rule: None,
+        }
+    }
+
+    fn applied(rules: &str, id: &str, name: &str) -> Option<RuleOverride> {
+        let mut dev = device(id, name);
+        Rules::parse(rules, "test").unwrap().apply(id, &mut dev);
+        dev.Rule
+    }
+
+    #[test]
+    fn wildcards_match_whole_ids_without_regex_syntax() {
+        let re = wildcard("USB\\VID_1366&PID_*").unwrap();
+        assert!(re.is_match(PROBE));
+        assert!(re.is_match("usb\\vid_1366&pid_0105\\x"));
+        assert!(!re.is_match("HID\\USB\\VID_1366&PID_0105"));
+
+        let re = wildcard("USB\\VID_1366&PID_010?\\1.3").unwrap();
+        assert!(re.is_match("USB\\VID_1366&PID_0105\\1.3"));
+        // `.` is literal, and `?` stands for exactly one character.
+        assert!(!re.is_match("USB\\VID_1366&PID_0105\\1x3"));
+        assert!(!re.is_match("USB\\VID_1366&PID_01055\\1.3"));
+        assert!(wildcard("(a+)[").unwrap().is_match("(a+)["));
+    }
+
+    #[test]
+    fn every_condition_has_to_match() {
+        let rules = "[[rule]]\nvid_pid = \"1366:0105\"\nicon = \"🔧\"\n";
+        assert!(applied(rules, PROBE, "J-Link").is_some());
+        assert!(applied(rules, "USB\\VID_1366&PID_1015\\1", "J-Link").is_none());
+
+        let rules = "[[rule]]\nvid_pid = \"1366:0105\"\nname_regex = \"^J-Link\"\nicon = \"🔧\"\n";
+        assert!(applied(rules, PROBE, "J-Link OB").is_some());
+        assert!(applied(rules, PROBE, "BULK interface").is_none());
+
+        let rules = "[[rule]]\ndevice_id = \"usb\\\\vid_1366&*\"\nicon = \"🔧\"\n";
+        assert!(applied(rules, PROBE, "J-Link").is_some());
+        assert!(applied(rules, "USB\\VID_0781&PID_5581\\1", "Ultra").is_none());
+    }
+
+    #[test]
+    fn later_rules_win_field_by_field() {
+        let rules = r#"
+[[rule]]
+vid_pid = "1366:0105"
+name = "J-Link"
+category = "other"
+storage = false
+
+[[rule]]
+device_id = 'USB\VID_1366&*'
+name = "J-Link JTAG probe"
+icon = "🔧"
+
+[[rule]]
+vid_pid = "0781:5581"
+name = "Not this one"
+"#;
+        assert_eq!(
+            applied(rules, PROBE, "BULK interface"),
+            Some(RuleOverride {
+                name: Some("J-Link JTAG probe".to_string()),
+                category: Some(DeviceCategory::Other),
+                icon: Some("🔧".to_string()),
+                storage: Some(false),
+            })
+        );
+    }
+
+    #[test]
+    fn reapplying_starts_from_the_reported_device() {
+        let rules = Rules::parse(
+            "[[rule]]\nname_regex = \"^BULK\"\nname = \"J-Link\"\n",
+            "test",
+        )
+        .unwrap();
+        let mut dev = device(PROBE, "BULK interface");
+        rules.apply(PROBE, &mut dev);
+        assert_eq!(dev.display_name(), "J-Link");
+        // The rule's own name doesn't stop it matching the second time.
+        rules.apply(PROBE, &mut dev);
+        assert_eq!(dev.display_name(), "J-Link");
+        Rules::empty().apply(PROBE, &mut dev);
+        assert_eq!(dev.Rule, None);
+    }
+
+    #[test]
+    fn bad_rules_files_are_rejected() {
+        let err = |text: &str| Rules::parse(text, "test").err().unwrap();
+        assert_eq!(
+            err("[[rule]]\nicon = \"🔧\"\n"),
+            "Rule 1 has nothing to match on"
+        );
+        assert!(
+            err("[[rule]]\nvid_pid = \"1366:0105\"\n[[rule]]\nname_regex = \"(\"\n")
+                .starts_with("Rule 2: ")
+        );
+        assert!(!err("[[rule]\n").is_empty());
+        assert!(!err("[[rule]]\nvid_pid = \"1366:0105\"\ncategory = \"toaster\"\n").is_empty());
+
+        let rules = Rules::parse("", "test").unwrap();
+        assert_eq!((rules.count(), rules.source()), (0, "test"));
+    }
+}
@@ -0,0 +1,342 @@
+#[cfg(target_os = "linux")]
+mod netlink;
+mod record;
+mod simulate;
+#[cfg(not(windows))]
+mod sysfs;
+#[cfg(target_os = "linux")]
+mod uevent;
+#[cfg(windows)]
+mod wmi;
+
+#[cfg(windows)]
+pub use self::wmi::WmiSource;
+#[cfg(target_os = "linux")]
+pub use netlink::UeventSource;
+pub use record::{RecordingSource, ReplaySource};
+pub use simulate::SimulatedSource;
+#[cfg(not(windows))]
+pub use sysfs::SysfsSource;
+
+use crate::types::{Bus, StorageInfo, UsbDevice};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+/// Attached devices keyed by their PnP-style device instance ID. Ordered,
+/// so diffs and recordings come out the same way every run.
+pub type DeviceMap = BTreeMap<String, UsbDevice>;
+
+/// Opens a backend on whichever thread calls it.
+pub type Opener = Box<dyn FnOnce() -> Result<Box<dyn DeviceSource>, String> + Send>;
+
+/// A single arrival or removal reported by an event-driven source.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Hotplug {
+    Added { id: String, device: Box<UsbDevice> },
+    Removed { id: String },
+}
+
+// ── Device source ──────────────────────────────────────────────
+
+/// A backend that can enumerate the currently attached devices.
+///
+/// The monitor and CLI only ever talk to this trait, so the same diff,
+/// cache and event logic runs on top of WMI, sysfs or anything else.
+pub trait DeviceSource {
+    /// Short backend name for log lines.
+    fn name(&self) -> &'static str;
+
+    /// Full enumeration of the attached devices.
+    fn enumerate(&mut self) -> Result<DeviceMap, String>;
+
+    /// How long to wait between full enumerations. Event-driven sources
+    /// return a long interval since enumeration is only a resync there.
+    fn poll_interval(&self) -> Duration {
+        Duration::from_millis(500)
+    }
+
+    /// Blocks for up to `timeout` and returns any hotplug notifications
+    /// that arrived, in order. Polling sources just sleep and return
+    /// nothing, leaving change detection to the enumeration diff.
+    fn wait_for_changes(&mut self, timeout: Duration) -> Vec<Hotplug> {
+        thread::sleep(timeout);
+        Vec::new()
+    }
+
+    /// Storage details for a connected mass-storage device, if the
+    /// backend knows how to look them up.
+    fn query_storage_info(&mut self, _device_id: &str) -> Option<StorageInfo> {
+        None
+    }
+
+    /// Opens a second instance of this backend for the enrichment worker,
+    /// so storage lookups don't hold up hotplug detection. Sources without
+    /// one are queried inline, which keeps simulations and recordings
+    /// in step with the device timeline.
+    fn storage_source(&self) -> Option<Opener> {
+        None
+    }
+
+    /// Current wall-clock time. Replays report the recorded time instead,
+    /// so everything timestamped downstream is reproducible.
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+
+    /// True once a finite source (a replay) has nothing left to report.
+    fn finished(&self) -> bool {
+        false
+    }
+}
+
+// ── Device filter ──────────────────────────────────────────────
+
+/// Which devices a native backend reports: everything on the listed
+/// buses, plus anything under the extra enumerators.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceFilter {
+    pub buses: BTreeSet<Bus>,
+    /// Extra device ID enumerators (`SD`, `SCSI`, ...), upper-case.
+    pub enumerators: Vec<String>,
+}
+
+impl Default for DeviceFilter {
+    /// The buses devices actually get plugged into. PCI and HID are opt
+    /// in: most PCI functions are soldered down, and HID nodes mostly
+    /// repeat devices already seen on USB.
+    fn default() -> Self {
+        Self {
+            buses: [Bus::Usb, Bus::Thunderbolt, Bus::Bluetooth].into(),
+            enumerators: Vec::new(),
+        }
+    }
+}
+
+impl DeviceFilter {
+    /// `--buses usb,pci` (or `all`) and `--enumerators SD,SCSI`. Unknown
+    /// bus names are logged and skipped.
+    pub fn from_args(args: &[String]) -> Self {
+        let mut filter = Self::default();
+        if let Some(list) = flag_value(args, "--buses") {
+            filter.buses = if list.eq_ignore_ascii_case("all") {
+                Bus::ALL.into()
+            } else {
+                list.split(',')
+                    .filter(|name| !name.trim().is_empty())
+                    .filter_map(|name| {
+                        let bus = Bus::parse(name);
+                        if bus.is_none() {
+                            crate::logging::log_to_file(&format!(
+                                "SOURCE: ignoring unknown bus '{}'",
+                                name.trim()
+                            ));
+                        }
+                        bus
+                    })
+                    .collect()
+            };
+        }
+        if let Some(list) = flag_value(args, "--enumerators") {
+            filter.enumerators = list
+                .split(',')
+                .map(|e| e.trim().to_ascii_uppercase())
+                .filter(|e| !e.is_empty())
+                .collect();
+        }
+        filter
+    }
+
+    pub fn wants(&self, bus: Bus) -> bool {
+        self.buses.contains(&bus)
+    }
+
+    pub fn allows(&self, dev: &UsbDevice) -> bool {
+        let Some(id) = dev.instance_id() else {
+            return false;
+        };
+        self.wants(Bus::from_enumerator(&id.enumerator))
+            || self.enumerators.contains(&id.enumerator)
+    }
+}
+
+// ── Source selection ───────────────────────────────────────────
+
+/// Which backend to monitor, picked from the command line.
+#[derive(Clone, Debug)]
+pub enum SourceKind {
+    /// Real hardware via the platform's native backend.
+    Native,
+    /// `--simulate <scenario.toml>`: a scripted device timeline.
+    Simulate(PathBuf),
+    /// `--replay <file> [--speed <x>]`: a recording made with `--record`.
+    Replay { path: PathBuf, speed: f64 },
+}
+
+#[derive(Clone, Debug)]
+pub struct SourceSpec {
+    pub kind: SourceKind,
+    /// `--record <file>`: also write everything the source returns.
+    pub record: Option<PathBuf>,
+    /// `--buses` / `--enumerators`, for the native backend.
+    pub filter: DeviceFilter,
+}
+
+impl SourceSpec {
+    pub fn from_args(args: &[String]) -> Self {
+        let kind = if let Some(path) = flag_value(args, "--replay") {
+            SourceKind::Replay {
+                path: PathBuf::from(path),
+                speed: flag_value(args, "--speed").map_or(1.0, replay_speed),
+            }
+        } else if let Some(path) = flag_value(args, "--simulate") {
+            SourceKind::Simulate(PathBuf::from(path))
+        } else {
+            SourceKind::Native
+        };
+        Self {
+            kind,
+            record: flag_value(args, "--record").map(PathBuf::from),
+            filter: DeviceFilter::from_args(args),
+        }
+    }
+
+    pub fn open(&self) -> Result<Box<dyn DeviceSource>, String> {
+        let source: Box<dyn DeviceSource> = match &self.kind {
+            SourceKind::Native => open_default(&self.filter)?,
+            SourceKind::Simulate(path) => Box::new(SimulatedSource::from_file(path)?),
+            SourceKind::Replay { path, speed } => Box::new(ReplaySource::open(path, *speed)?),
+        };
+        match &self.record {
+            Some(path) => Ok(Box::new(RecordingSource::new(source, path)?)),
+            None => Ok(source),
+        }
+    }
+}
+
+/// `--speed` must be a positive number; anything else is logged and
+/// played at normal speed.
+fn replay_speed(value: &str) -> f64 {
+    positive_speed(value).unwrap_or_else(|| {
+        crate::logging::log_to_file(&format!(
+            "Ignoring --speed {}: must be a positive number",
+            value
+        ));
+        1.0
+    })
+}
+
+fn positive_speed(value: &str) -> Option<f64> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|speed| speed.is_finite() && *speed > 0.0)
+}
+
+/// Value following `flag` on the command line, e.g. `--simulate demo.toml`.
+pub fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let pos = args.iter().position(|a| a == flag)?;
+    args.get(pos + 1).map(String::as_str)
+}
+
+/// Opens the native backend for the current platform. On Linux this is
+/// the uevent listener, falling back to plain sysfs polling when the
+/// netlink socket is unavailable (e.g. inside some containers).
+pub fn open_default(filter: &DeviceFilter) -> Result<Box<dyn DeviceSource>, String> {
+    #[cfg(windows)]
+    {
+        Ok(Box::new(WmiSource::new(filter.clone())?))
+    }
+    #[cfg(target_os = "linux")]
+    {
+        let sysfs = || SysfsSource::new().with_filter(filter.clone());
+        match UeventSource::new(sysfs()) {
+            Ok(s) => Ok(Box::new(s)),
+            Err(e) => {
+                crate::logging::log_to_file(&format!("{} — falling back to sysfs polling", e));
+                Ok(Box::new(sysfs()))
+            }
+        }
+    }
+    #[cfg(not(any(windows, target_os = "linux")))]
+    {
+        Ok(Box::new(SysfsSource::new().with_filter(filter.clone())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn replay_speed_must_be_positive() {
+        assert_eq!(positive_speed("4"), Some(4.0));
+        assert_eq!(positive_speed("0.25"), Some(0.25));
+        for bad in ["0", "-2", "inf", "NaN", "fast", ""] {
+            assert_eq!(positive_speed(bad), None, "{}", bad);
+        }
+        let spec = SourceSpec::from_args(&args(&["app", "--replay", "s.jsonl", "--speed", "4"]));
+        assert!(matches!(spec.kind, SourceKind::Replay { speed, .. } if speed == 4.0));
+    }
+
+    #[test]
+    fn picks_the_source_from_the_command_line() {
+        let spec = SourceSpec::from_args(&args(&["app", "--simulate", "demo.toml"]));
+        assert!(matches!(spec.kind, SourceKind::Simulate(_)));
+        let spec = SourceSpec::from_args(&args(&["app", "--record", "out.jsonl"]));
+        assert!(matches!(spec.kind, SourceKind::Native));
+        assert_eq!(spec.record, Some(PathBuf::from("out.jsonl")));
+    }
+
+    #[test]
+    fn filters_default_to_pluggable_buses() {
+        let filter = DeviceFilter::default();
+        assert!(filter.wants(Bus::Usb) && filter.wants(Bus::Thunderbolt));
+        assert!(filter.wants(Bus::Bluetooth));
+        assert!(!filter.wants(Bus::Pci) && !filter.wants(Bus::Hid));
+        assert_eq!(DeviceFilter::from_args(&args(&["app"])), filter);
+    }
+
+    #[test]
+    fn filters_take_buses_and_enumerators() {
+        let filter = DeviceFilter::from_args(&args(&[
+            "app",
+            "--buses",
+            "usb,PCI,,floppy",
+            "--enumerators",
+            "sd, scsi",
+        ]));
+        assert_eq!(filter.buses, [Bus::Usb, Bus::Pci].into());
+        assert_eq!(filter.enumerators, ["SD", "SCSI"]);
+
+        let device = |id: &str| UsbDevice {
+            Name: None,
+            DeviceID: Some(id.to_string()),
+            Description: None,
+            Manufacturer: None,
+            PNPClass: None,
+            Service: None,
+            CompatibleID: None,
+            Interfaces: Vec::new(),
+            Parent: None,
+            LocationPath: None,
+            Speed: None,
+            UsbVersion: None,
+            Power: None,
+            Rule: None,
+        };
+        assert!(filter.allows(&device("PCI\\VEN_144D&DEV_A808\\0000:3c:00.0")));
+        assert!(filter.allows(&device("SD\\DISK&GENERIC_SD\\1234")));
+        assert!(!filter.allows(&device("BTHENUM\\VID_046D&PID_B023\\1")));
+
+        let all = DeviceFilter::from_args(&args(&["app", "--buses", "all"]));
+        assert_eq!(all.buses, Bus::ALL.into());
+    }
+}
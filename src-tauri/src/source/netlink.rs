@@ -0,0 +1,352 @@
+use super::sysfs::port_key;
+use super::uevent::{parse_uevents, Uevent, UeventAction};
+use super::{DeviceMap, DeviceSource, Hotplug, Opener, SysfsSource};
+use crate::types::{StorageInfo, UsbDevice};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::time::{Duration, Instant};
+
+/// Kernel uevent multicast group on `NETLINK_KOBJECT_UEVENT`.
+const KERNEL_UEVENT_GROUP: u32 = 1;
+
+/// How often to fully re-enumerate sysfs when uevents are flowing, in
+/// case one was dropped (socket overrun, monitor suspended, ...).
+const RESYNC_INTERVAL: Duration = Duration::from_secs(5);
+
+struct UeventSocket {
+    fd: OwnedFd,
+}
+
+impl UeventSocket {
+    fn open() -> io::Result<Self> {
+        let raw = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                libc::NETLINK_KOBJECT_UEVENT,
+            )
+        };
+        if raw < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(raw) };
+
+        let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = KERNEL_UEVENT_GROUP;
+        let rc = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if rc < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { fd })
+    }
+
+    /// Blocks until a datagram is readable or the timeout passes.
+    fn wait(&self, timeout: Duration) -> bool {
+        let mut pfd = libc::pollfd {
+            fd: self.fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+        unsafe { libc::poll(&mut pfd, 1, ms) > 0 }
+    }
+
+    /// Reads one datagram without blocking; `None` once drained.
+    fn recv(&self, buf: &mut [u8]) -> Option<usize> {
+        let n = unsafe {
+            libc::recv(
+                self.fd.as_raw_fd(),
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+                libc::MSG_DONTWAIT,
+            )
+        };
+        (n > 0).then_some(n as usize)
+    }
+}
+
+/// Event-driven Linux backend: listens for kernel uevents and only falls
+/// back to a full sysfs enumeration every few seconds to resync.
+pub struct UeventSource {
+    sysfs: SysfsSource,
+    socket: UeventSocket,
+}
+
+impl UeventSource {
+    pub fn new(sysfs: SysfsSource) -> Result<Self, String> {
+        let socket = UeventSocket::open().map_err(|e| format!("uevent socket failed: {}", e))?;
+        Ok(Self { sysfs, socket })
+    }
+}
+
+/// Hotplug changes for a batch of uevents, or None when none of them came
+/// from a subsystem the source scans (`power_supply`, `net`, ...).
+fn changes_for(sysfs: &mut SysfsSource, events: &[Uevent]) -> Option<Vec<Hotplug>> {
+    let relevant: Vec<&Uevent> = events
+        .iter()
+        .filter(|e| sysfs.scans(&e.subsystem))
+        .collect();
+    if relevant.is_empty() {
+        return None;
+    }
+    Some(
+        relevant
+            .into_iter()
+            .filter_map(|event| hotplug_for(sysfs, event))
+            .collect(),
+    )
+}
+
+fn hotplug_for(sysfs: &mut SysfsSource, event: &Uevent) -> Option<Hotplug> {
+    let subsystem = event.subsystem.as_str();
+    if !sysfs.scans(subsystem) || (subsystem == "usb" && !event.is_usb_device()) {
+        return None;
+    }
+    let port = port_key(subsystem, event.kernel_name());
+    match event.action {
+        UeventAction::Add | UeventAction::Bind => {
+            if event.action == UeventAction::Bind && sysfs.port_id(&port).is_some() {
+                return None;
+            }
+            let path = sysfs.root().join(event.devpath.trim_start_matches('/'));
+            // A USB device that's already gone again by the time we
+            // look still gets a connect, built from the uevent itself.
+            let mut device = sysfs
+                .read_subsystem_device(subsystem, &path)
+                .or_else(|| device_from_uevent(event))?;
+            let id = sysfs.claim_id(&port, event.kernel_name(), &mut device)?;
+            Some(Hotplug::Added {
+                id,
+                device: Box::new(device),
+            })
+        }
+        UeventAction::Remove => {
+            let id = sysfs.forget_port(&port)?;
+            Some(Hotplug::Removed { id })
+        }
+        // A driver detaching isn't a disconnect; the remove follows.
+        _ => None,
+    }
+}
+
+fn device_from_uevent(event: &Uevent) -> Option<UsbDevice> {
+    if event.subsystem != "usb" {
+        return None;
+    }
+    let (vid, pid) = event.vid_pid()?;
+    Some(UsbDevice {
+        Name: None,
+        DeviceID: Some(format!(
+            "USB\\VID_{:04X}&PID_{:04X}\\{}",
+            vid,
+            pid,
+            event.kernel_name()
+        )),
+        Description: None,
+        Manufacturer: None,
+        PNPClass: Some("USB".to_string()),
+        Service: None,
+        CompatibleID: None,
+        Interfaces: Vec::new(),
+        Parent: None,
+        LocationPath: None,
+        Speed: None,
+        UsbVersion: None,
+        Power: None,
+        Rule: None,
+    })
+}
+
+impl DeviceSource for UeventSource {
+    fn name(&self) -> &'static str {
+        "uevent"
+    }
+
+    fn enumerate(&mut self) -> Result<DeviceMap, String> {
+        self.sysfs.enumerate()
+    }
+
+    fn query_storage_info(&mut self, device_id: &str) -> Option<StorageInfo> {
+        self.sysfs.query_storage_info(device_id)
+    }
+
+    fn storage_source(&self) -> Option<Opener> {
+        self.sysfs.storage_source()
+    }
+
+    fn poll_interval(&self) -> Duration {
+        RESYNC_INTERVAL
+    }
+
+    /// Uevents from subsystems that aren't scanned wake the socket too.
+    /// Those are waited through rather than returned as an empty batch,
+    /// which the monitor would take as idle and re-enumerate on.
+    fn wait_for_changes(&mut self, timeout: Duration) -> Vec<Hotplug> {
+        let deadline = Instant::now() + timeout;
+        let mut buf = vec![0u8; 8192];
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() || !self.socket.wait(left) {
+                return Vec::new();
+            }
+            let mut changes = None;
+            while let Some(n) = self.socket.recv(&mut buf) {
+                let events = parse_uevents(&buf[..n]);
+                if let Some(batch) = changes_for(&mut self.sysfs, &events) {
+                    changes.get_or_insert_with(Vec::new).extend(batch);
+                }
+            }
+            if let Some(changes) = changes {
+                return changes;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const NETLINK_DUMP: &[u8] = include_bytes!("../../tests/fixtures/uevent-netlink.bin");
+    const UDEVADM_DUMP: &str = include_str!("../../tests/fixtures/udevadm-monitor.txt");
+
+    fn summary(changes: &[Hotplug]) -> Vec<String> {
+        changes
+            .iter()
+            .map(|c| match c {
+                Hotplug::Added { id, .. } => format!("+{}", id),
+                Hotplug::Removed { id } => format!("-{}", id),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_added_devices_from_sysfs() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("devices/pci0000:00/0000:00:14.0/usb1/1-2");
+        fs::create_dir_all(&dir).unwrap();
+        for (attr, value) in [
+            ("idVendor", "0781"),
+            ("idProduct", "5581"),
+            ("serial", "4C530001"),
+            ("product", "Ultra"),
+        ] {
+            fs::write(dir.join(attr), value).unwrap();
+        }
+        let mut sysfs = SysfsSource::with_root(root.path());
+
+        let changes = changes_for(&mut sysfs, &parse_uevents(NETLINK_DUMP)).unwrap();
+        assert_eq!(
+            summary(&changes),
+            [
+                "+USB\\VID_0781&PID_5581\\4C530001",
+                "-USB\\VID_0781&PID_5581\\4C530001",
+            ]
+        );
+        let Hotplug::Added { device, .. } = &changes[0] else {
+            unreachable!()
+        };
+        assert_eq!(device.Name.as_deref(), Some("Ultra"));
+    }
+
+    #[test]
+    fn identical_serials_keep_their_ids_across_a_resync() {
+        let root = tempfile::tempdir().unwrap();
+        let usb1 = root.path().join("devices/pci0000:00/0000:00:14.0/usb1");
+        let bus = root.path().join("bus/usb/devices");
+        fs::create_dir_all(&bus).unwrap();
+        let mut uevents = Vec::new();
+        // Plugged in the other way round from how sysfs lists them.
+        for port in ["1-3", "1-2"] {
+            let dir = usb1.join(port);
+            fs::create_dir_all(&dir).unwrap();
+            for (attr, value) in [
+                ("idVendor", "0781"),
+                ("idProduct", "5581"),
+                ("serial", "4C530001"),
+            ] {
+                fs::write(dir.join(attr), value).unwrap();
+            }
+            std::os::unix::fs::symlink(&dir, bus.join(port)).unwrap();
+            let devpath = format!("/devices/pci0000:00/0000:00:14.0/usb1/{}", port);
+            uevents.extend_from_slice(
+                format!(
+                    "add@{0}\0ACTION=add\0DEVPATH={0}\0SUBSYSTEM=usb\0DEVTYPE=usb_device\0",
+                    devpath
+                )
+                .as_bytes(),
+            );
+        }
+        let mut sysfs = SysfsSource::with_root(root.path());
+
+        let changes = changes_for(&mut sysfs, &parse_uevents(&uevents)).unwrap();
+        assert_eq!(
+            summary(&changes),
+            [
+                "+USB\\VID_0781&PID_5581\\4C530001",
+                "+USB\\VID_0781&PID_5581\\1-2",
+            ]
+        );
+        assert_eq!(
+            sysfs.port_id("1-3").map(String::as_str),
+            Some("USB\\VID_0781&PID_5581\\4C530001")
+        );
+
+        let devices = sysfs.enumerate().unwrap();
+        let mut ids: Vec<&str> = devices.keys().map(String::as_str).collect();
+        ids.sort();
+        assert_eq!(
+            ids,
+            [
+                "USB\\VID_0781&PID_5581\\1-2",
+                "USB\\VID_0781&PID_5581\\4C530001",
+            ]
+        );
+        assert_eq!(
+            sysfs.port_id("1-3").map(String::as_str),
+            Some("USB\\VID_0781&PID_5581\\4C530001")
+        );
+    }
+
+    #[test]
+    fn devices_gone_before_the_lookup_come_from_the_uevent() {
+        let root = tempfile::tempdir().unwrap();
+        let mut sysfs = SysfsSource::with_root(root.path());
+        let changes = changes_for(&mut sysfs, &parse_uevents(UDEVADM_DUMP.as_bytes())).unwrap();
+        assert_eq!(
+            summary(&changes),
+            [
+                "+USB\\VID_046D&PID_C52B\\1-2",
+                "-USB\\VID_046D&PID_C52B\\1-2",
+            ]
+        );
+    }
+
+    #[test]
+    fn other_subsystems_are_not_a_wakeup() {
+        let root = tempfile::tempdir().unwrap();
+        let mut sysfs = SysfsSource::with_root(root.path());
+        let power_supply: Vec<Uevent> = parse_uevents(NETLINK_DUMP)
+            .into_iter()
+            .filter(|e| e.subsystem == "power_supply")
+            .collect();
+        assert_eq!(power_supply.len(), 1);
+        assert!(changes_for(&mut sysfs, &power_supply).is_none());
+
+        // An interface or a driver binding counts, though it's no change.
+        let bind: Vec<Uevent> = parse_uevents(NETLINK_DUMP)
+            .into_iter()
+            .filter(|e| e.action == UeventAction::Bind)
+            .collect();
+        sysfs.remember_port("1-2", "USB\\VID_0781&PID_5581\\4C530001");
+        assert_eq!(changes_for(&mut sysfs, &bind).map(|c| c.len()), Some(0));
+    }
+}
@@ -0,0 +1,408 @@
+use super::{DeviceMap, DeviceSource, Hotplug};
+use crate::types::StorageInfo;
+use chrono::{DateTime, Local, TimeDelta};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const FORMAT_VERSION: u32 = 1;
+
+/// One line of a recording. Enumerations are stored as deltas against the
+/// previous one, and unchanged polls are not written at all.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "k", rename_all = "snake_case")]
+enum Frame {
+    Header {
+        version: u32,
+        started: DateTime<Local>,
+        source: String,
+    },
+    Enumerate {
+        /// Milliseconds since the recording started (monotonic).
+        t: u64,
+        #[serde(default, skip_serializing_if = "DeviceMap::is_empty")]
+        set: DeviceMap,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        rm: Vec<String>,
+    },
+    Failed {
+        t: u64,
+        error: String,
+    },
+    Hotplug {
+        t: u64,
+        changes: Vec<Hotplug>,
+    },
+    Storage {
+        t: u64,
+        id: String,
+        info: Option<Box<StorageInfo>>,
+    },
+}
+
+/// Applies hotplug changes to the last known enumeration. Both sides do
+/// this, so deltas stay valid however the replay interleaves its polls.
+fn apply(devices: &mut DeviceMap, changes: &[Hotplug]) {
+    for change in changes {
+        match change {
+            Hotplug::Added { id, device } => {
+                devices.insert(id.clone(), (**device).clone());
+            }
+            Hotplug::Removed { id } => {
+                devices.remove(id);
+            }
+        }
+    }
+}
+
+impl Frame {
+    fn time(&self) -> u64 {
+        match self {
+            Self::Header { .. } => 0,
+            Self::Enumerate { t, .. }
+            | Self::Failed { t, .. }
+            | Self::Hotplug { t, .. }
+            | Self::Storage { t, .. } => *t,
+        }
+    }
+}
+
+// ── Recording ──────────────────────────────────────────────────
+
+/// Wraps another source and writes everything it returns to a JSON-lines
+/// file for `--replay`.
+pub struct RecordingSource {
+    inner: Box<dyn DeviceSource>,
+    out: BufWriter<File>,
+    start: Instant,
+    last: DeviceMap,
+}
+
+impl RecordingSource {
+    pub fn new(inner: Box<dyn DeviceSource>, path: &Path) -> Result<Self, String> {
+        let file = File::create(path)
+            .map_err(|e| format!("Can't create recording {}: {}", path.display(), e))?;
+        let mut rec = Self {
+            out: BufWriter::new(file),
+            start: Instant::now(),
+            last: DeviceMap::new(),
+            inner,
+        };
+        rec.write(&Frame::Header {
+            version: FORMAT_VERSION,
+            started: rec.inner.now(),
+            source: rec.inner.name().to_string(),
+        });
+        Ok(rec)
+    }
+
+    fn elapsed_ms(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+
+    /// Best effort: a full disk shouldn't take the monitor down with it.
+    fn write(&mut self, frame: &Frame) {
+        if let Ok(line) = serde_json::to_string(frame) {
+            let _ = writeln!(self.out, "{}", line);
+            let _ = self.out.flush();
+        }
+    }
+}
+
+impl DeviceSource for RecordingSource {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn enumerate(&mut self) -> Result<DeviceMap, String> {
+        let t = self.elapsed_ms();
+        match self.inner.enumerate() {
+            Ok(devices) => {
+                let set: DeviceMap = devices
+                    .iter()
+                    .filter(|(id, dev)| self.last.get(*id) != Some(*dev))
+                    .map(|(id, dev)| (id.clone(), dev.clone()))
+                    .collect();
+                let rm: Vec<_> = self
+                    .last
+                    .keys()
+                    .filter(|id| !devices.contains_key(*id))
+                    .cloned()
+                    .collect();
+                if !set.is_empty() || !rm.is_empty() {
+                    self.write(&Frame::Enumerate { t, set, rm });
+                }
+                self.last = devices.clone();
+                Ok(devices)
+            }
+            Err(error) => {
+                self.write(&Frame::Failed {
+                    t,
+                    error: error.clone(),
+                });
+                Err(error)
+            }
+        }
+    }
+
+    fn poll_interval(&self) -> Duration {
+        self.inner.poll_interval()
+    }
+
+    fn wait_for_changes(&mut self, timeout: Duration) -> Vec<Hotplug> {
+        let changes = self.inner.wait_for_changes(timeout);
+        if !changes.is_empty() {
+            apply(&mut self.last, &changes);
+            let t = self.elapsed_ms();
+            self.write(&Frame::Hotplug {
+                t,
+                changes: changes.clone(),
+            });
+        }
+        changes
+    }
+
+    fn query_storage_info(&mut self, device_id: &str) -> Option<StorageInfo> {
+        let t = self.elapsed_ms();
+        let info = self.inner.query_storage_info(device_id);
+        self.write(&Frame::Storage {
+            t,
+            id: device_id.to_string(),
+            info: info.clone().map(Box::new),
+        });
+        info
+    }
+
+    fn now(&self) -> DateTime<Local> {
+        self.inner.now()
+    }
+}
+
+// ── Replay ─────────────────────────────────────────────────────
+
+/// Feeds a recording back through the monitor on a virtual clock.
+///
+/// `speed` scales real time (2.0 plays twice as fast); 0 skips the waits
+/// entirely, for tests, since the command line only takes positive speeds.
+///
+/// Either way `now()` reports the recorded time, so the events produced
+/// are identical from run to run.
+pub struct ReplaySource {
+    frames: VecDeque<Frame>,
+    /// Storage lookups in the order they were made, served by device ID.
+    storage: Vec<(String, Option<StorageInfo>)>,
+    started: DateTime<Local>,
+    speed: f64,
+    /// Virtual milliseconds since the recording started.
+    clock: u64,
+    devices: DeviceMap,
+}
+
+impl ReplaySource {
+    pub fn open(path: &Path, speed: f64) -> Result<Self, String> {
+        let file = File::open(path)
+            .map_err(|e| format!("Can't open recording {}: {}", path.display(), e))?;
+        let mut frames = VecDeque::new();
+        let mut storage = Vec::new();
+        for (n, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let frame: Frame = serde_json::from_str(&line)
+                .map_err(|e| format!("{} line {}: {}", path.display(), n + 1, e))?;
+            match frame {
+                Frame::Storage { id, info, .. } => storage.push((id, info.map(|i| *i))),
+                frame => frames.push_back(frame),
+            }
+        }
+        let started = match frames.pop_front() {
+            Some(Frame::Header {
+                version, started, ..
+            }) if version == FORMAT_VERSION => started,
+            Some(Frame::Header { version, .. }) => {
+                return Err(format!("Unsupported recording version {}", version))
+            }
+            _ => {
+                return Err(format!(
+                    "{} is not a device-history recording",
+                    path.display()
+                ))
+            }
+        };
+        // Start the virtual clock at the first frame so the initial
+        // enumeration sees the recorded startup state.
+        let clock = frames.front().map_or(0, Frame::time);
+        Ok(Self {
+            frames,
+            storage,
+            started,
+            speed,
+            clock,
+            devices: DeviceMap::new(),
+        })
+    }
+
+    fn next_is(&self, due: impl Fn(&Frame) -> bool) -> bool {
+        self.frames
+            .front()
+            .is_some_and(|f| f.time() <= self.clock && due(f))
+    }
+}
+
+impl DeviceSource for ReplaySource {
+    fn name(&self) -> &'static str {
+        "replay"
+    }
+
+    fn enumerate(&mut self) -> Result<DeviceMap, String> {
+        let mut result = Ok(());
+        while self.next_is(|f| matches!(f, Frame::Enumerate { .. } | Frame::Failed { .. })) {
+            match self.frames.pop_front() {
+                Some(Frame::Enumerate { set, rm, .. }) => {
+                    for id in rm {
+                        self.devices.remove(&id);
+                    }
+                    self.devices.extend(set);
+                    result = Ok(());
+                }
+                Some(Frame::Failed { error, .. }) => result = Err(error),
+                _ => {}
+            }
+        }
+        result.map(|_| self.devices.clone())
+    }
+
+    fn wait_for_changes(&mut self, timeout: Duration) -> Vec<Hotplug> {
+        let target = self.clock + timeout.as_millis() as u64;
+        let next = self
+            .frames
+            .front()
+            .map_or(target, |f| f.time().max(self.clock));
+        let until = next.min(target);
+        if self.speed > 0.0 {
+            let real = (until - self.clock) as f64 / self.speed;
+            thread::sleep(Duration::try_from_secs_f64(real / 1000.0).unwrap_or(Duration::MAX));
+        }
+        self.clock = until;
+
+        let mut changes = Vec::new();
+        while self.next_is(|f| matches!(f, Frame::Hotplug { .. })) {
+            if let Some(Frame::Hotplug { changes: c, .. }) = self.frames.pop_front() {
+                apply(&mut self.devices, &c);
+                changes.extend(c);
+            }
+        }
+        changes
+    }
+
+    fn query_storage_info(&mut self, device_id: &str) -> Option<StorageInfo> {
+        let pos = self.storage.iter().position(|(id, _)| id == device_id)?;
+        self.storage.remove(pos).1
+    }
+
+    fn now(&self) -> DateTime<Local> {
+        self.started + TimeDelta::milliseconds(self.clock as i64)
+    }
+
+    fn finished(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+    use crate::monitor;
+    use crate::rules::Rules;
+    use crate::types::{DeviceEvent, EventKind};
+    use crate::usb_ids::UsbIds;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    /// Replays a recording through the monitor's own tick, with storage
+    /// looked up inline, until it's over and settled.
+    fn replay(path: &Path) -> Vec<DeviceEvent> {
+        let mut source = ReplaySource::open(path, 0.0).unwrap();
+        let mut engine = Engine::new(
+            HashMap::new(),
+            source.enumerate().unwrap(),
+            Arc::new(UsbIds::default()),
+            Arc::new(Rules::empty()),
+            source.now(),
+        );
+        let mut events = monitor::run_enrichments(&mut source, None, &mut engine).events;
+        let mut last_resync = source.now();
+        loop {
+            let out = monitor::tick(&mut source, None, &mut engine, &mut last_resync);
+            events.extend(out.events);
+            if source.finished() && engine.settled() {
+                return events;
+            }
+            assert!(source.clock < 600_000, "replay never settled");
+        }
+    }
+
+    #[test]
+    fn replays_a_recorded_session() {
+        let events = replay(&fixture("session.jsonl"));
+        let summary: Vec<_> = events
+            .iter()
+            .map(|e| (e.seq, e.kind.name(), e.name.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (1, "connect", "SanDisk Ultra"),
+                (2, "storage_enriched", "SanDisk Ultra"),
+                (3, "disconnect", "SanDisk Ultra"),
+                (4, "reconnect", "SanDisk Ultra"),
+                (5, "disconnect", "Logitech USB Input Device"),
+                (6, "storage_enriched", "SanDisk Ultra"),
+            ]
+        );
+        assert!(matches!(
+            events[3].kind,
+            EventKind::Reconnect { away_ms } if (90..=110).contains(&away_ms)
+        ));
+        let EventKind::StorageEnriched { drives, .. } = &events[1].kind else {
+            unreachable!()
+        };
+        assert_eq!(drives, &["E:"]);
+    }
+
+    #[test]
+    fn replays_are_deterministic() {
+        let path = fixture("session.jsonl");
+        let first = serde_json::to_string(&replay(&path)).unwrap();
+        assert_eq!(first, serde_json::to_string(&replay(&path)).unwrap());
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        std::fs::write(&path, "{\"k\":\"header\",\"version\":99,\"started\":\"2024-03-09T14:05:22Z\",\"source\":\"x\"}\n").unwrap();
+        assert_eq!(
+            ReplaySource::open(&path, 1.0).err().unwrap(),
+            "Unsupported recording version 99"
+        );
+        std::fs::write(&path, "{\"k\":\"hotplug\",\"t\":0,\"changes\":[]}\n").unwrap();
+        assert!(ReplaySource::open(&path, 1.0)
+            .err()
+            .unwrap()
+            .ends_with("is not a device-history recording"));
+    }
+}
@@ -0,0 +1,431 @@
+use super::{DeviceMap, DeviceSource, Hotplug};
+use crate::types::{Bus, DevicePower, LinkSpeed, StorageInfo, UsbDevice};
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// ── Scenario file ──────────────────────────────────────────────
+
+#[derive(Deserialize)]
+struct Scenario {
+    #[serde(default, rename = "device")]
+    devices: Vec<ScenarioDevice>,
+    #[serde(default, rename = "step")]
+    steps: Vec<ScenarioStep>,
+}
+
+#[derive(Deserialize)]
+struct ScenarioDevice {
+    /// Name the steps refer to the device by.
+    key: String,
+    vid_pid: String,
+    name: String,
+    #[serde(default = "default_class")]
+    class: String,
+    /// First part of the device ID, for devices that aren't on USB:
+    /// "THUNDERBOLT", "BTHENUM", "PCI", ...
+    #[serde(default = "default_enumerator")]
+    enumerator: String,
+    manufacturer: Option<String>,
+    description: Option<String>,
+    /// Driver the device is bound to, e.g. "usbstor".
+    driver: Option<String>,
+    /// Instance part of the device ID; defaults to the key.
+    serial: Option<String>,
+    /// Attached when the scenario starts.
+    #[serde(default)]
+    connected: bool,
+    storage: Option<StorageInfo>,
+    /// Key of the hub the device is plugged into.
+    parent: Option<String>,
+    /// Location shown in the topology tree, e.g. `1-2.3`.
+    port: Option<String>,
+    /// Negotiated speed: "high", "super", ...
+    speed: Option<LinkSpeed>,
+    usb_version: Option<String>,
+    /// `bMaxPower` in mA.
+    max_power_ma: Option<u32>,
+    #[serde(default)]
+    self_powered: bool,
+    /// Downstream ports, for hubs.
+    hub_ports: Option<u8>,
+    /// Interfaces of a composite device. Each one is reported as its own
+    /// `MI_xx` child entry, the way Windows lists them.
+    #[serde(default, rename = "interface")]
+    interfaces: Vec<ScenarioInterface>,
+}
+
+#[derive(Deserialize)]
+struct ScenarioInterface {
+    name: String,
+    #[serde(default = "default_class")]
+    class: String,
+}
+
+fn default_class() -> String {
+    "USB".to_string()
+}
+
+fn default_enumerator() -> String {
+    "USB".to_string()
+}
+
+#[derive(Deserialize)]
+struct ScenarioStep {
+    /// Seconds since the scenario started.
+    at: f64,
+    connect: Option<String>,
+    disconnect: Option<String>,
+    /// Flip the device to the other state and back, `count` times.
+    flap: Option<String>,
+    #[serde(default = "default_flap_count")]
+    count: u32,
+    /// Seconds between flap edges.
+    #[serde(default = "default_flap_interval")]
+    interval: f64,
+    /// Speed the device comes up at from this connect on, to play a
+    /// drive landing on a slower port.
+    speed: Option<LinkSpeed>,
+    /// Driver the device is bound to from this connect on. Connecting a
+    /// device that's already attached re-reports it, which plays a
+    /// driver being swapped under it.
+    driver: Option<String>,
+}
+
+fn default_flap_count() -> u32 {
+    1
+}
+
+fn default_flap_interval() -> f64 {
+    0.1
+}
+
+/// Scenario seconds as a duration, negative ones counting as zero. None
+/// for NaN, infinity, or times too large to represent.
+fn seconds(secs: f64) -> Option<Duration> {
+    if !secs.is_finite() {
+        return None;
+    }
+    Duration::try_from_secs_f64(secs.max(0.0)).ok()
+}
+
+/// A single connect or disconnect on the flattened timeline.
+struct Edge {
+    at: Duration,
+    key: String,
+    up: bool,
+    speed: Option<LinkSpeed>,
+    driver: Option<String>,
+}
+
+// ── Simulated source ───────────────────────────────────────────
+
+/// Plays a scripted scenario as if the devices were really being plugged
+/// in and out. Changes are reported as hotplug notifications, so flaps
+/// faster than the poll interval still reach the event log.
+pub struct SimulatedSource {
+    /// Entries for each scenario key: the device itself, then any
+    /// interface children.
+    devices: HashMap<String, Vec<(String, UsbDevice)>>,
+    storage: HashMap<String, StorageInfo>,
+    timeline: VecDeque<Edge>,
+    connected: DeviceMap,
+    start: Instant,
+}
+
+impl SimulatedSource {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Can't read scenario {}: {}", path.display(), e))?;
+        let scenario: Scenario = toml::from_str(&text)
+            .map_err(|e| format!("Bad scenario {}: {}", path.display(), e))?;
+        Self::from_scenario(scenario)
+    }
+
+    fn from_scenario(scenario: Scenario) -> Result<Self, String> {
+        let mut devices = HashMap::new();
+        let mut storage = HashMap::new();
+        let mut connected = DeviceMap::new();
+        let mut parents = Vec::new();
+        let mut initially = Vec::new();
+        for d in scenario.devices {
+            let (vid, pid) = d
+                .vid_pid
+                .split_once(':')
+                .ok_or_else(|| format!("Device '{}': vid_pid must look like 0781:5581", d.key))?;
+            let (vid, pid) = (vid.to_uppercase(), pid.to_uppercase());
+            let instance = d.serial.as_deref().unwrap_or(&d.key);
+            let enumerator = d.enumerator.to_uppercase();
+            // PCI and Thunderbolt IDs spell vendor and device differently.
+            let (vid_key, pid_key) = if Bus::from_enumerator(&enumerator).has_usb_ids() {
+                ("VID", "PID")
+            } else {
+                ("VEN", "DEV")
+            };
+            let id = format!(
+                "{}\\{}_{}&{}_{}\\{}",
+                enumerator, vid_key, vid, pid_key, pid, instance
+            );
+            let dev = UsbDevice {
+                Name: Some(d.name.clone()),
+                DeviceID: Some(id.clone()),
+                Description: Some(d.description.unwrap_or(d.name)),
+                Manufacturer: d.manufacturer.clone(),
+                PNPClass: Some(d.class),
+                Service: d.driver,
+                CompatibleID: None,
+                Interfaces: Vec::new(),
+                Parent: None,
+                LocationPath: d.port,
+                Speed: d.speed,
+                UsbVersion: d.usb_version,
+                Power: d.max_power_ma.map(|max_power_ma| DevicePower {
+                    max_power_ma,
+                    self_powered: d.self_powered,
+                    hub_ports: d.hub_ports,
+                }),
+                Rule: None,
+            };
+            if let Some(info) = d.storage {
+                storage.insert(id.clone(), info);
+            }
+            let mut entries = vec![(id, dev)];
+            for (n, iface) in d.interfaces.into_iter().enumerate() {
+                let id = format!(
+                    "{}\\{}_{}&{}_{}&MI_{:02X}\\{}&0&{:04X}",
+                    enumerator, vid_key, vid, pid_key, pid, n, instance, n
+                );
+                let dev = UsbDevice {
+                    Name: Some(iface.name.clone()),
+                    DeviceID: Some(id.clone()),
+                    Description: Some(iface.name),
+                    Manufacturer: d.manufacturer.clone(),
+                    PNPClass: Some(iface.class),
+                    Service: None,
+                    CompatibleID: None,
+                    Interfaces: Vec::new(),
+                    Parent: None,
+                    LocationPath: None,
+                    Speed: None,
+                    UsbVersion: None,
+                    Power: None,
+                    Rule: None,
+                };
+                entries.push((id, dev));
+            }
+            if let Some(parent) = d.parent {
+                parents.push((d.key.clone(), parent));
+            }
+            if d.connected {
+                initially.push(d.key.clone());
+            }
+            devices.insert(d.key, entries);
+        }
+        for (key, parent) in parents {
+            let parent_id = devices
+                .get(&parent)
+                .map(|entries| entries[0].0.clone())
+                .ok_or_else(|| format!("Device '{}': unknown parent '{}'", key, parent))?;
+            if let Some(entries) = devices.get_mut(&key) {
+                entries[0].1.Parent = Some(parent_id);
+            }
+        }
+        for key in initially {
+            connected.extend(devices[&key].iter().cloned());
+        }
+
+        // Flatten the steps into single connect/disconnect edges, tracking
+        // attachment so a flap knows which way to toggle first.
+        let mut attached: HashMap<&str, bool> = devices
+            .iter()
+            .map(|(key, entries)| (key.as_str(), connected.contains_key(&entries[0].0)))
+            .collect();
+        let mut steps = scenario.steps;
+        steps.sort_by(|a, b| a.at.total_cmp(&b.at));
+        let mut timeline = Vec::new();
+        for step in &steps {
+            let at = seconds(step.at)
+                .ok_or_else(|| format!("Step at {}s: `at` is not a valid time", step.at))?;
+            if !step.interval.is_finite() {
+                return Err(format!(
+                    "Step at {}s: `interval` must be a finite time, not {}",
+                    step.at, step.interval
+                ));
+            }
+            let flaps = step.count.checked_mul(2).ok_or_else(|| {
+                format!(
+                    "Step at {}s: `count` of {} is too large",
+                    step.at, step.count
+                )
+            })?;
+            let edges = [
+                (step.connect.as_deref(), Some(true), 1),
+                (step.disconnect.as_deref(), Some(false), 1),
+                (step.flap.as_deref(), None, flaps),
+            ];
+            for (key, target, n) in edges {
+                let Some(key) = key else { continue };
+                let state = attached
+                    .get_mut(key)
+                    .ok_or_else(|| format!("Step at {}s: unknown device '{}'", step.at, key))?;
+                for i in 0..n {
+                    let up = target.unwrap_or(!*state);
+                    let edge_at = seconds(step.interval * i as f64)
+                        .and_then(|offset| at.checked_add(offset))
+                        .ok_or_else(|| format!("Step at {}s: flap runs out of range", step.at))?;
+                    timeline.push(Edge {
+                        at: edge_at,
+                        key: key.to_string(),
+                        up,
+                        speed: step.speed.filter(|_| up),
+                        driver: step.driver.clone().filter(|_| up),
+                    });
+                    *state = up;
+                }
+            }
+        }
+        timeline.sort_by_key(|edge| edge.at);
+
+        Ok(Self {
+            devices,
+            storage,
+            timeline: timeline.into(),
+            connected,
+            start: Instant::now(),
+        })
+    }
+}
+
+impl DeviceSource for SimulatedSource {
+    fn name(&self) -> &'static str {
+        "simulate"
+    }
+
+    fn enumerate(&mut self) -> Result<DeviceMap, String> {
+        Ok(self.connected.clone())
+    }
+
+    fn wait_for_changes(&mut self, timeout: Duration) -> Vec<Hotplug> {
+        let deadline = self.start.elapsed() + timeout;
+        let next = self.timeline.front().map(|edge| edge.at);
+        let until = next.map_or(deadline, |at| at.min(deadline));
+        thread::sleep(until.saturating_sub(self.start.elapsed()));
+
+        let now = self.start.elapsed();
+        let mut changes = Vec::new();
+        while self.timeline.front().is_some_and(|edge| edge.at <= now) {
+            let Some(edge) = self.timeline.pop_front() else {
+                break;
+            };
+            let Some(entries) = self.devices.get_mut(&edge.key) else {
+                continue;
+            };
+            if let Some(speed) = edge.speed {
+                entries[0].1.Speed = Some(speed);
+            }
+            if let Some(driver) = edge.driver {
+                entries[0].1.Service = Some(driver);
+            }
+            for (id, dev) in entries.iter() {
+                if edge.up {
+                    self.connected.insert(id.clone(), dev.clone());
+                    changes.push(Hotplug::Added {
+                        id: id.clone(),
+                        device: Box::new(dev.clone()),
+                    });
+                } else if self.connected.remove(id).is_some() {
+                    changes.push(Hotplug::Removed { id: id.clone() });
+                }
+            }
+        }
+        changes
+    }
+
+    fn query_storage_info(&mut self, device_id: &str) -> Option<StorageInfo> {
+        if !self.connected.contains_key(device_id) {
+            return None;
+        }
+        self.storage.get(device_id).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(toml: &str) -> Result<SimulatedSource, String> {
+        SimulatedSource::from_scenario(toml::from_str(toml).map_err(|e| e.to_string())?)
+    }
+
+    const DEVICE: &str = r#"
+        [[device]]
+        key = "stick"
+        vid_pid = "0781:5581"
+        name = "SanDisk Ultra"
+    "#;
+
+    #[test]
+    fn demo_scenario_loads() {
+        let scenario = include_str!("../../../scenarios/demo.toml");
+        assert!(load(scenario).is_ok());
+    }
+
+    #[test]
+    fn flaps_expand_into_edges() {
+        let source = load(&format!(
+            "{}\n[[step]]\nat = 1.0\nflap = \"stick\"\ncount = 2\ninterval = 0.5\n",
+            DEVICE
+        ))
+        .unwrap();
+        let edges: Vec<_> = source
+            .timeline
+            .iter()
+            .map(|e| (e.at.as_millis(), e.up))
+            .collect();
+        assert_eq!(
+            edges,
+            [(1000, true), (1500, false), (2000, true), (2500, false)]
+        );
+    }
+
+    #[test]
+    fn rejects_times_that_are_not_finite() {
+        for step in [
+            "at = inf\nconnect = \"stick\"",
+            "at = nan\nconnect = \"stick\"",
+            "at = 1e300\nconnect = \"stick\"",
+            "at = 1.0\nflap = \"stick\"\ninterval = nan",
+            "at = 1.0\nflap = \"stick\"\ninterval = inf",
+        ] {
+            let err = load(&format!("{}\n[[step]]\n{}\n", DEVICE, step))
+                .err()
+                .unwrap_or_else(|| panic!("accepted {:?}", step));
+            assert!(err.starts_with("Step at "), "{}", err);
+        }
+    }
+
+    #[test]
+    fn rejects_flap_counts_that_overflow() {
+        let err = load(&format!(
+            "{}\n[[step]]\nat = 1\nflap = \"stick\"\ncount = {}\n",
+            DEVICE,
+            u32::MAX
+        ))
+        .err()
+        .unwrap();
+        assert_eq!(err, "Step at 1s: `count` of 4294967295 is too large");
+    }
+
+    #[test]
+    fn rejects_unknown_devices() {
+        let err = load(&format!(
+            "{}\n[[step]]\nat = 1\nconnect = \"mouse\"\n",
+            DEVICE
+        ))
+        .err()
+        .unwrap();
+        assert_eq!(err, "Step at 1s: unknown device 'mouse'");
+    }
+}
@@ -0,0 +1,987 @@
+#[cfg(target_os = "linux")]
+use super::Opener;
+use super::{DeviceFilter, DeviceMap, DeviceSource};
+#[cfg(target_os = "linux")]
+use crate::storage::{BlockStorage, StorageProvider};
+#[cfg(target_os = "linux")]
+use crate::types::StorageInfo;
+use crate::types::{Bus, DeviceCategory, DeviceInterface, DevicePower, LinkSpeed, UsbDevice};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Subsystems scanned besides `usb`, in the order they're enumerated.
+const OTHER_SUBSYSTEMS: [&str; 3] = ["thunderbolt", "pci", "hid"];
+
+/// Enumerates devices from `<root>/bus/<subsystem>/devices` on Linux:
+/// USB, plus Thunderbolt, hot-pluggable PCI and HID devices when the
+/// filter asks for them.
+///
+/// The root defaults to `/sys` but can point at any directory laid out
+/// the same way, which is how the backend is exercised without hardware.
+pub struct SysfsSource {
+    root: PathBuf,
+    /// Device ID last seen on each port, so a remove uevent can be mapped
+    /// back after the directory has vanished. USB ports are keyed by their
+    /// sysfs name (`1-2.3`), other buses by `subsystem/name`.
+    ports: HashMap<String, String>,
+    filter: DeviceFilter,
+    #[cfg(target_os = "linux")]
+    storage: BlockStorage,
+}
+
+impl Default for SysfsSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SysfsSource {
+    pub fn new() -> Self {
+        Self::with_root("/sys")
+    }
+
+    /// Block devices are looked up under the same root; device nodes and
+    /// mounts always come from the real `/dev` and `/proc`.
+    pub fn with_root(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        Self {
+            #[cfg(target_os = "linux")]
+            storage: BlockStorage::with_roots(&root, "/dev", "/proc/self/mountinfo"),
+            root,
+            ports: HashMap::new(),
+            filter: DeviceFilter::default(),
+        }
+    }
+
+    pub fn with_filter(mut self, filter: DeviceFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn port_id(&self, port: &str) -> Option<&String> {
+        self.ports.get(port)
+    }
+
+    #[cfg(test)]
+    pub fn remember_port(&mut self, port: &str, id: &str) {
+        self.ports.insert(port.to_string(), id.to_string());
+    }
+
+    pub fn forget_port(&mut self, port: &str) -> Option<String> {
+        self.ports.remove(port)
+    }
+
+    /// Whether devices of `subsystem` are wanted at all. HID carries
+    /// Bluetooth keyboards and mice too.
+    pub fn scans(&self, subsystem: &str) -> bool {
+        match subsystem {
+            "usb" => self.filter.wants(Bus::Usb),
+            "thunderbolt" => self.filter.wants(Bus::Thunderbolt),
+            "pci" => self.filter.wants(Bus::Pci),
+            "hid" => self.filter.wants(Bus::Hid) || self.filter.wants(Bus::Bluetooth),
+            _ => false,
+        }
+    }
+
+    /// Device ID of the hub above the device at `location`, if that hub
+    /// has been seen.
+    fn parent_id(&self, location: &str) -> Option<String> {
+        self.ports.get(&parent_port(location)?).cloned()
+    }
+
+    /// Parent of any device: the hub for USB, the upstream router for
+    /// Thunderbolt. Other buses don't report one.
+    fn parent_of(&self, dev: &UsbDevice) -> Option<String> {
+        let location = dev.LocationPath.as_deref()?;
+        match dev.bus() {
+            Bus::Usb => self.parent_id(location),
+            Bus::Thunderbolt => self
+                .ports
+                .get(&port_key("thunderbolt", &thunderbolt_parent(location)?))
+                .cloned(),
+            _ => None,
+        }
+    }
+
+    fn devices_dir(&self, subsystem: &str) -> PathBuf {
+        self.root.join("bus").join(subsystem).join("devices")
+    }
+
+    /// Reads one device directory (e.g. `1-2.3`) into a `UsbDevice` with a
+    /// Windows-style `USB\VID_xxxx&PID_xxxx\<instance>` device ID, so the
+    /// VID:PID and serial handling downstream works unchanged.
+    pub fn read_device(&self, dir: &Path) -> Option<UsbDevice> {
+        let kernel_name = dir.file_name()?.to_str()?;
+        let vid = read_attr(dir, "idVendor")?.to_uppercase();
+        let pid = read_attr(dir, "idProduct")?.to_uppercase();
+        let serial = read_attr(dir, "serial").filter(|s| !s.is_empty());
+        let product = read_attr(dir, "product");
+        let manufacturer = read_attr(dir, "manufacturer");
+        let (class, subclass, protocol) = device_class(dir, kernel_name);
+        let location = location(dir);
+
+        let instance = serial.unwrap_or_else(|| kernel_name.to_string());
+        Some(UsbDevice {
+            Name: product.clone(),
+            DeviceID: Some(format!("USB\\VID_{}&PID_{}\\{}", vid, pid, instance)),
+            Description: product,
+            Manufacturer: manufacturer,
+            PNPClass: Some(pnp_class_for(class).to_string()),
+            Service: interface_driver(dir, kernel_name),
+            CompatibleID: Some(vec![format!(
+                "USB\\Class_{:02X}&SubClass_{:02X}&Prot_{:02X}",
+                class, subclass, protocol
+            )]),
+            Interfaces: read_interfaces(dir, kernel_name, &vid, &pid, &instance),
+            Parent: location.as_deref().and_then(|l| self.parent_id(l)),
+            LocationPath: location,
+            Speed: read_attr(dir, "speed").and_then(|s| LinkSpeed::from_mbps(&s)),
+            UsbVersion: read_attr(dir, "version").filter(|v| !v.is_empty()),
+            Power: read_power(dir),
+            Rule: None,
+        })
+    }
+
+    /// Reads a device directory from any subsystem the filter takes.
+    /// None for devices it doesn't, that aren't hot-pluggable, or that
+    /// repeat a USB device already reported.
+    pub fn read_subsystem_device(&self, subsystem: &str, dir: &Path) -> Option<UsbDevice> {
+        let dev = match subsystem {
+            "usb" => self.read_device(dir),
+            "thunderbolt" => read_thunderbolt(dir),
+            "pci" => self.read_pci(dir),
+            "hid" if is_usb_hid(dir) && self.filter.wants(Bus::Usb) => None,
+            "hid" => read_hid(dir),
+            _ => None,
+        }?;
+        self.filter.allows(&dev).then_some(dev)
+    }
+
+    /// A PCI function (`0000:3c:00.0`) as `PCI\VEN_xxxx&DEV_xxxx\<address>`,
+    /// the way Windows names it. Only functions the kernel marks removable
+    /// (behind a Thunderbolt or USB4 port) or that sit in a hotplug slot
+    /// count; bridges are plumbing and skipped.
+    fn read_pci(&self, dir: &Path) -> Option<UsbDevice> {
+        let address = dir.file_name()?.to_str()?;
+        let class = read_attr(dir, "class")
+            .and_then(|c| u32::from_str_radix(c.trim_start_matches("0x"), 16).ok())
+            .unwrap_or(0);
+        let (base, sub) = ((class >> 16) as u8, (class >> 8) as u8);
+        if base == 0x06 {
+            return None;
+        }
+        let removable = read_attr(dir, "removable").is_some_and(|r| r == "removable");
+        if !removable && !self.in_hotplug_slot(address) {
+            return None;
+        }
+        let vendor = read_id(dir, "vendor")?;
+        let device = read_id(dir, "device")?;
+        let name = pci_class_name(base, sub);
+        Some(UsbDevice {
+            Name: Some(name.to_string()),
+            DeviceID: Some(format!(
+                "PCI\\VEN_{:04X}&DEV_{:04X}\\{}",
+                vendor, device, address
+            )),
+            Description: Some(name.to_string()),
+            Manufacturer: None,
+            PNPClass: Some(pci_pnp_class(base, sub).to_string()),
+            Service: driver(dir),
+            CompatibleID: Some(vec![format!("PCI\\CC_{:06X}", class)]),
+            Interfaces: Vec::new(),
+            Parent: None,
+            LocationPath: Some(address.to_string()),
+            Speed: None,
+            UsbVersion: None,
+            Power: None,
+            Rule: None,
+        })
+    }
+
+    /// Whether a PCI address is in one of `bus/pci/slots/*`, which only
+    /// lists slots a hotplug driver manages. Slots name the device, not
+    /// the function: `0000:3c:00`.
+    fn in_hotplug_slot(&self, address: &str) -> bool {
+        let Some((slot, _function)) = address.rsplit_once('.') else {
+            return false;
+        };
+        let Ok(entries) = fs::read_dir(self.root.join("bus/pci/slots")) else {
+            return false;
+        };
+        entries
+            .flatten()
+            .any(|e| read_attr(&e.path(), "address").is_some_and(|a| a == slot))
+    }
+
+    /// Remembers `dev` on `port` and returns its ID. When a device on
+    /// another port already reported the same serial, this one is keyed
+    /// by its sysfs name instead. Enumeration and hotplug both go through
+    /// here, so they agree on which of the two keeps the serial.
+    pub fn claim_id(&mut self, port: &str, name: &str, dev: &mut UsbDevice) -> Option<String> {
+        let mut id = dev.DeviceID.clone()?;
+        if self
+            .ports
+            .iter()
+            .any(|(p, taken)| *taken == id && p != port)
+        {
+            if let Some((prefix, _)) = id.rsplit_once('\\') {
+                id = format!("{}\\{}", prefix, name);
+            }
+            dev.DeviceID = Some(id.clone());
+        }
+        self.ports.insert(port.to_string(), id.clone());
+        Some(id)
+    }
+}
+
+impl DeviceSource for SysfsSource {
+    fn name(&self) -> &'static str {
+        "sysfs"
+    }
+
+    fn enumerate(&mut self) -> Result<DeviceMap, String> {
+        // (port, sysfs name, device)
+        let mut found: Vec<(String, String, UsbDevice)> = Vec::new();
+        if self.scans("usb") {
+            let dir = self.devices_dir("usb");
+            let entries =
+                fs::read_dir(&dir).map_err(|e| format!("Can't read {}: {}", dir.display(), e))?;
+            for entry in entries.flatten() {
+                let name = entry.file_name();
+                let Some(name) = name.to_str() else { continue };
+                // Interface nodes look like "1-2:1.0"; only whole devices count.
+                if name.contains(':') {
+                    continue;
+                }
+                if let Some(dev) = self.read_device(&entry.path()) {
+                    found.push((name.to_string(), name.to_string(), dev));
+                }
+            }
+        }
+        for subsystem in OTHER_SUBSYSTEMS {
+            if !self.scans(subsystem) {
+                continue;
+            }
+            // No Thunderbolt controller, no directory.
+            let Ok(entries) = fs::read_dir(self.devices_dir(subsystem)) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name();
+                let Some(name) = name.to_str() else { continue };
+                if let Some(dev) = self.read_subsystem_device(subsystem, &entry.path()) {
+                    found.push((port_key(subsystem, name), name.to_string(), dev));
+                }
+            }
+        }
+
+        // Of two devices sharing a serial, the one that had it before
+        // keeps it, whatever order the directories are listed in.
+        let previous = std::mem::take(&mut self.ports);
+        found.sort_by_key(|(port, _, dev)| previous.get(port) != dev.DeviceID.as_ref());
+        let mut devices = DeviceMap::new();
+        for (port, name, mut dev) in found {
+            if let Some(id) = self.claim_id(&port, &name, &mut dev) {
+                devices.insert(id, dev);
+            }
+        }
+        // Directory order doesn't put hubs first, so parents are only
+        // known once every port has been seen.
+        let parents: Vec<_> = devices.values().map(|dev| self.parent_of(dev)).collect();
+        for (dev, parent) in devices.values_mut().zip(parents) {
+            dev.Parent = parent;
+        }
+        Ok(devices)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn query_storage_info(&mut self, device_id: &str) -> Option<StorageInfo> {
+        self.storage.query_storage_info(device_id)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn storage_source(&self) -> Option<Opener> {
+        let root = self.root.clone();
+        Some(Box::new(move || {
+            Ok(Box::new(SysfsSource::with_root(root)) as Box<dyn DeviceSource>)
+        }))
+    }
+}
+
+/// Key for `SysfsSource::ports`. USB keeps the bare port name, which is
+/// what `parent_port` produces.
+pub fn port_key(subsystem: &str, name: &str) -> String {
+    if subsystem == "usb" {
+        name.to_string()
+    } else {
+        format!("{}/{}", subsystem, name)
+    }
+}
+
+fn read_attr(dir: &Path, attr: &str) -> Option<String> {
+    fs::read_to_string(dir.join(attr))
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+/// Name of the driver bound to the device at `dir`, from its `driver` link.
+fn driver(dir: &Path) -> Option<String> {
+    let link = fs::read_link(dir.join("driver")).ok()?;
+    Some(link.file_name()?.to_str()?.to_string())
+}
+
+/// A USB device itself is always bound to the generic `usb` driver; the
+/// one that matters is on its interfaces (`usb-storage`, `usbhid`, ...).
+/// Takes the lowest-numbered interface that has one.
+fn interface_driver(dir: &Path, kernel_name: &str) -> Option<String> {
+    let prefix = format!("{}:", kernel_name);
+    let mut interfaces: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|e| {
+            e.file_name()
+                .to_str()
+                .is_some_and(|n| n.starts_with(&prefix))
+        })
+        .map(|e| e.path())
+        .collect();
+    interfaces.sort();
+    interfaces.iter().find_map(|path| driver(path))
+}
+
+fn read_hex(dir: &Path, attr: &str) -> Option<u8> {
+    u8::from_str_radix(&read_attr(dir, attr)?, 16).ok()
+}
+
+/// bDeviceClass/SubClass/Protocol, or the first interface's when the
+/// device defers to its interfaces (0x00) or is a miscellaneous/composite
+/// device (0xEF).
+fn device_class(dir: &Path, kernel_name: &str) -> (u8, u8, u8) {
+    let device = (
+        read_hex(dir, "bDeviceClass").unwrap_or(0),
+        read_hex(dir, "bDeviceSubClass").unwrap_or(0),
+        read_hex(dir, "bDeviceProtocol").unwrap_or(0),
+    );
+    if device.0 != 0x00 && device.0 != 0xEF {
+        return device;
+    }
+    let config = read_attr(dir, "bConfigurationValue")
+        .filter(|c| !c.is_empty())
+        .unwrap_or_else(|| "1".to_string());
+    let iface = dir.join(format!("{}:{}.0", kernel_name, config));
+    match read_hex(&iface, "bInterfaceClass") {
+        Some(class) => (
+            class,
+            read_hex(&iface, "bInterfaceSubClass").unwrap_or(0),
+            read_hex(&iface, "bInterfaceProtocol").unwrap_or(0),
+        ),
+        None => device,
+    }
+}
+
+/// `bMaxPower` ("100mA"), the self-powered bit of `bmAttributes`, and
+/// `maxchild` for hubs.
+fn read_power(dir: &Path) -> Option<DevicePower> {
+    let max_power = read_attr(dir, "bMaxPower")?;
+    let max_power_ma = max_power.trim_end_matches("mA").trim().parse().ok()?;
+    let attributes = read_hex(dir, "bmAttributes").unwrap_or(0);
+    Some(DevicePower {
+        max_power_ma,
+        self_powered: attributes & 0x40 != 0,
+        hub_ports: read_attr(dir, "maxchild")
+            .and_then(|m| m.parse().ok())
+            .filter(|p| *p > 0),
+    })
+}
+
+/// `busnum-devpath`, which is also the sysfs name: `1-2.3`, or `usb1` for
+/// a root hub (devpath 0).
+fn location(dir: &Path) -> Option<String> {
+    let bus = read_attr(dir, "busnum")?;
+    match read_attr(dir, "devpath")?.as_str() {
+        "0" => Some(format!("usb{}", bus)),
+        devpath => Some(format!("{}-{}", bus, devpath)),
+    }
+}
+
+/// The hub port above `location`: `1-2.3` → `1-2` → `usb1`.
+fn parent_port(location: &str) -> Option<String> {
+    let (bus, devpath) = location.split_once('-')?;
+    Some(match devpath.rsplit_once('.') {
+        Some((hub, _)) => format!("{}-{}", bus, hub),
+        None => format!("usb{}", bus),
+    })
+}
+
+/// The interface nodes of a composite device (`1-2:1.0`, `1-2:1.1`, ...),
+/// with Windows-style `MI_xx` IDs. Single-interface devices get none, as
+/// Windows doesn't list a child for them either.
+fn read_interfaces(
+    dir: &Path,
+    kernel_name: &str,
+    vid: &str,
+    pid: &str,
+    instance: &str,
+) -> Vec<DeviceInterface> {
+    let prefix = format!("{}:", kernel_name);
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut interfaces: Vec<DeviceInterface> = entries
+        .flatten()
+        .filter(|e| {
+            e.file_name()
+                .to_str()
+                .is_some_and(|n| n.starts_with(&prefix))
+        })
+        .filter_map(|e| {
+            let path = e.path();
+            let number = read_hex(&path, "bInterfaceNumber")?;
+            let codes = (
+                read_hex(&path, "bInterfaceClass").unwrap_or(0),
+                read_hex(&path, "bInterfaceSubClass").unwrap_or(0),
+                read_hex(&path, "bInterfaceProtocol").unwrap_or(0),
+            );
+            Some(DeviceInterface {
+                device_id: format!(
+                    "USB\\VID_{}&PID_{}&MI_{:02X}\\{}",
+                    vid, pid, number, instance
+                ),
+                number: Some(number),
+                name: read_attr(&path, "interface")
+                    .filter(|s| !s.is_empty())
+                    .unwrap_or_else(|| format!("Interface {}", number)),
+                class: pnp_class_for(codes.0).to_string(),
+                category: DeviceCategory::from_class_codes(codes.0, codes.1, codes.2)
+                    .unwrap_or_default(),
+            })
+        })
+        .collect();
+    if interfaces.len() < 2 {
+        return Vec::new();
+    }
+    interfaces.sort_by_key(|i| i.number);
+    interfaces
+}
+
+/// Maps a USB class code to the closest Windows PnP class name, so class
+/// based logic (storage detection, UI filters) treats both backends alike.
+fn pnp_class_for(class: u8) -> &'static str {
+    match class {
+        0x01 => "MEDIA",
+        0x02 | 0x0A => "Ports",
+        0x03 => "HIDClass",
+        0x06 => "Image",
+        0x07 => "Printer",
+        0x08 => "DiskDrive",
+        0x0B => "SmartCardReader",
+        0x0E => "Camera",
+        0xE0 => "Bluetooth",
+        _ => "USB",
+    }
+}
+
+// ── Other buses ────────────────────────────────────────────────
+
+/// `vendor`/`device` attributes, which sysfs writes as `0x8086`.
+fn read_id(dir: &Path, attr: &str) -> Option<u16> {
+    u16::from_str_radix(read_attr(dir, attr)?.trim_start_matches("0x"), 16).ok()
+}
+
+/// `KEY=value` lines of a device's `uevent` file.
+fn read_uevent(dir: &Path) -> HashMap<String, String> {
+    fs::read_to_string(dir.join("uevent"))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+/// A Thunderbolt/USB4 device (`0-1`, `0-301`) as
+/// `THUNDERBOLT\VEN_xxxx&DEV_xxxx\<unique_id>`. Host routers (`0-0`),
+/// domains and XDomain services are skipped.
+fn read_thunderbolt(dir: &Path) -> Option<UsbDevice> {
+    let name = dir.file_name()?.to_str()?;
+    let (_domain, route) = name.split_once('-')?;
+    if u64::from_str_radix(route, 16).ok()? == 0 {
+        return None;
+    }
+    let unique_id = read_attr(dir, "unique_id").filter(|u| !u.is_empty())?;
+    let product = read_attr(dir, "device_name").filter(|s| !s.is_empty());
+    Some(UsbDevice {
+        Name: product.clone(),
+        DeviceID: Some(format!(
+            "THUNDERBOLT\\VEN_{:04X}&DEV_{:04X}\\{}",
+            read_id(dir, "vendor").unwrap_or(0),
+            read_id(dir, "device").unwrap_or(0),
+            unique_id
+        )),
+        Description: product,
+        Manufacturer: read_attr(dir, "vendor_name").filter(|s| !s.is_empty()),
+        PNPClass: Some("System".to_string()),
+        Service: driver(dir),
+        CompatibleID: None,
+        Interfaces: Vec::new(),
+        Parent: None,
+        LocationPath: Some(name.to_string()),
+        Speed: None,
+        UsbVersion: None,
+        Power: None,
+        Rule: None,
+    })
+}
+
+/// The router upstream of `0-301`. Each byte of the route string is a
+/// port number, first hop lowest, so dropping the top byte gives the
+/// parent: `0-301` → `0-1`. None when that's the host router.
+fn thunderbolt_parent(location: &str) -> Option<String> {
+    let (domain, route) = location.split_once('-')?;
+    let route = u64::from_str_radix(route, 16).ok()?;
+    let depth = (64 - route.leading_zeros()).div_ceil(8);
+    let parent = route & (1u64 << (8 * (depth.max(1) - 1))).wrapping_sub(1);
+    (parent != 0).then(|| format!("{}-{:x}", domain, parent))
+}
+
+/// A HID device (`0005:046D:B023.0004`). Bluetooth ones (bus 0005) get a
+/// `BTHENUM\` ID so they count as Bluetooth; the rest stay `HID\`. The
+/// instance is `HID_UNIQ` (a serial or Bluetooth address) or else
+/// `HID_PHYS`, since the `.0004` suffix changes on every connect.
+fn read_hid(dir: &Path) -> Option<UsbDevice> {
+    let kernel_name = dir.file_name()?.to_str()?;
+    let mut parts = kernel_name.split([':', '.']);
+    let bus = u16::from_str_radix(parts.next()?, 16).ok()?;
+    let vid = u16::from_str_radix(parts.next()?, 16).ok()?;
+    let pid = u16::from_str_radix(parts.next()?, 16).ok()?;
+    let mut vars = read_uevent(dir);
+    let mut take = |key: &str| vars.remove(key).filter(|v| !v.is_empty());
+    let name = take("HID_NAME");
+    let instance = take("HID_UNIQ")
+        .or_else(|| take("HID_PHYS"))
+        .unwrap_or_else(|| kernel_name.to_string());
+    let enumerator = if bus == 0x0005 { "BTHENUM" } else { "HID" };
+    Some(UsbDevice {
+        Name: name.clone(),
+        DeviceID: Some(format!(
+            "{}\\VID_{:04X}&PID_{:04X}\\{}",
+            enumerator, vid, pid, instance
+        )),
+        Description: name,
+        Manufacturer: None,
+        PNPClass: Some("HIDClass".to_string()),
+        Service: driver(dir),
+        CompatibleID: None,
+        Interfaces: Vec::new(),
+        Parent: None,
+        LocationPath: None,
+        Speed: None,
+        UsbVersion: None,
+        Power: None,
+        Rule: None,
+    })
+}
+
+/// Whether a HID node sits on the USB bus (`0003:...`), as the input
+/// interface of a USB device.
+fn is_usb_hid(dir: &Path) -> bool {
+    dir.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.starts_with("0003:"))
+}
+
+/// Generic name for a PCI base class and subclass, since sysfs has no
+/// product string and `pci.ids` isn't bundled.
+fn pci_class_name(base: u8, sub: u8) -> &'static str {
+    match (base, sub) {
+        (0x01, 0x08) => "NVMe Controller",
+        (0x01, _) => "Storage Controller",
+        (0x02, _) => "Network Controller",
+        (0x03, _) => "Display Controller",
+        (0x04, 0x03) => "Audio Device",
+        (0x04, _) => "Multimedia Controller",
+        (0x08, 0x05) => "SD Host Controller",
+        (0x0C, 0x03) => "USB Controller",
+        (0x0C, 0x0A) => "USB4 Host Interface",
+        (0x0D, 0x11) => "Bluetooth Controller",
+        (0x0D, _) => "Wireless Controller",
+        _ => "PCI Device",
+    }
+}
+
+/// The Windows PnP class for a PCI base class and subclass.
+fn pci_pnp_class(base: u8, sub: u8) -> &'static str {
+    match (base, sub) {
+        (0x01, _) => "SCSIAdapter",
+        (0x02, _) => "Net",
+        (0x03, _) => "Display",
+        (0x04, _) => "MEDIA",
+        (0x08, 0x05) => "SDHost",
+        (0x0C, 0x03) => "USB",
+        (0x0D, 0x11) => "Bluetooth",
+        _ => "System",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Writes `attrs` as files in `<root>/bus/<subsystem>/devices/<name>`.
+    fn bus_dir(root: &Path, subsystem: &str, name: &str, attrs: &[(&str, &str)]) -> PathBuf {
+        let dir = root.join("bus").join(subsystem).join("devices").join(name);
+        fs::create_dir_all(&dir).unwrap();
+        for (attr, value) in attrs {
+            fs::write(dir.join(attr), format!("{}\n", value)).unwrap();
+        }
+        dir
+    }
+
+    fn usb_dir(root: &Path, name: &str, attrs: &[(&str, &str)]) -> PathBuf {
+        bus_dir(root, "usb", name, attrs)
+    }
+
+    /// A sysfs root with no USB devices, for the other buses.
+    fn bare_root() -> TempDir {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("bus/usb/devices")).unwrap();
+        root
+    }
+
+    fn filter(buses: &[Bus]) -> DeviceFilter {
+        DeviceFilter {
+            buses: buses.iter().copied().collect(),
+            enumerators: Vec::new(),
+        }
+    }
+
+    fn ids(devices: &DeviceMap) -> Vec<&str> {
+        let mut ids: Vec<&str> = devices.keys().map(String::as_str).collect();
+        ids.sort();
+        ids
+    }
+
+    /// A root hub, a hub on its port 2 and a flash drive on the hub's
+    /// port 3.
+    fn tree() -> TempDir {
+        let root = tempfile::tempdir().unwrap();
+        usb_dir(
+            root.path(),
+            "usb1",
+            &[
+                ("idVendor", "1d6b"),
+                ("idProduct", "0002"),
+                ("busnum", "1"),
+                ("devpath", "0"),
+                ("bDeviceClass", "09"),
+            ],
+        );
+        usb_dir(
+            root.path(),
+            "1-2",
+            &[
+                ("idVendor", "05e3"),
+                ("idProduct", "0610"),
+                ("product", "USB2.0 Hub"),
+                ("busnum", "1"),
+                ("devpath", "2"),
+                ("bDeviceClass", "09"),
+                ("speed", "480"),
+                ("bMaxPower", "100mA"),
+                ("bmAttributes", "e0"),
+                ("maxchild", "4"),
+            ],
+        );
+        usb_dir(
+            root.path(),
+            "1-2.3",
+            &[
+                ("idVendor", "0781"),
+                ("idProduct", "5581"),
+                ("serial", "4C530001"),
+                ("product", "Ultra"),
+                ("manufacturer", "SanDisk"),
+                ("busnum", "1"),
+                ("devpath", "2.3"),
+                ("bDeviceClass", "00"),
+                ("bConfigurationValue", "1"),
+                ("speed", "5000"),
+                ("version", " 3.20"),
+                ("bMaxPower", "896mA"),
+                ("bmAttributes", "80"),
+            ],
+        );
+        let iface = root.path().join("bus/usb/devices/1-2.3/1-2.3:1.0");
+        fs::create_dir_all(&iface).unwrap();
+        fs::write(iface.join("bInterfaceClass"), "08\n").unwrap();
+        fs::write(iface.join("bInterfaceNumber"), "00\n").unwrap();
+        // Interface nodes are listed next to devices, and skipped.
+        fs::create_dir_all(root.path().join("bus/usb/devices/1-2.3:1.0")).unwrap();
+        root
+    }
+
+    #[test]
+    fn enumerates_ids_names_and_serials() {
+        let root = tree();
+        let mut sysfs = SysfsSource::with_root(root.path());
+        let devices = sysfs.enumerate().unwrap();
+        assert_eq!(devices.len(), 3);
+
+        let drive = &devices["USB\\VID_0781&PID_5581\\4C530001"];
+        assert_eq!(drive.Name.as_deref(), Some("Ultra"));
+        assert_eq!(drive.Manufacturer.as_deref(), Some("SanDisk"));
+        assert_eq!(drive.PNPClass.as_deref(), Some("DiskDrive"));
+        // Removes only carry the port, so it maps back to the ID.
+        assert_eq!(
+            sysfs.port_id("1-2.3").map(String::as_str),
+            Some("USB\\VID_0781&PID_5581\\4C530001")
+        );
+    }
+
+    #[test]
+    fn devices_without_a_serial_use_their_port() {
+        let root = tree();
+        let devices = SysfsSource::with_root(root.path()).enumerate().unwrap();
+        let hub = &devices["USB\\VID_05E3&PID_0610\\1-2"];
+        assert_eq!(hub.Name.as_deref(), Some("USB2.0 Hub"));
+        assert!(devices.contains_key("USB\\VID_1D6B&PID_0002\\usb1"));
+    }
+
+    #[test]
+    fn reads_link_speed_and_version() {
+        let root = tree();
+        let devices = SysfsSource::with_root(root.path()).enumerate().unwrap();
+        let drive = &devices["USB\\VID_0781&PID_5581\\4C530001"];
+        assert_eq!(drive.Speed, Some(LinkSpeed::Super));
+        assert_eq!(drive.UsbVersion.as_deref(), Some("3.20"));
+        let hub = &devices["USB\\VID_05E3&PID_0610\\1-2"];
+        assert_eq!(hub.Speed, Some(LinkSpeed::High));
+        assert_eq!(hub.UsbVersion, None);
+        // Speeds the kernel may add later are left unknown.
+        assert_eq!(LinkSpeed::from_mbps("40000"), None);
+    }
+
+    #[test]
+    fn reads_power_figures() {
+        let root = tree();
+        let devices = SysfsSource::with_root(root.path()).enumerate().unwrap();
+        let drive = &devices["USB\\VID_0781&PID_5581\\4C530001"];
+        assert_eq!(
+            drive.Power,
+            Some(DevicePower {
+                max_power_ma: 896,
+                self_powered: false,
+                hub_ports: None,
+            })
+        );
+        let hub = &devices["USB\\VID_05E3&PID_0610\\1-2"];
+        assert_eq!(
+            hub.Power,
+            Some(DevicePower {
+                max_power_ma: 100,
+                self_powered: true,
+                hub_ports: Some(4),
+            })
+        );
+        // No bMaxPower, no figures.
+        assert_eq!(devices["USB\\VID_1D6B&PID_0002\\usb1"].Power, None);
+    }
+
+    #[test]
+    fn links_devices_to_their_hub_port() {
+        let root = tree();
+        let devices = SysfsSource::with_root(root.path()).enumerate().unwrap();
+        let drive = &devices["USB\\VID_0781&PID_5581\\4C530001"];
+        assert_eq!(drive.LocationPath.as_deref(), Some("1-2.3"));
+        assert_eq!(drive.Parent.as_deref(), Some("USB\\VID_05E3&PID_0610\\1-2"));
+        let hub = &devices["USB\\VID_05E3&PID_0610\\1-2"];
+        assert_eq!(hub.Parent.as_deref(), Some("USB\\VID_1D6B&PID_0002\\usb1"));
+        assert_eq!(devices["USB\\VID_1D6B&PID_0002\\usb1"].Parent, None);
+    }
+
+    #[test]
+    fn parent_ports() {
+        assert_eq!(parent_port("1-2.3.4").as_deref(), Some("1-2.3"));
+        assert_eq!(parent_port("1-2").as_deref(), Some("usb1"));
+        assert_eq!(parent_port("usb1"), None);
+    }
+
+    #[test]
+    fn duplicate_serials_fall_back_to_the_port() {
+        let root = tree();
+        usb_dir(
+            root.path(),
+            "1-2.4",
+            &[
+                ("idVendor", "0781"),
+                ("idProduct", "5581"),
+                ("serial", "4C530001"),
+                ("busnum", "1"),
+                ("devpath", "2.4"),
+            ],
+        );
+        let devices = SysfsSource::with_root(root.path()).enumerate().unwrap();
+        assert_eq!(devices.len(), 4);
+        let ids: Vec<_> = devices
+            .keys()
+            .filter(|id| id.starts_with("USB\\VID_0781&PID_5581\\"))
+            .collect();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.iter().any(|id| id.ends_with("\\4C530001")));
+    }
+
+    #[test]
+    fn reads_removable_pci_functions() {
+        let root = bare_root();
+        let pci = [
+            // An NVMe drive in a Thunderbolt enclosure.
+            ("0000:3c:00.0", "0x010802", "0x144d", "removable"),
+            // The bridge in front of it is plumbing.
+            ("0000:3b:00.0", "0x060400", "0x8086", "removable"),
+            // Soldered down, and not in a hotplug slot.
+            ("0000:00:1f.3", "0x040300", "0x8086", "fixed"),
+            // Fixed, but in a slot the hotplug driver manages.
+            ("0000:05:00.0", "0x020000", "0x10ec", "fixed"),
+        ];
+        for (address, class, vendor, removable) in pci {
+            bus_dir(
+                root.path(),
+                "pci",
+                address,
+                &[
+                    ("class", class),
+                    ("vendor", vendor),
+                    ("device", "0xa808"),
+                    ("removable", removable),
+                ],
+            );
+        }
+        let slot = root.path().join("bus/pci/slots/3");
+        fs::create_dir_all(&slot).unwrap();
+        fs::write(slot.join("address"), "0000:05:00\n").unwrap();
+
+        // PCI is opt-in.
+        let devices = SysfsSource::with_root(root.path()).enumerate().unwrap();
+        assert!(devices.is_empty());
+
+        let devices = SysfsSource::with_root(root.path())
+            .with_filter(filter(&[Bus::Pci]))
+            .enumerate()
+            .unwrap();
+        assert_eq!(
+            ids(&devices),
+            [
+                "PCI\\VEN_10EC&DEV_A808\\0000:05:00.0",
+                "PCI\\VEN_144D&DEV_A808\\0000:3c:00.0",
+            ]
+        );
+        let nvme = &devices["PCI\\VEN_144D&DEV_A808\\0000:3c:00.0"];
+        assert_eq!(nvme.Name.as_deref(), Some("NVMe Controller"));
+        assert_eq!(nvme.PNPClass.as_deref(), Some("SCSIAdapter"));
+        assert_eq!(nvme.bus(), Bus::Pci);
+    }
+
+    #[test]
+    fn reads_thunderbolt_devices_below_their_router() {
+        let root = bare_root();
+        bus_dir(root.path(), "thunderbolt", "domain0", &[]);
+        bus_dir(
+            root.path(),
+            "thunderbolt",
+            "0-0",
+            &[("unique_id", "host"), ("device_name", "Host")],
+        );
+        bus_dir(
+            root.path(),
+            "thunderbolt",
+            "0-1",
+            &[
+                ("unique_id", "d1a3e0c2-aa00-4b2b-ffff-ffffffffffff"),
+                ("vendor", "0x8086"),
+                ("device", "0x0b26"),
+                ("vendor_name", "CalDigit, Inc."),
+                ("device_name", "TS4"),
+            ],
+        );
+        bus_dir(
+            root.path(),
+            "thunderbolt",
+            "0-301",
+            &[
+                ("unique_id", "0c8a4f51-2200-5e33-ffff-ffffffffffff"),
+                ("vendor", "0x0108"),
+                ("device", "0x0002"),
+                ("device_name", "Thunderbolt SSD"),
+            ],
+        );
+
+        // Thunderbolt is on by default.
+        let devices = SysfsSource::with_root(root.path()).enumerate().unwrap();
+        assert_eq!(devices.len(), 2);
+        let dock = &devices["THUNDERBOLT\\VEN_8086&DEV_0B26\\d1a3e0c2-aa00-4b2b-ffff-ffffffffffff"];
+        assert_eq!(dock.Name.as_deref(), Some("TS4"));
+        assert_eq!(dock.Manufacturer.as_deref(), Some("CalDigit, Inc."));
+        assert_eq!(dock.Parent, None);
+        let ssd = &devices["THUNDERBOLT\\VEN_0108&DEV_0002\\0c8a4f51-2200-5e33-ffff-ffffffffffff"];
+        assert_eq!(ssd.Parent, dock.DeviceID);
+        assert_eq!(ssd.bus(), Bus::Thunderbolt);
+    }
+
+    #[test]
+    fn thunderbolt_parents() {
+        assert_eq!(thunderbolt_parent("0-301").as_deref(), Some("0-1"));
+        assert_eq!(thunderbolt_parent("1-30201").as_deref(), Some("1-201"));
+        assert_eq!(thunderbolt_parent("0-1"), None);
+        assert_eq!(thunderbolt_parent("domain0"), None);
+    }
+
+    #[test]
+    fn reads_hid_nodes_without_repeating_usb_devices() {
+        let root = bare_root();
+        bus_dir(
+            root.path(),
+            "hid",
+            "0005:046D:B023.0004",
+            &[(
+                "uevent",
+                "DRIVER=hid-generic\nHID_NAME=MX Master 3\nHID_PHYS=a0:b1:c2:d3:e4:f5\nHID_UNIQ=11:22:33:44:55:66",
+            )],
+        );
+        bus_dir(
+            root.path(),
+            "hid",
+            "0003:046D:C077.0001",
+            &[(
+                "uevent",
+                "HID_NAME=Logitech USB Optical Mouse\nHID_PHYS=usb-0000:00:14.0-2/input0\nHID_UNIQ=",
+            )],
+        );
+        let mouse = "HID\\VID_046D&PID_C077\\usb-0000:00:14.0-2/input0";
+        let bluetooth = "BTHENUM\\VID_046D&PID_B023\\11:22:33:44:55:66";
+
+        // Bluetooth is on by default, plain HID isn't.
+        let devices = SysfsSource::with_root(root.path()).enumerate().unwrap();
+        assert_eq!(ids(&devices), [bluetooth]);
+        assert_eq!(devices[bluetooth].Name.as_deref(), Some("MX Master 3"));
+        assert_eq!(devices[bluetooth].bus(), Bus::Bluetooth);
+
+        // A USB mouse's HID node is the USB device again.
+        let devices = SysfsSource::with_root(root.path())
+            .with_filter(filter(&[Bus::Usb, Bus::Hid]))
+            .enumerate()
+            .unwrap();
+        assert!(!devices.contains_key(mouse));
+
+        // Unless USB itself isn't watched.
+        let devices = SysfsSource::with_root(root.path())
+            .with_filter(filter(&[Bus::Hid]))
+            .enumerate()
+            .unwrap();
+        assert_eq!(ids(&devices), [mouse]);
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+
+// ── Kernel uevent parsing ──────────────────────────────────────
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UeventAction {
+    Add,
+    Remove,
+    Bind,
+    Unbind,
+    Change,
+    Other(String),
+}
+
+impl UeventAction {
+    fn parse(s: &str) -> Self {
+        match s {
+            "add" => Self::Add,
+            "remove" => Self::Remove,
+            "bind" => Self::Bind,
+            "unbind" => Self::Unbind,
+            "change" => Self::Change,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+/// One kernel uevent, e.g. from `NETLINK_KOBJECT_UEVENT`.
+#[derive(Clone, Debug)]
+pub struct Uevent {
+    pub action: UeventAction,
+    /// Path below `/sys`, e.g. `/devices/pci0000:00/0000:00:14.0/usb1/1-2`.
+    pub devpath: String,
+    pub subsystem: String,
+    pub devtype: Option<String>,
+    /// `PRODUCT=781/5581/100` — VID/PID/bcdDevice in unpadded hex.
+    pub product: Option<String>,
+}
+
+impl Uevent {
+    /// Last path component, which is the sysfs device name (`1-2.3`).
+    pub fn kernel_name(&self) -> &str {
+        self.devpath.rsplit('/').next().unwrap_or("")
+    }
+
+    /// True for whole USB devices, as opposed to their interfaces.
+    pub fn is_usb_device(&self) -> bool {
+        if self.subsystem != "usb" {
+            return false;
+        }
+        match self.devtype.as_deref() {
+            Some(t) => t == "usb_device",
+            None => !self.kernel_name().contains(':'),
+        }
+    }
+
+    pub fn vid_pid(&self) -> Option<(u16, u16)> {
+        let mut parts = self.product.as_deref()?.split('/');
+        let vid = u16::from_str_radix(parts.next()?, 16).ok()?;
+        let pid = u16::from_str_radix(parts.next()?, 16).ok()?;
+        Some((vid, pid))
+    }
+}
+
+/// What an event's header line announces.
+#[derive(Default)]
+struct Header {
+    action: String,
+    devpath: String,
+    /// Only udevadm's headers name the subsystem.
+    subsystem: Option<String>,
+}
+
+/// Header of a kernel netlink message (`add@/devices/...`) or of a
+/// `udevadm monitor --kernel` line (`KERNEL[12.34] add /devices/... (usb)`).
+fn parse_header(field: &str) -> Option<Header> {
+    if let Some(rest) = field.strip_prefix("KERNEL[") {
+        let mut words = rest.split_once(']')?.1.split_whitespace();
+        return Some(Header {
+            action: words.next()?.to_string(),
+            devpath: words.next()?.to_string(),
+            subsystem: words
+                .next()
+                .and_then(|w| w.strip_prefix('(')?.strip_suffix(')'))
+                .map(str::to_string),
+        });
+    }
+    let at = field.find('@')?;
+    if field[..at].contains('=') {
+        return None;
+    }
+    Some(Header {
+        action: field[..at].to_string(),
+        devpath: field[at + 1..].to_string(),
+        subsystem: None,
+    })
+}
+
+#[derive(Default)]
+struct Pending {
+    header: Option<Header>,
+    vars: HashMap<String, String>,
+}
+
+impl Pending {
+    fn finish(self) -> Option<Uevent> {
+        let mut vars = self.vars;
+        let Header {
+            action: h_action,
+            devpath: h_devpath,
+            subsystem: h_subsystem,
+        } = self.header.unwrap_or_default();
+        let action = vars.remove("ACTION").unwrap_or(h_action);
+        let devpath = vars.remove("DEVPATH").unwrap_or(h_devpath);
+        if action.is_empty() || devpath.is_empty() {
+            return None;
+        }
+        Some(Uevent {
+            action: UeventAction::parse(&action),
+            devpath,
+            subsystem: vars.remove("SUBSYSTEM").or(h_subsystem).unwrap_or_default(),
+            devtype: vars.remove("DEVTYPE"),
+            product: vars.remove("PRODUCT"),
+        })
+    }
+}
+
+/// Splits an arbitrary byte stream into uevents, so a recorded dump
+/// parses the same way as a live datagram.
+///
+/// Fields may be separated by NULs (raw netlink datagrams, possibly
+/// concatenated into a dump) or newlines (`udevadm monitor --property`
+/// output). A new event starts at a header or at a repeated `ACTION=`.
+/// Anything that doesn't look like a field is skipped, including the
+/// `UDEV` lines udevadm prints for its own re-broadcasts.
+pub fn parse_uevents(bytes: &[u8]) -> Vec<Uevent> {
+    let mut events = Vec::new();
+    let mut pending = Pending::default();
+    let mut skipping = false;
+
+    for raw in bytes.split(|&b| b == 0 || b == b'\n') {
+        let field = String::from_utf8_lossy(raw);
+        let field = field.trim();
+        if field.is_empty() {
+            continue;
+        }
+
+        if field.starts_with("UDEV") && field.contains('[') {
+            events.extend(std::mem::take(&mut pending).finish());
+            skipping = true;
+            continue;
+        }
+        if let Some(header) = parse_header(field) {
+            events.extend(std::mem::take(&mut pending).finish());
+            pending.header = Some(header);
+            skipping = false;
+            continue;
+        }
+        if skipping {
+            continue;
+        }
+        let Some((key, value)) = field.split_once('=') else {
+            continue;
+        };
+        if key == "ACTION" && pending.vars.contains_key("ACTION") {
+            events.extend(std::mem::take(&mut pending).finish());
+        }
+        pending.vars.insert(key.to_string(), value.to_string());
+    }
+    events.extend(pending.finish());
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NETLINK_DUMP: &[u8] = include_bytes!("../../tests/fixtures/uevent-netlink.bin");
+    const UDEVADM_DUMP: &str = include_str!("../../tests/fixtures/udevadm-monitor.txt");
+
+    fn summary(events: &[Uevent]) -> Vec<(UeventAction, &str, &str)> {
+        events
+            .iter()
+            .map(|e| (e.action.clone(), e.kernel_name(), e.subsystem.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_a_netlink_dump() {
+        let events = parse_uevents(NETLINK_DUMP);
+        assert_eq!(
+            summary(&events),
+            [
+                (UeventAction::Add, "1-2", "usb"),
+                (UeventAction::Add, "1-2:1.0", "usb"),
+                (UeventAction::Bind, "1-2", "usb"),
+                (UeventAction::Change, "AC", "power_supply"),
+                (UeventAction::Remove, "1-2:1.0", "usb"),
+                (UeventAction::Remove, "1-2", "usb"),
+            ]
+        );
+        assert!(events[0].is_usb_device());
+        assert!(!events[1].is_usb_device());
+        assert!(!events[3].is_usb_device());
+        assert_eq!(events[0].vid_pid(), Some((0x0781, 0x5581)));
+        assert_eq!(
+            events[0].devpath,
+            "/devices/pci0000:00/0000:00:14.0/usb1/1-2"
+        );
+    }
+
+    #[test]
+    fn parses_udevadm_monitor_output() {
+        let events = parse_uevents(UDEVADM_DUMP.as_bytes());
+        // The UDEV re-broadcast of the first add is skipped.
+        assert_eq!(
+            summary(&events),
+            [
+                (UeventAction::Add, "1-2", "usb"),
+                (UeventAction::Add, "1-2:1.0", "usb"),
+                (UeventAction::Remove, "1-2:1.0", "usb"),
+                (UeventAction::Remove, "1-2", "usb"),
+            ]
+        );
+        assert_eq!(events[0].devtype.as_deref(), Some("usb_device"));
+        assert_eq!(events[0].vid_pid(), Some((0x046D, 0xC52B)));
+    }
+
+    #[test]
+    fn headers_alone_are_enough() {
+        let events = parse_uevents(b"KERNEL[1.0] remove /devices/pci0000:00/usb2/2-1 (usb)\n");
+        assert_eq!(summary(&events), [(UeventAction::Remove, "2-1", "usb")]);
+        // A SUBSYSTEM= line, when there is one, has the last word.
+        let events =
+            parse_uevents(b"KERNEL[1.0] change /devices/virtual/misc/x (usb)\nSUBSYSTEM=misc\n");
+        assert_eq!(summary(&events), [(UeventAction::Change, "x", "misc")]);
+        let events = parse_uevents(b"remove@/devices/pci0000:00/usb2/2-1\0");
+        assert_eq!(summary(&events), [(UeventAction::Remove, "2-1", "")]);
+        assert!(parse_uevents(b"libudev\0SUBSYSTEM=usb\0").is_empty());
+    }
+}
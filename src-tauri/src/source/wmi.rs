@@ -0,0 +1,89 @@
+use super::{DeviceFilter, DeviceMap, DeviceSource, Opener};
+use crate::storage::{StorageProvider, WmiStorage};
+use crate::types::{Bus, StorageInfo, UsbDevice};
+use ::wmi::{COMLibrary, WMIConnection};
+
+/// Polls `Win32_PnPEntity` for everything enumerated under the filter's
+/// buses (`USB\`, `USBSTOR\`, `BTHENUM\`, ...).
+pub struct WmiSource {
+    wmi: WMIConnection,
+    filter: DeviceFilter,
+    query: String,
+}
+
+impl WmiSource {
+    /// Initializes COM on the calling thread and connects to WMI. The
+    /// connection is not `Send`, so create the source on the thread that
+    /// will use it.
+    pub fn new(filter: DeviceFilter) -> Result<Self, String> {
+        let com = COMLibrary::new().map_err(|e| format!("COM init failed: {}", e))?;
+        let wmi = WMIConnection::new(com).map_err(|e| format!("WMI connect failed: {}", e))?;
+        let query = build_query(&filter);
+        Ok(Self { wmi, filter, query })
+    }
+}
+
+/// Device ID prefixes that cover each bus. They overlap a little (`USB%`
+/// also matches `USB4\`), so results are filtered again by bus.
+fn prefixes(bus: Bus) -> &'static [&'static str] {
+    match bus {
+        Bus::Usb => &["USB"],
+        Bus::Thunderbolt => &["THUNDERBOLT", "USB4"],
+        Bus::Bluetooth => &["BTH"],
+        Bus::Pci => &["PCI"],
+        Bus::Hid => &["HID"],
+        Bus::Other => &[],
+    }
+}
+
+fn build_query(filter: &DeviceFilter) -> String {
+    let mut patterns: Vec<String> = filter
+        .buses
+        .iter()
+        .flat_map(|bus| prefixes(*bus))
+        .map(|p| p.to_string())
+        .chain(filter.enumerators.iter().cloned())
+        // Quotes can't be escaped in WQL; drop any rather than break out.
+        .map(|p| format!("DeviceID LIKE '{}%'", p.replace('\'', "")))
+        .collect();
+    patterns.dedup();
+    // An empty filter still needs a valid query; nothing passes `allows`.
+    if patterns.is_empty() {
+        patterns.push("DeviceID LIKE 'USB%'".to_string());
+    }
+    format!(
+        "SELECT Name, DeviceID, Description, Manufacturer, PNPClass, Service, CompatibleID \
+         FROM Win32_PnPEntity WHERE {}",
+        patterns.join(" OR ")
+    )
+}
+
+impl DeviceSource for WmiSource {
+    fn name(&self) -> &'static str {
+        "wmi"
+    }
+
+    fn enumerate(&mut self) -> Result<DeviceMap, String> {
+        let results: Vec<UsbDevice> = self
+            .wmi
+            .raw_query(&self.query)
+            .map_err(|e| format!("WMI query failed: {}", e))?;
+        Ok(results
+            .into_iter()
+            .filter(|d| self.filter.allows(d))
+            .filter_map(|d| Some((d.DeviceID.clone()?, d)))
+            .collect())
+    }
+
+    fn query_storage_info(&mut self, device_id: &str) -> Option<StorageInfo> {
+        WmiStorage::new(&self.wmi).query_storage_info(device_id)
+    }
+
+    /// The worker gets its own COM apartment and WMI connection.
+    fn storage_source(&self) -> Option<Opener> {
+        let filter = self.filter.clone();
+        Some(Box::new(move || {
+            Ok(Box::new(WmiSource::new(filter)?) as Box<dyn DeviceSource>)
+        }))
+    }
+}
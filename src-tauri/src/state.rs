@@ -1,4 +1,4 @@
-use crate::types::{AppSnapshot, DeviceEvent, DeviceSnapshot, KnownDevice, StorageInfo};
+use crate::types::{AppSnapshot, DeviceEvent, DeviceSnapshot, KnownDevice, Prefs, StorageInfo};
 use parking_lot::RwLock;
 use std::collections::HashMap;
 
@@ -8,20 +8,31 @@ pub struct AppState {
     pub known_devices: RwLock<HashMap<String, KnownDevice>>,
     pub storage_info: RwLock<HashMap<String, StorageInfo>>,
     pub error: RwLock<Option<String>>,
+    /// Drives to look up again, queued by commands for the monitor.
+    pub storage_refresh: RwLock<Vec<String>>,
     pub prefs_theme: RwLock<String>,
     pub prefs_tab: RwLock<String>,
+    pub prefs_expand_interfaces: RwLock<bool>,
+    /// Sequence number of the last event the user cleared from the log.
+    pub events_cleared_through: RwLock<u64>,
 }
 
 impl AppState {
-    pub fn new(theme: String, tab: String) -> Self {
+    /// `history` is what the journal had at startup; events the user
+    /// cleared before are left out.
+    pub fn new(prefs: Prefs, mut history: Vec<DeviceEvent>) -> Self {
+        history.retain(|e| e.seq > prefs.events_cleared_through);
         Self {
             devices: RwLock::new(Vec::new()),
-            events: RwLock::new(Vec::new()),
+            events: RwLock::new(history),
             known_devices: RwLock::new(HashMap::new()),
             storage_info: RwLock::new(HashMap::new()),
             error: RwLock::new(None),
-            prefs_theme: RwLock::new(theme),
-            prefs_tab: RwLock::new(tab),
+            storage_refresh: RwLock::new(Vec::new()),
+            prefs_theme: RwLock::new(prefs.theme),
+            prefs_tab: RwLock::new(prefs.active_tab),
+            prefs_expand_interfaces: RwLock::new(prefs.expand_interfaces),
+            events_cleared_through: RwLock::new(prefs.events_cleared_through),
         }
     }
 
@@ -0,0 +1,526 @@
+use super::partition::{link, read_table_file};
+use super::probe::probe_file;
+use super::{format_bytes, StorageProvider};
+use crate::instance_id::DeviceInstanceId;
+use crate::logging::log_to_file;
+use crate::types::{PartitionInfo, StorageInfo, VolumeInfo};
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// Sector size the kernel reports `size` attributes in, whatever the
+/// device's own block size.
+const SECTOR: u64 = 512;
+
+/// Finds a USB device's disks under `<sys>/block`, reads them from sysfs,
+/// probes each partition's superblock for its filesystem, and looks the
+/// partitions up in `mountinfo` for mount points and `statvfs` for free
+/// space. With no drive letters on Linux, volumes go by mount point.
+///
+/// All three locations can be pointed at fake trees, the same way
+/// `SysfsSource` takes a root.
+pub struct BlockStorage {
+    sys_root: PathBuf,
+    dev_root: PathBuf,
+    mountinfo: PathBuf,
+}
+
+impl BlockStorage {
+    pub fn with_roots(
+        sys_root: impl Into<PathBuf>,
+        dev_root: impl Into<PathBuf>,
+        mountinfo: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            sys_root: sys_root.into(),
+            dev_root: dev_root.into(),
+            mountinfo: mountinfo.into(),
+        }
+    }
+
+    /// Block devices (`sdb`, ...) whose sysfs path runs through the USB
+    /// device `id`, with their resolved sysfs directories.
+    fn disks_for(&self, id: &DeviceInstanceId) -> Vec<(String, PathBuf)> {
+        let Ok(entries) = fs::read_dir(self.sys_root.join("block")) else {
+            return Vec::new();
+        };
+        let mut disks: Vec<(String, PathBuf)> = entries
+            .flatten()
+            .filter_map(|e| {
+                let name = e.file_name().to_str()?.to_string();
+                let dir = fs::canonicalize(e.path()).ok()?;
+                let owner = dir.ancestors().find(|a| a.join("idVendor").is_file())?;
+                is_device(owner, id).then_some((name, dir))
+            })
+            .collect();
+        disks.sort();
+        disks
+    }
+
+    /// One disk: what the SCSI layer says about it, its partition table,
+    /// and its volumes (the whole disk is one, for sticks formatted
+    /// without a partition table).
+    fn read_disk(&self, name: &str, dir: &Path, serial: &str, mounts: &[Mount]) -> StorageInfo {
+        let scsi = dir.join("device");
+        let model = [read_attr(&scsi, "vendor"), read_attr(&scsi, "model")]
+            .into_iter()
+            .flatten()
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let removable = read_attr(dir, "removable").is_some_and(|r| r == "1");
+
+        let mut part_dirs = partitions(dir);
+        // Reading the table takes the same permission as probing; sysfs
+        // still has the offsets when that fails.
+        let (partition_table, mut parts) = match read_table_file(&self.dev_root.join(name)) {
+            Some(table) => (table.scheme.to_string(), table.partitions),
+            None => (
+                String::new(),
+                part_dirs
+                    .iter()
+                    .filter_map(|p| sysfs_partition(p))
+                    .collect(),
+            ),
+        };
+        if part_dirs.is_empty() {
+            part_dirs.push(dir.to_path_buf());
+        }
+        let volumes: Vec<(u64, VolumeInfo)> = part_dirs
+            .iter()
+            .filter_map(|part| {
+                let start = read_attr(part, "start")
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(0);
+                Some((start * SECTOR, self.read_volume(part, mounts)?))
+            })
+            .collect();
+        link(&mut parts, &volumes);
+
+        StorageInfo {
+            model,
+            serial_number: serial.to_string(),
+            total_bytes: sectors(dir).unwrap_or(0) * SECTOR,
+            interface_type: "USB".to_string(),
+            media_type: if removable {
+                "Removable Media"
+            } else {
+                "Fixed hard disk media"
+            }
+            .to_string(),
+            removable,
+            rotational: read_attr(&dir.join("queue"), "rotational").is_some_and(|r| r == "1"),
+            device_path: self.dev_root.join(name).to_string_lossy().into_owned(),
+            firmware: read_attr(&scsi, "rev").unwrap_or_default(),
+            partition_count: parts.len() as u32,
+            status: match read_attr(&scsi, "state").as_deref() {
+                Some("running") | None => "OK".to_string(),
+                Some(state) => state.to_string(),
+            },
+            volumes: volumes.into_iter().map(|(_, vol)| vol).collect(),
+            partition_table,
+            partitions: parts,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// A partition as a volume: what its superblock says, plus the mount
+    /// point and free space if it's mounted. Partitions that are neither
+    /// mounted nor hold a filesystem the probe knows are left out.
+    fn read_volume(&self, part: &Path, mounts: &[Mount]) -> Option<VolumeInfo> {
+        let dev = read_attr(part, "dev")?;
+        let mount = mounts.iter().find(|m| m.dev == dev);
+        let fs = part
+            .file_name()
+            .and_then(|name| probe_file(&self.dev_root.join(name), 0));
+        if mount.is_none() && fs.is_none() {
+            return None;
+        }
+        let size = fs
+            .as_ref()
+            .map(|fs| fs.size_bytes)
+            .filter(|s| *s > 0)
+            .unwrap_or_else(|| sectors(part).unwrap_or(0) * SECTOR);
+        let (total, free) = mount
+            .and_then(|m| space(&m.mount_point))
+            .unwrap_or((size, 0));
+        let fs = fs.unwrap_or_default();
+        Some(VolumeInfo {
+            drive_letter: mount
+                .map(|m| m.mount_point.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            volume_name: fs.label,
+            total_bytes: total,
+            free_bytes: free,
+            // mountinfo says `fuseblk` for ntfs-3g; the superblock knows.
+            file_system: match (fs.kind, mount) {
+                ("", Some(m)) => m.fs_type.clone(),
+                (kind, _) => kind.to_string(),
+            },
+            volume_serial: fs.uuid,
+        })
+    }
+}
+
+impl StorageProvider for BlockStorage {
+    fn query_storage_info(&mut self, device_id: &str) -> Option<StorageInfo> {
+        let id = DeviceInstanceId::parse(device_id);
+        let disks = self.disks_for(&id);
+        if disks.is_empty() {
+            log_to_file(&format!("ENRICH FAIL: no block device for {}", device_id));
+            return None;
+        }
+        let mounts = fs::read_to_string(&self.mountinfo)
+            .map(|text| parse_mountinfo(&text))
+            .unwrap_or_default();
+        let serial = id.serial().unwrap_or_default();
+
+        // Card readers have a disk per slot; empty slots read as size 0.
+        // The first loaded one describes the drive, and every mounted
+        // volume is listed.
+        let mut info: Option<StorageInfo> = None;
+        for (name, dir) in &disks {
+            if sectors(dir).unwrap_or(0) == 0 {
+                continue;
+            }
+            let disk = self.read_disk(name, dir, serial, &mounts);
+            log_to_file(&format!(
+                "ENRICH: {} is {} ({}), {} volumes",
+                device_id,
+                disk.device_path,
+                format_bytes(disk.total_bytes),
+                disk.volumes.len()
+            ));
+            match &mut info {
+                Some(info) => info.volumes.extend(disk.volumes),
+                None => info = Some(disk),
+            }
+        }
+        if info.is_none() {
+            log_to_file(&format!("ENRICH FAIL: no media in {}", device_id));
+        }
+        info
+    }
+}
+
+// ── sysfs ──────────────────────────────────────────────────────
+
+fn read_attr(dir: &Path, attr: &str) -> Option<String> {
+    fs::read_to_string(dir.join(attr))
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+fn sectors(dir: &Path) -> Option<u64> {
+    read_attr(dir, "size")?.parse().ok()
+}
+
+/// Whether the USB device directory `dir` is the one `id` was built
+/// from: same VID/PID, and the same serial, or the same port when the ID
+/// is port-derived.
+fn is_device(dir: &Path, id: &DeviceInstanceId) -> bool {
+    let hex = |attr| read_attr(dir, attr).and_then(|v| u16::from_str_radix(&v, 16).ok());
+    if hex("idVendor") != id.vid || hex("idProduct") != id.pid {
+        return false;
+    }
+    match id.serial() {
+        Some(serial) => read_attr(dir, "serial").is_some_and(|s| s == serial),
+        None => dir.file_name().is_some_and(|n| n == id.instance.as_str()),
+    }
+}
+
+/// A partition as sysfs describes it: number, offset and size, but no
+/// type or name.
+fn sysfs_partition(part: &Path) -> Option<PartitionInfo> {
+    Some(PartitionInfo {
+        number: read_attr(part, "partition")?.parse().ok()?,
+        start_bytes: read_attr(part, "start")?.parse::<u64>().ok()? * SECTOR,
+        size_bytes: sectors(part)? * SECTOR,
+        ..Default::default()
+    })
+}
+
+/// Partition directories of a disk (`sdb1`, `sdb2`, ...), in order.
+fn partitions(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut parts: Vec<(u32, PathBuf)> = entries
+        .flatten()
+        .filter_map(|e| {
+            let path = e.path();
+            let n = read_attr(&path, "partition")?.parse().ok()?;
+            Some((n, path))
+        })
+        .collect();
+    parts.sort();
+    parts.into_iter().map(|(_, path)| path).collect()
+}
+
+// ── Mounts ─────────────────────────────────────────────────────
+
+struct Mount {
+    /// `major:minor`, as in a block device's `dev` attribute.
+    dev: String,
+    mount_point: PathBuf,
+    fs_type: String,
+}
+
+/// `/proc/self/mountinfo` lines:
+///
+/// ```text
+/// 36 35 8:17 / /media/usb\040stick rw,nosuid shared:1 - vfat /dev/sdb1 rw
+/// ```
+///
+/// Optional fields run up to the lone `-`; the filesystem type follows it.
+fn parse_mountinfo(text: &str) -> Vec<Mount> {
+    text.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(' ').collect();
+            let sep = fields.iter().skip(6).position(|f| *f == "-")? + 6;
+            Some(Mount {
+                dev: fields.get(2)?.to_string(),
+                mount_point: PathBuf::from(unescape(fields.get(4)?)),
+                fs_type: fields.get(sep + 1)?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Undoes the `\040`-style octal escapes mountinfo uses for spaces,
+/// tabs, newlines and backslashes.
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes
+            .get(i + 1..i + 4)
+            .filter(|_| bytes[i] == b'\\')
+            .and_then(|d| std::str::from_utf8(d).ok())
+            .and_then(|d| u8::from_str_radix(d, 8).ok());
+        match octal {
+            Some(b) => {
+                out.push(b);
+                i += 4;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Total and available bytes of the filesystem mounted at `path`.
+fn space(path: &Path) -> Option<(u64, u64)> {
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut st: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut st) } != 0 {
+        return None;
+    }
+    let frsize = st.f_frsize as u64;
+    Some((st.f_blocks as u64 * frsize, st.f_bavail as u64 * frsize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use tempfile::TempDir;
+
+    const STICK: &str = r"USB\VID_0781&PID_5581\4C530001";
+
+    /// Writes `attrs` as files in `dir`.
+    fn attrs(dir: &Path, attrs: &[(&str, &str)]) {
+        fs::create_dir_all(dir).unwrap();
+        for (attr, value) in attrs {
+            fs::write(dir.join(attr), format!("{}\n", value)).unwrap();
+        }
+    }
+
+    /// A FAT16 boot sector labelled `label`, 20000 sectors long.
+    fn fat16(label: &str) -> Vec<u8> {
+        let mut b = vec![0u8; 512];
+        b[0] = 0xEB;
+        b[11..13].copy_from_slice(&512u16.to_le_bytes());
+        b[13] = 1;
+        b[14..16].copy_from_slice(&1u16.to_le_bytes());
+        b[16] = 2;
+        b[17..19].copy_from_slice(&512u16.to_le_bytes());
+        b[19..21].copy_from_slice(&20000u16.to_le_bytes());
+        b[22..24].copy_from_slice(&80u16.to_le_bytes());
+        b[38] = 0x29;
+        b[39..43].copy_from_slice(&0x1A2B_3C4Du32.to_le_bytes());
+        b[43..54].copy_from_slice(format!("{:<11}", label).as_bytes());
+        b[510] = 0x55;
+        b[511] = 0xAA;
+        b
+    }
+
+    /// A flash drive as disk `sdb` with three partitions: `sdb1` holds a
+    /// FAT16 filesystem and isn't mounted, `sdb2` is mounted on a path
+    /// with a space in it, and `sdb3` is neither. The USB device and the
+    /// mount point live in the same temporary directory.
+    fn stick() -> (TempDir, BlockStorage) {
+        let root = tempfile::tempdir().unwrap();
+        let sys = root.path().join("sys");
+        let usb = sys.join("devices/pci0000:00/0000:00:14.0/usb1/1-2");
+        attrs(
+            &usb,
+            &[
+                ("idVendor", "0781"),
+                ("idProduct", "5581"),
+                ("serial", "4C530001"),
+            ],
+        );
+        let disk = usb.join("1-2:1.0/host0/target0:0:0/0:0:0:0/block/sdb");
+        attrs(
+            &disk,
+            &[("size", "60088320"), ("removable", "1"), ("dev", "8:16")],
+        );
+        attrs(&disk.join("queue"), &[("rotational", "0")]);
+        attrs(
+            &disk.join("device"),
+            &[
+                ("vendor", "SanDisk"),
+                ("model", "Ultra"),
+                ("rev", "1.00"),
+                ("state", "running"),
+            ],
+        );
+        for (n, start, size) in [
+            (1, "2048", "20000"),
+            (2, "22048", "40960"),
+            (3, "63008", "2048"),
+        ] {
+            attrs(
+                &disk.join(format!("sdb{}", n)),
+                &[
+                    ("partition", &n.to_string()),
+                    ("start", start),
+                    ("size", size),
+                    ("dev", &format!("8:{}", 16 + n)),
+                ],
+            );
+        }
+        fs::create_dir_all(sys.join("block")).unwrap();
+        symlink(&disk, sys.join("block/sdb")).unwrap();
+        // Another disk, not on the stick.
+        let sda = sys.join("devices/pci0000:00/0000:00:17.0/ata1/host1/block/sda");
+        attrs(&sda, &[("size", "1000215216"), ("dev", "8:0")]);
+        symlink(&sda, sys.join("block/sda")).unwrap();
+
+        let dev = root.path().join("dev");
+        fs::create_dir_all(&dev).unwrap();
+        fs::write(dev.join("sdb1"), fat16("PHOTOS")).unwrap();
+
+        let media = root.path().join("media/usb stick");
+        fs::create_dir_all(&media).unwrap();
+        let escaped = media.to_str().unwrap().replace(' ', r"\040");
+        let mountinfo = root.path().join("mountinfo");
+        fs::write(
+            &mountinfo,
+            format!(
+                "22 1 8:2 / / rw,relatime shared:1 - ext4 /dev/sda2 rw\n\
+                 36 22 8:18 / {} rw,nosuid,nodev shared:90 - exfat /dev/sdb2 rw\n",
+                escaped
+            ),
+        )
+        .unwrap();
+
+        let storage = BlockStorage::with_roots(sys, dev, mountinfo);
+        (root, storage)
+    }
+
+    fn read(storage: &BlockStorage) -> StorageInfo {
+        let disks = storage.disks_for(&DeviceInstanceId::parse(STICK));
+        let names: Vec<&str> = disks.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["sdb"]);
+        let mounts = parse_mountinfo(&fs::read_to_string(&storage.mountinfo).unwrap());
+        let (name, dir) = &disks[0];
+        storage.read_disk(name, dir, "4C530001", &mounts)
+    }
+
+    #[test]
+    fn finds_the_disks_of_a_usb_device() {
+        let (_root, storage) = stick();
+        let other = DeviceInstanceId::parse(r"USB\VID_0781&PID_5581\OTHER");
+        assert!(storage.disks_for(&other).is_empty());
+
+        let info = read(&storage);
+        assert_eq!(info.model, "SanDisk Ultra");
+        assert_eq!(info.total_bytes, 60088320 * 512);
+        assert!(info.removable && !info.rotational);
+        assert_eq!(info.firmware, "1.00");
+        assert_eq!(info.status, "OK");
+        assert!(info.device_path.ends_with("dev/sdb"));
+    }
+
+    #[test]
+    fn partitions_map_to_their_mounts() {
+        let (root, storage) = stick();
+        let info = read(&storage);
+        let media = root.path().join("media/usb stick");
+
+        // No readable table, so the partitions come from sysfs.
+        assert_eq!(info.partition_table, "");
+        let parts: Vec<(u32, u64, &str, &str, &str)> = info
+            .partitions
+            .iter()
+            .map(|p| {
+                (
+                    p.number,
+                    p.start_bytes,
+                    p.file_system.as_str(),
+                    p.label.as_str(),
+                    p.drive_letter.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            parts,
+            [
+                (1, 2048 * 512, "FAT16", "PHOTOS", ""),
+                (2, 22048 * 512, "exfat", "", media.to_str().unwrap()),
+                (3, 63008 * 512, "", "", ""),
+            ]
+        );
+
+        // The partition that's neither mounted nor formatted isn't a volume.
+        let [photos, mounted] = &info.volumes[..] else {
+            panic!("expected two volumes: {:?}", info.volumes);
+        };
+        assert_eq!(photos.drive_letter, "");
+        assert_eq!(photos.volume_serial, "1A2B-3C4D");
+        assert_eq!((photos.total_bytes, photos.free_bytes), (20000 * 512, 0));
+        assert_eq!(mounted.drive_letter, media.to_str().unwrap());
+        assert_eq!(mounted.file_system, "exfat");
+        // statvfs found the directory behind the escaped path.
+        assert!(mounted.total_bytes > 0);
+    }
+
+    #[test]
+    fn unescapes_mountinfo_paths() {
+        let mounts = parse_mountinfo(
+            "36 35 8:17 / /media/usb\\040stick rw,nosuid shared:1 - vfat /dev/sdb1 rw\n\
+             37 35 8:33 / /mnt/tab\\011and\\134slash rw - ext4 /dev/sdc1 rw\n\
+             38 35 8:49 / /mnt/odd\\04 rw master:2 propagate_from:1 - btrfs /dev/sdd1 rw\n\
+             not a mountinfo line\n",
+        );
+        let parsed: Vec<(&str, &Path, &str)> = mounts
+            .iter()
+            .map(|m| (m.dev.as_str(), m.mount_point.as_path(), m.fs_type.as_str()))
+            .collect();
+        assert_eq!(
+            parsed,
+            [
+                ("8:17", Path::new("/media/usb stick"), "vfat"),
+                ("8:33", Path::new("/mnt/tab\tand\\slash"), "ext4"),
+                ("8:49", Path::new("/mnt/odd\\04"), "btrfs"),
+            ]
+        );
+    }
+}
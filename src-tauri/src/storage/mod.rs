@@ -0,0 +1,40 @@
+#[cfg(target_os = "linux")]
+mod block;
+#[cfg(any(windows, target_os = "linux"))]
+mod partition;
+#[cfg(any(windows, target_os = "linux"))]
+mod probe;
+#[cfg(windows)]
+mod wmi;
+
+#[cfg(windows)]
+pub use self::wmi::WmiStorage;
+#[cfg(target_os = "linux")]
+pub use block::BlockStorage;
+
+use crate::types::StorageInfo;
+
+/// Looks up drive and volume details for a storage device. Native
+/// sources hold one: WMI and PowerShell on Windows, sysfs, mountinfo and
+/// `statvfs` on Linux. Both fill the same `StorageInfo`.
+pub trait StorageProvider {
+    fn query_storage_info(&mut self, device_id: &str) -> Option<StorageInfo>;
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const KB: u64 = 1024;
+    const MB: u64 = 1024 * KB;
+    const GB: u64 = 1024 * MB;
+    const TB: u64 = 1024 * GB;
+    if bytes >= TB {
+        format!("{:.2} TB", bytes as f64 / TB as f64)
+    } else if bytes >= GB {
+        format!("{:.2} GB", bytes as f64 / GB as f64)
+    } else if bytes >= MB {
+        format!("{:.1} MB", bytes as f64 / MB as f64)
+    } else if bytes >= KB {
+        format!("{:.0} KB", bytes as f64 / KB as f64)
+    } else {
+        format!("{} B", bytes)
+    }
+}
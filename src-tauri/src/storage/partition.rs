@@ -0,0 +1,502 @@
+use super::probe::{le32, le64, probe, read_at, utf16};
+use crate::types::{PartitionFlag, PartitionInfo, VolumeInfo};
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+/// Logical sector sizes GPT is looked for at. MBR always assumes 512.
+const SECTOR_SIZES: [u64; 2] = [512, 4096];
+
+/// Upper bounds, so a corrupt table can't make us read forever.
+const MAX_GPT_ENTRIES: u32 = 1024;
+const MAX_LOGICAL: u32 = 128;
+
+/// A disk's partition table.
+pub struct Table {
+    /// "gpt" or "mbr".
+    pub scheme: &'static str,
+    pub partitions: Vec<PartitionInfo>,
+}
+
+/// Reads the partition table of a whole-disk device or image. None when
+/// it can't be opened or has no table, which includes sticks formatted
+/// as one big filesystem.
+pub fn read_table_file(path: &Path) -> Option<Table> {
+    let mut file = File::open(path).ok()?;
+    read_table(&mut file)
+}
+
+pub fn read_table<R: Read + Seek>(r: &mut R) -> Option<Table> {
+    let mbr = read_at(r, 0, 512)?;
+    if mbr[510..512] != [0x55, 0xAA] {
+        return None;
+    }
+    let entries: Vec<&[u8]> = mbr[446..510].chunks_exact(16).collect();
+    if entries.iter().any(|e| e[4] == 0xEE) {
+        if let Some(table) = gpt(r) {
+            return Some(table);
+        }
+    }
+    // A FAT or NTFS boot sector carries the same signature, with code
+    // where the table would be. Hybrid ISOs do have a real table.
+    let superfloppy = probe(r, 0).is_some_and(|fs| fs.kind != "ISO9660");
+    if superfloppy || entries.iter().any(|e| !matches!(e[0], 0x00 | 0x80)) {
+        return None;
+    }
+    mbr_table(r, &entries)
+}
+
+/// A partition with its type name and the flags its type implies.
+/// Callers add flags that come from elsewhere (MBR active, GPT
+/// attributes, Windows' own idea of hidden).
+pub fn partition(number: u32, type_id: String, start_bytes: u64, size_bytes: u64) -> PartitionInfo {
+    let (type_name, flags) = describe(&type_id);
+    PartitionInfo {
+        number,
+        type_name: type_name.to_string(),
+        type_id,
+        start_bytes,
+        size_bytes,
+        flags: flags.to_vec(),
+        ..Default::default()
+    }
+}
+
+/// Fills in each partition's filesystem, label and mount point from the
+/// volume starting at the same offset.
+pub fn link(partitions: &mut [PartitionInfo], volumes: &[(u64, VolumeInfo)]) {
+    for part in partitions {
+        let Some((_, vol)) = volumes.iter().find(|(start, _)| *start == part.start_bytes) else {
+            continue;
+        };
+        part.file_system = vol.file_system.clone();
+        part.label = vol.volume_name.clone();
+        part.drive_letter = vol.drive_letter.clone();
+    }
+}
+
+fn add_flag(part: &mut PartitionInfo, flag: PartitionFlag) {
+    if !part.flags.contains(&flag) {
+        part.flags.push(flag);
+    }
+}
+
+// ── MBR ────────────────────────────────────────────────────────
+
+fn mbr_table<R: Read + Seek>(r: &mut R, entries: &[&[u8]]) -> Option<Table> {
+    let mut partitions = Vec::new();
+    let mut extended = None;
+    for (i, e) in entries.iter().enumerate() {
+        let kind = e[4];
+        let start = le32(e, 8) as u64;
+        let len = le32(e, 12) as u64;
+        if kind == 0 || len == 0 {
+            continue;
+        }
+        if is_extended(kind) {
+            extended.get_or_insert(start);
+        }
+        let mut part = partition(
+            i as u32 + 1,
+            format!("0x{:02x}", kind),
+            start * 512,
+            len * 512,
+        );
+        if e[0] == 0x80 {
+            add_flag(&mut part, PartitionFlag::Bootable);
+        }
+        partitions.push(part);
+    }
+    if partitions.is_empty() {
+        return None;
+    }
+    if let Some(base) = extended {
+        partitions.extend(logical_partitions(r, base));
+    }
+    Some(Table {
+        scheme: "mbr",
+        partitions,
+    })
+}
+
+/// Walks the chain of extended boot records. Each holds one logical
+/// partition, relative to itself, and a link to the next, relative to
+/// the start of the extended partition.
+fn logical_partitions<R: Read + Seek>(r: &mut R, base: u64) -> Vec<PartitionInfo> {
+    let mut partitions = Vec::new();
+    let mut ebr = base;
+    for number in 5..5 + MAX_LOGICAL {
+        let Some(b) = read_at(r, ebr * 512, 512) else {
+            break;
+        };
+        if b[510..512] != [0x55, 0xAA] {
+            break;
+        }
+        let (this, next) = (&b[446..462], &b[462..478]);
+        if this[4] != 0 && le32(this, 12) != 0 {
+            let start = ebr + le32(this, 8) as u64;
+            partitions.push(partition(
+                number,
+                format!("0x{:02x}", this[4]),
+                start * 512,
+                le32(this, 12) as u64 * 512,
+            ));
+        }
+        let link = le32(next, 8) as u64;
+        if !is_extended(next[4]) || link == 0 {
+            break;
+        }
+        ebr = base + link;
+    }
+    partitions
+}
+
+fn is_extended(kind: u8) -> bool {
+    matches!(kind, 0x05 | 0x0F | 0x85)
+}
+
+// ── GPT ────────────────────────────────────────────────────────
+
+/// Attribute bits the GPT spec and Microsoft define.
+const GPT_ATTR_READ_ONLY: u64 = 1 << 60;
+const GPT_ATTR_HIDDEN: u64 = 1 << 62;
+
+/// Entries are 128 bytes, or a larger multiple of 8. Nothing uses more
+/// than 512.
+const GPT_ENTRY_SIZES: std::ops::RangeInclusive<usize> = 128..=512;
+
+/// A header or entry pointing past what a `u64` can address means the
+/// table is corrupt, and none of it is used.
+fn gpt<R: Read + Seek>(r: &mut R) -> Option<Table> {
+    let (sector, header) = SECTOR_SIZES.iter().find_map(|&sector| {
+        let header = read_at(r, sector, 92)?;
+        (&header[..8] == b"EFI PART").then_some((sector, header))
+    })?;
+    let entries_lba = le64(&header, 72);
+    let count = le32(&header, 80).min(MAX_GPT_ENTRIES);
+    let size = le32(&header, 84) as usize;
+    if !GPT_ENTRY_SIZES.contains(&size) || !size.is_multiple_of(8) {
+        return None;
+    }
+    let table = read_at(r, entries_lba.checked_mul(sector)?, count as usize * size)?;
+
+    let mut partitions = Vec::new();
+    for (i, e) in table.chunks_exact(size).enumerate() {
+        if e[..16].iter().all(|b| *b == 0) {
+            continue;
+        }
+        let first = le64(e, 32);
+        let last = le64(e, 40);
+        let attrs = le64(e, 48);
+        let mut part = partition(
+            i as u32 + 1,
+            format_guid(&e[..16]),
+            first.checked_mul(sector)?,
+            last.checked_add(1)?
+                .saturating_sub(first)
+                .checked_mul(sector)?,
+        );
+        part.name = utf16(&e[56..128]);
+        if attrs & GPT_ATTR_HIDDEN != 0 {
+            add_flag(&mut part, PartitionFlag::Hidden);
+        }
+        if attrs & GPT_ATTR_READ_ONLY != 0 {
+            add_flag(&mut part, PartitionFlag::ReadOnly);
+        }
+        partitions.push(part);
+    }
+    Some(Table {
+        scheme: "gpt",
+        partitions,
+    })
+}
+
+/// GUIDs are stored with the first three fields little-endian.
+fn format_guid(b: &[u8]) -> String {
+    format!(
+        "{:08X}-{:04X}-{:04X}-{}-{}",
+        le32(b, 0),
+        u16::from_le_bytes([b[4], b[5]]),
+        u16::from_le_bytes([b[6], b[7]]),
+        hex(&b[8..10]),
+        hex(&b[10..16])
+    )
+}
+
+fn hex(b: &[u8]) -> String {
+    b.iter().map(|x| format!("{:02X}", x)).collect()
+}
+
+// ── Partition types ────────────────────────────────────────────
+
+/// Name and implied flags of a GPT type GUID or MBR type byte.
+fn describe(type_id: &str) -> (&'static str, &'static [PartitionFlag]) {
+    use PartitionFlag::*;
+    match type_id.to_ascii_uppercase().as_str() {
+        "C12A7328-F81F-11D2-BA4B-00A0C93EC93B" | "0XEF" => ("EFI System", &[Esp]),
+        "E3C9E316-0B5C-4DB8-817D-F92DF00215AE" => ("Microsoft reserved", &[Hidden]),
+        "EBD0A0A2-B9E5-4433-87C0-68B6B72699C7" => ("Microsoft basic data", &[]),
+        "DE94BBA4-06D1-4D40-A16A-BFD50179D6AC" => ("Windows recovery", &[Recovery, Hidden]),
+        "0FC63DAF-8483-4772-8E79-3D69D8477DE4" | "0X83" => ("Linux filesystem", &[]),
+        "4F68BCE3-E8CD-4DB1-96E7-FBCAF984B709" => ("Linux root (x86-64)", &[]),
+        "933AC7E1-2EB4-4F13-B844-0E14E2AEF915" => ("Linux home", &[]),
+        "0657FD6D-A4AB-43C4-84E5-0933C84B4F4F" | "0X82" => ("Linux swap", &[]),
+        "E6D6D379-F507-44C2-A23C-238F2A3DF928" | "0X8E" => ("Linux LVM", &[]),
+        "A19D880F-05FC-4D3B-A006-743F0F84911E" | "0XFD" => ("Linux RAID", &[]),
+        "21686148-6449-6E6F-744E-656564454649" => ("BIOS boot", &[]),
+        "48465300-0000-11AA-AA11-00306543ECAC" | "0XAF" => ("Apple HFS+", &[]),
+        "7C3457EF-0000-11AA-AA11-00306543ECAC" => ("Apple APFS", &[]),
+        "426F6F74-0000-11AA-AA11-00306543ECAC" => ("Apple boot", &[Recovery]),
+        "0X01" => ("FAT12", &[]),
+        "0X04" | "0X06" | "0X0E" => ("FAT16", &[]),
+        "0X07" => ("NTFS/exFAT", &[]),
+        "0X0B" | "0X0C" => ("FAT32", &[]),
+        "0X05" | "0X0F" | "0X85" => ("Extended", &[]),
+        "0X11" | "0X14" | "0X16" | "0X1E" => ("Hidden FAT16", &[Hidden]),
+        "0X1B" | "0X1C" => ("Hidden FAT32", &[Hidden]),
+        "0X17" => ("Hidden NTFS", &[Hidden]),
+        "0X27" => ("Windows recovery", &[Recovery, Hidden]),
+        "0XEE" => ("GPT protective", &[]),
+        _ => ("Unknown", &[]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const LINUX: &str = "0FC63DAF-8483-4772-8E79-3D69D8477DE4";
+    const ESP: &str = "C12A7328-F81F-11D2-BA4B-00A0C93EC93B";
+
+    fn put(b: &mut [u8], at: usize, bytes: &[u8]) {
+        b[at..at + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn table(image: &[u8]) -> Option<Table> {
+        read_table(&mut Cursor::new(image))
+    }
+
+    fn layout(table: &Table) -> Vec<(u32, &str, u64, u64)> {
+        table
+            .partitions
+            .iter()
+            .map(|p| (p.number, p.type_name.as_str(), p.start_bytes, p.size_bytes))
+            .collect()
+    }
+
+    /// Writes an MBR slot: status, type, first sector and length.
+    fn mbr_entry(b: &mut [u8], slot: usize, status: u8, kind: u8, start: u32, len: u32) {
+        let e = &mut b[446 + slot * 16..462 + slot * 16];
+        e[0] = status;
+        e[4] = kind;
+        put(e, 8, &start.to_le_bytes());
+        put(e, 12, &len.to_le_bytes());
+    }
+
+    fn signed(b: &mut [u8]) {
+        put(b, 510, &[0x55, 0xAA]);
+    }
+
+    /// The GUID as stored on disk, first three fields little-endian.
+    fn guid_bytes(guid: &str) -> Vec<u8> {
+        let hex: String = guid.chars().filter(|c| *c != '-').collect();
+        let mut b: Vec<u8> = (0..16)
+            .map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap())
+            .collect();
+        b[..4].reverse();
+        b[4..6].reverse();
+        b[6..8].reverse();
+        b
+    }
+
+    /// A protective MBR, a GPT header at LBA 1 and 128 entries of `size`
+    /// bytes from LBA 2.
+    fn gpt_image(sector: usize, size: u32, entries: &[(&str, u64, u64, u64, &str)]) -> Vec<u8> {
+        let mut image = vec![0u8; 2 * sector + 128 * size as usize];
+        mbr_entry(&mut image, 0, 0, 0xEE, 1, u32::MAX);
+        signed(&mut image);
+        let header = &mut image[sector..];
+        put(header, 0, b"EFI PART");
+        put(header, 72, &2u64.to_le_bytes());
+        put(header, 80, &128u32.to_le_bytes());
+        put(header, 84, &size.to_le_bytes());
+        for (i, (guid, first, last, attrs, name)) in entries.iter().enumerate() {
+            let e = &mut image[2 * sector + i * size as usize..];
+            put(e, 0, &guid_bytes(guid));
+            put(e, 32, &first.to_le_bytes());
+            put(e, 40, &last.to_le_bytes());
+            put(e, 48, &attrs.to_le_bytes());
+            let name: Vec<u8> = name.encode_utf16().flat_map(u16::to_le_bytes).collect();
+            put(e, 56, &name);
+        }
+        image
+    }
+
+    #[test]
+    fn mbr_primaries_and_logicals() {
+        let mut image = vec![0u8; 4000 * 512];
+        mbr_entry(&mut image, 0, 0x80, 0x0C, 2048, 1000);
+        mbr_entry(&mut image, 1, 0, 0x05, 1000 + 2048, 1500);
+        signed(&mut image);
+        // Extended boot records: each partition is relative to its EBR,
+        // each link to the start of the extended partition.
+        let ebr = 3048 * 512;
+        mbr_entry(&mut image[ebr..], 0, 0, 0x83, 63, 500);
+        mbr_entry(&mut image[ebr..], 1, 0, 0x05, 600, 800);
+        signed(&mut image[ebr..]);
+        let ebr = (3048 + 600) * 512;
+        mbr_entry(&mut image[ebr..], 0, 0, 0x82, 63, 700);
+        signed(&mut image[ebr..]);
+
+        let table = table(&image).unwrap();
+        assert_eq!(table.scheme, "mbr");
+        assert_eq!(
+            layout(&table),
+            [
+                (1, "FAT32", 2048 * 512, 1000 * 512),
+                (2, "Extended", 3048 * 512, 1500 * 512),
+                (5, "Linux filesystem", (3048 + 63) * 512, 500 * 512),
+                (6, "Linux swap", (3648 + 63) * 512, 700 * 512),
+            ]
+        );
+        assert_eq!(table.partitions[0].flags, [PartitionFlag::Bootable]);
+    }
+
+    #[test]
+    fn no_table() {
+        assert!(table(&[0u8; 512]).is_none());
+        // A stick formatted without a table: a FAT boot sector carries
+        // the same signature.
+        let mut boot = vec![0u8; 512];
+        boot[0] = 0xEB;
+        put(&mut boot, 11, &512u16.to_le_bytes());
+        boot[13] = 1;
+        put(&mut boot, 14, &1u16.to_le_bytes());
+        boot[16] = 2;
+        put(&mut boot, 17, &512u16.to_le_bytes());
+        put(&mut boot, 19, &20000u16.to_le_bytes());
+        put(&mut boot, 22, &80u16.to_le_bytes());
+        signed(&mut boot);
+        assert!(table(&boot).is_none());
+        // Status bytes that aren't 0x00 or 0x80 mean it's not a table.
+        let mut image = vec![0u8; 512];
+        mbr_entry(&mut image, 0, 0x12, 0x0C, 2048, 1000);
+        signed(&mut image);
+        assert!(table(&image).is_none());
+    }
+
+    #[test]
+    fn gpt_partitions() {
+        let image = gpt_image(
+            512,
+            128,
+            &[
+                (ESP, 2048, 206_847, 0, "EFI system partition"),
+                ("00000000-0000-0000-0000-000000000000", 0, 0, 0, ""),
+                (
+                    LINUX,
+                    206_848,
+                    409_599,
+                    GPT_ATTR_HIDDEN | GPT_ATTR_READ_ONLY,
+                    "data",
+                ),
+            ],
+        );
+        let table = table(&image).unwrap();
+        assert_eq!(table.scheme, "gpt");
+        assert_eq!(
+            layout(&table),
+            [
+                (1, "EFI System", 2048 * 512, 204_800 * 512),
+                (3, "Linux filesystem", 206_848 * 512, 202_752 * 512),
+            ]
+        );
+        let (esp, data) = (&table.partitions[0], &table.partitions[1]);
+        assert_eq!(esp.type_id, ESP);
+        assert_eq!(esp.name, "EFI system partition");
+        assert_eq!(esp.flags, [PartitionFlag::Esp]);
+        assert_eq!(data.name, "data");
+        assert_eq!(data.flags, [PartitionFlag::Hidden, PartitionFlag::ReadOnly]);
+    }
+
+    #[test]
+    fn gpt_on_4k_sectors_with_large_entries() {
+        let image = gpt_image(4096, 256, &[(LINUX, 256, 511, 0, "big")]);
+        let table = table(&image).unwrap();
+        assert_eq!(table.scheme, "gpt");
+        assert_eq!(
+            layout(&table),
+            [(1, "Linux filesystem", 256 * 4096, 256 * 4096)]
+        );
+        assert_eq!(table.partitions[0].name, "big");
+    }
+
+    /// A GPT that can't be used leaves the protective MBR entry.
+    fn protective_only(image: &[u8]) {
+        let table = table(image).unwrap();
+        assert_eq!(table.scheme, "mbr");
+        assert_eq!(
+            layout(&table),
+            [(1, "GPT protective", 512, u32::MAX as u64 * 512)]
+        );
+    }
+
+    #[test]
+    fn gpt_entry_sizes() {
+        let entries = [(LINUX, 2048, 4095, 0, "data")];
+        for size in [0u32, 64, 127, 132, 1024, 4096] {
+            let mut image = gpt_image(512, 512, &entries);
+            put(&mut image, 512 + 84, &size.to_le_bytes());
+            protective_only(&image);
+        }
+        for size in [128, 136, 512] {
+            let image = gpt_image(512, size, &entries);
+            assert_eq!(
+                table(&image).unwrap().scheme,
+                "gpt",
+                "{}-byte entries",
+                size
+            );
+        }
+    }
+
+    #[test]
+    fn gpt_offsets_that_overflow() {
+        let mut image = gpt_image(512, 128, &[(LINUX, 2048, 4095, 0, "data")]);
+        put(&mut image, 512 + 72, &(u64::MAX / 256).to_le_bytes());
+        protective_only(&image);
+
+        protective_only(&gpt_image(
+            512,
+            128,
+            &[(LINUX, u64::MAX / 256, u64::MAX / 256, 0, "")],
+        ));
+        protective_only(&gpt_image(512, 128, &[(LINUX, 0, u64::MAX, 0, "")]));
+        protective_only(&gpt_image(512, 128, &[(LINUX, 1, u64::MAX / 256, 0, "")]));
+    }
+
+    #[test]
+    fn links_volumes_by_offset() {
+        let mut parts = vec![
+            partition(1, "0x0c".to_string(), 1024 * 1024, 1 << 30),
+            partition(2, "0x83".to_string(), (1 << 30) + 1024 * 1024, 1 << 30),
+        ];
+        let volume = VolumeInfo {
+            drive_letter: "E:".to_string(),
+            volume_name: "STICK".to_string(),
+            file_system: "FAT32".to_string(),
+            ..Default::default()
+        };
+        link(&mut parts, &[(1024 * 1024, volume)]);
+        assert_eq!(
+            (
+                parts[0].file_system.as_str(),
+                parts[0].label.as_str(),
+                parts[0].drive_letter.as_str()
+            ),
+            ("FAT32", "STICK", "E:")
+        );
+        assert_eq!(parts[1].file_system, "");
+    }
+}
@@ -0,0 +1,730 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Raw devices only take sector-aligned reads, on Windows especially.
+const ALIGN: u64 = 512;
+
+/// Cap on how much of a directory or MFT record gets read.
+const MAX_READ: u64 = 64 * 1024;
+
+/// What a filesystem's superblock says about it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Filesystem {
+    /// "FAT32", "exFAT", "NTFS", "ext4", "btrfs", "ISO9660", ...
+    pub kind: &'static str,
+    pub label: String,
+    /// Volume serial or UUID, printed the way the filesystem's own tools
+    /// print it: `1A2B-3C4D` for FAT and exFAT, 16 hex digits for NTFS,
+    /// a dashed UUID for ext and btrfs, the creation time for ISO9660.
+    pub uuid: String,
+    pub size_bytes: u64,
+}
+
+/// Probes the filesystem starting `offset` bytes into a block device or
+/// image file. None if it can't be opened (no permission on `/dev/sdX`
+/// is the usual case) or nothing recognisable is there.
+pub fn probe_file(path: &Path, offset: u64) -> Option<Filesystem> {
+    let mut file = File::open(path).ok()?;
+    probe(&mut file, offset)
+}
+
+/// Tries each known superblock layout in turn: FAT12/16/32, exFAT, NTFS,
+/// ext2/3/4, btrfs and ISO9660. Reading the superblock directly finds the
+/// label, serial or UUID of partitions that aren't mounted or lettered.
+pub fn probe<R: Read + Seek>(r: &mut R, base: u64) -> Option<Filesystem> {
+    let mut disk = Disk { r, base };
+    // Layouts with a superblock away from the start go first: a hybrid
+    // ISO also carries a boot sector.
+    if let Some(fs) = iso9660(&mut disk) {
+        return Some(fs);
+    }
+    if let Some(fs) = btrfs(&mut disk) {
+        return Some(fs);
+    }
+    if let Some(fs) = ext(&mut disk) {
+        return Some(fs);
+    }
+    let boot = disk.read(0, 512)?;
+    match &boot[3..11] {
+        b"NTFS    " => ntfs(&mut disk, &boot),
+        b"EXFAT   " => exfat(&mut disk, &boot),
+        _ => fat(&mut disk, &boot),
+    }
+}
+
+struct Disk<'a, R> {
+    r: &'a mut R,
+    base: u64,
+}
+
+impl<R: Read + Seek> Disk<'_, R> {
+    /// `len` bytes at `offset` from the start of the filesystem.
+    fn read(&mut self, offset: u64, len: usize) -> Option<Vec<u8>> {
+        read_at(self.r, self.base.checked_add(offset)?, len)
+    }
+}
+
+/// `len` bytes at `offset`, read in whole sectors.
+pub fn read_at<R: Read + Seek>(r: &mut R, offset: u64, len: usize) -> Option<Vec<u8>> {
+    let aligned = offset - offset % ALIGN;
+    let skip = (offset - aligned) as usize;
+    let total = (skip + len).div_ceil(ALIGN as usize) * ALIGN as usize;
+    r.seek(SeekFrom::Start(aligned)).ok()?;
+    let mut buf = vec![0u8; total];
+    r.read_exact(&mut buf).ok()?;
+    Some(buf[skip..skip + len].to_vec())
+}
+
+// ── FAT ────────────────────────────────────────────────────────
+
+/// FAT12, FAT16 and FAT32. The variant follows from the cluster count,
+/// as the spec says, not from the type string in the boot sector.
+fn fat<R: Read + Seek>(disk: &mut Disk<R>, b: &[u8]) -> Option<Filesystem> {
+    if b[510..512] != [0x55, 0xAA] || !matches!(b[0], 0xEB | 0xE9) {
+        return None;
+    }
+    let bps = le16(b, 11) as u64;
+    let spc = b[13] as u64;
+    let reserved = le16(b, 14) as u64;
+    let fats = b[16] as u64;
+    if !matches!(bps, 512 | 1024 | 2048 | 4096) || !spc.is_power_of_two() || fats == 0 {
+        return None;
+    }
+    let root_entries = le16(b, 17) as u64;
+    let total = match le16(b, 19) {
+        0 => le32(b, 32) as u64,
+        n => n as u64,
+    };
+    let fat_size = match le16(b, 22) {
+        0 => le32(b, 36) as u64,
+        n => n as u64,
+    };
+    let root_sectors = (root_entries * 32).div_ceil(bps);
+    let first_data = reserved + fats * fat_size + root_sectors;
+    let clusters = total.checked_sub(first_data)? / spc;
+    let (kind, ext) = match clusters {
+        0..4085 => ("FAT12", 36),
+        4085..65525 => ("FAT16", 36),
+        _ => ("FAT32", 64),
+    };
+
+    // Extended BPB: signature, serial, then an 11-byte label.
+    let (serial, mut label) = if b[ext + 2] == 0x29 {
+        (
+            format_serial(le32(b, ext + 3)),
+            fat_label(&b[ext + 7..ext + 18]),
+        )
+    } else {
+        (String::new(), String::new())
+    };
+    // Windows only updates the label entry in the root directory.
+    let root = if kind == "FAT32" {
+        let cluster = (le32(b, 44) as u64).checked_sub(2)?;
+        disk.read(
+            (first_data + cluster * spc) * bps,
+            (spc * bps).min(MAX_READ) as usize,
+        )
+    } else {
+        let start = (reserved + fats * fat_size) * bps;
+        disk.read(start, (root_sectors * bps).min(MAX_READ) as usize)
+    };
+    if let Some(entry) = root.as_deref().and_then(fat_label_entry) {
+        label = entry;
+    }
+    Some(Filesystem {
+        kind,
+        label,
+        uuid: serial,
+        size_bytes: total * bps,
+    })
+}
+
+/// The volume label entry of a FAT root directory, if it has one.
+fn fat_label_entry(dir: &[u8]) -> Option<String> {
+    for entry in dir.chunks_exact(32) {
+        match entry[0] {
+            0x00 => break,
+            0xE5 => continue,
+            _ => {}
+        }
+        // Volume ID bit, but not a long file name fragment.
+        if entry[11] & 0x08 != 0 && entry[11] & 0x0F != 0x0F {
+            return Some(fat_label(&entry[..11]));
+        }
+    }
+    None
+}
+
+fn fat_label(b: &[u8]) -> String {
+    let label = text(b);
+    if label == "NO NAME" {
+        String::new()
+    } else {
+        label
+    }
+}
+
+// ── exFAT ──────────────────────────────────────────────────────
+
+fn exfat<R: Read + Seek>(disk: &mut Disk<R>, b: &[u8]) -> Option<Filesystem> {
+    let sector_shift = b[108] as u32;
+    let cluster_shift = sector_shift + b[109] as u32;
+    if !(9..=12).contains(&sector_shift) || cluster_shift > 25 {
+        return None;
+    }
+    let heap = (le32(b, 88) as u64) << sector_shift;
+    let root_cluster = (le32(b, 96) as u64).checked_sub(2)?;
+    let root = heap + (root_cluster << cluster_shift);
+    let dir = disk.read(root, (1u64 << cluster_shift).min(MAX_READ) as usize)?;
+
+    // The label is a 0x83 entry in the root directory, up to 11 UTF-16
+    // characters.
+    let mut label = String::new();
+    for entry in dir.chunks_exact(32) {
+        match entry[0] {
+            0x00 => break,
+            0x83 => {
+                let chars = (entry[1] as usize).min(11);
+                label = utf16(&entry[2..2 + chars * 2]);
+                break;
+            }
+            _ => {}
+        }
+    }
+    Some(Filesystem {
+        kind: "exFAT",
+        label,
+        uuid: format_serial(le32(b, 100)),
+        size_bytes: le64(b, 72) << sector_shift,
+    })
+}
+
+// ── NTFS ───────────────────────────────────────────────────────
+
+/// MFT record of the `$Volume` metadata file, which holds the label.
+const MFT_VOLUME: u64 = 3;
+const ATTR_VOLUME_NAME: u32 = 0x60;
+const ATTR_END: u32 = 0xFFFF_FFFF;
+
+fn ntfs<R: Read + Seek>(disk: &mut Disk<R>, b: &[u8]) -> Option<Filesystem> {
+    let bps = le16(b, 11) as u64;
+    if !matches!(bps, 512 | 1024 | 2048 | 4096) {
+        return None;
+    }
+    // Values above 0x80 are negative powers of two.
+    let cluster = match b[13] {
+        n @ 1..=0x80 => n as u64 * bps,
+        n => bps << (256 - n as u32).min(31),
+    };
+    let record = match b[64] as i8 {
+        n if n > 0 => n as u64 * cluster,
+        n => 1u64 << (-(n as i32)).min(31),
+    };
+    // No real volume has records smaller than a sector.
+    if record < 512 {
+        return None;
+    }
+    let size_bytes = le64(b, 40).checked_mul(bps)?;
+    let mft = le64(b, 48).checked_mul(cluster)?;
+    let label = MFT_VOLUME
+        .checked_mul(record)
+        .and_then(|offset| mft.checked_add(offset))
+        .and_then(|at| disk.read(at, record.min(MAX_READ) as usize))
+        .and_then(|rec| ntfs_volume_name(rec, bps as usize))
+        .unwrap_or_default();
+    Some(Filesystem {
+        kind: "NTFS",
+        label,
+        uuid: format!("{:016X}", le64(b, 72)),
+        size_bytes,
+    })
+}
+
+/// The `$VOLUME_NAME` attribute of an MFT record, after undoing the
+/// update sequence that overwrites the last two bytes of each sector.
+fn ntfs_volume_name(mut rec: Vec<u8>, sector: usize) -> Option<String> {
+    // The header runs up to the first attribute's offset at byte 20.
+    if rec.len() < 24 || &rec[..4] != b"FILE" {
+        return None;
+    }
+    let usa = le16(&rec, 4) as usize;
+    let usa_count = le16(&rec, 6) as usize;
+    for i in 1..usa_count {
+        let end = i * sector;
+        let fix = usa + i * 2;
+        if end > rec.len() || fix + 2 > rec.len() {
+            break;
+        }
+        let (a, b) = (rec[fix], rec[fix + 1]);
+        rec[end - 2] = a;
+        rec[end - 1] = b;
+    }
+
+    let mut at = le16(&rec, 20) as usize;
+    while at + 24 <= rec.len() {
+        let kind = le32(&rec, at);
+        let len = le32(&rec, at + 4) as usize;
+        if kind == ATTR_END || len == 0 {
+            break;
+        }
+        // Resident attributes only; the name always is.
+        if kind == ATTR_VOLUME_NAME && rec[at + 8] == 0 {
+            let size = le32(&rec, at + 16) as usize;
+            let start = at + le16(&rec, at + 20) as usize;
+            return rec.get(start..start.checked_add(size)?).map(utf16);
+        }
+        at += len;
+    }
+    None
+}
+
+// ── ext2/3/4 ───────────────────────────────────────────────────
+
+const EXT_MAGIC: u16 = 0xEF53;
+const EXT_COMPAT_JOURNAL: u32 = 0x0004;
+const EXT_INCOMPAT_64BIT: u32 = 0x0080;
+/// Extents, 64-bit and flex_bg: features only ext4 has.
+const EXT4_INCOMPAT: u32 = 0x0040 | 0x0080 | 0x0200;
+/// huge_file, gdt_csum, dir_nlink and extra_isize.
+const EXT4_RO_COMPAT: u32 = 0x0008 | 0x0010 | 0x0020 | 0x0040;
+
+fn ext<R: Read + Seek>(disk: &mut Disk<R>) -> Option<Filesystem> {
+    let sb = disk.read(1024, 1024)?;
+    if le16(&sb, 56) != EXT_MAGIC {
+        return None;
+    }
+    let block_shift = le32(&sb, 24);
+    if block_shift > 6 {
+        return None;
+    }
+    let compat = le32(&sb, 92);
+    let incompat = le32(&sb, 96);
+    let ro_compat = le32(&sb, 100);
+    let mut blocks = le32(&sb, 4) as u64;
+    if incompat & EXT_INCOMPAT_64BIT != 0 {
+        blocks |= (le32(&sb, 0x150) as u64) << 32;
+    }
+    let kind = if incompat & EXT4_INCOMPAT != 0 || ro_compat & EXT4_RO_COMPAT != 0 {
+        "ext4"
+    } else if compat & EXT_COMPAT_JOURNAL != 0 {
+        "ext3"
+    } else {
+        "ext2"
+    };
+    Some(Filesystem {
+        kind,
+        label: text(&sb[120..136]),
+        uuid: format_uuid(&sb[104..120]),
+        size_bytes: blocks << (10 + block_shift),
+    })
+}
+
+// ── btrfs ──────────────────────────────────────────────────────
+
+const BTRFS_SUPER: u64 = 0x10000;
+
+fn btrfs<R: Read + Seek>(disk: &mut Disk<R>) -> Option<Filesystem> {
+    let sb = disk.read(BTRFS_SUPER, 0x22B)?;
+    if &sb[0x40..0x48] != b"_BHRfS_M" {
+        return None;
+    }
+    Some(Filesystem {
+        kind: "btrfs",
+        label: text(&sb[0x12B..0x22B]),
+        uuid: format_uuid(&sb[0x20..0x30]),
+        size_bytes: le64(&sb, 0x70),
+    })
+}
+
+// ── ISO9660 ────────────────────────────────────────────────────
+
+/// The primary volume descriptor is always in sector 16.
+const ISO_PVD: u64 = 16 * 2048;
+
+fn iso9660<R: Read + Seek>(disk: &mut Disk<R>) -> Option<Filesystem> {
+    let d = disk.read(ISO_PVD, 2048)?;
+    if d[0] != 1 || &d[1..6] != b"CD001" {
+        return None;
+    }
+    // ISO9660 has no UUID; blkid uses the creation time, "2024013112000000"
+    // printed as 2024-01-31-12-00-00-00.
+    let created = &d[813..829];
+    let uuid = if created.iter().all(u8::is_ascii_digit) && created.iter().any(|c| *c != b'0') {
+        let s = std::str::from_utf8(created).unwrap_or_default();
+        let mut parts = vec![&s[..4]];
+        parts.extend((4..16).step_by(2).map(|i| &s[i..i + 2]));
+        parts.join("-")
+    } else {
+        String::new()
+    };
+    Some(Filesystem {
+        kind: "ISO9660",
+        label: text(&d[40..72]),
+        uuid,
+        size_bytes: le32(&d, 80) as u64 * le16(&d, 128) as u64,
+    })
+}
+
+// ── Helpers ────────────────────────────────────────────────────
+
+fn le16(b: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([b[at], b[at + 1]])
+}
+
+pub fn le32(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap_or_default())
+}
+
+pub fn le64(b: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(b[at..at + 8].try_into().unwrap_or_default())
+}
+
+/// A fixed-width label field, padded with spaces or NULs.
+fn text(b: &[u8]) -> String {
+    let end = b.iter().position(|c| *c == 0).unwrap_or(b.len());
+    String::from_utf8_lossy(&b[..end]).trim_end().to_string()
+}
+
+pub fn utf16(b: &[u8]) -> String {
+    let units: Vec<u16> = b
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|u| *u != 0)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// `1A2B-3C4D`, the way `dir` and `vol` print a volume serial.
+pub fn format_serial(serial: u32) -> String {
+    format!("{:04X}-{:04X}", serial >> 16, serial & 0xFFFF)
+}
+
+fn format_uuid(b: &[u8]) -> String {
+    let hex: String = b.iter().map(|x| format!("{:02x}", x)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn put(b: &mut [u8], at: usize, bytes: &[u8]) {
+        b[at..at + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn probe_bytes(image: &[u8]) -> Option<Filesystem> {
+        probe(&mut Cursor::new(image), 0)
+    }
+
+    /// A FAT boot sector with 512-byte sectors and one sector per cluster.
+    fn fat_boot(reserved: u16, fat_size: u32, root_entries: u16, total: u32) -> Vec<u8> {
+        let mut b = vec![0u8; 512];
+        b[0] = 0xEB;
+        put(&mut b, 11, &512u16.to_le_bytes());
+        b[13] = 1;
+        put(&mut b, 14, &reserved.to_le_bytes());
+        b[16] = 2;
+        put(&mut b, 17, &root_entries.to_le_bytes());
+        match u16::try_from(total) {
+            Ok(total) => put(&mut b, 19, &total.to_le_bytes()),
+            Err(_) => put(&mut b, 32, &total.to_le_bytes()),
+        }
+        match u16::try_from(fat_size) {
+            Ok(size) if root_entries > 0 => put(&mut b, 22, &size.to_le_bytes()),
+            _ => put(&mut b, 36, &fat_size.to_le_bytes()),
+        }
+        put(&mut b, 510, &[0x55, 0xAA]);
+        b
+    }
+
+    #[test]
+    fn fat12_without_a_label() {
+        // A 1.44 MB floppy: 224 root entries, so data starts at sector 33.
+        let mut image = vec![0u8; 33 * 512];
+        let mut boot = fat_boot(1, 9, 224, 2880);
+        boot[38] = 0x29;
+        put(&mut boot, 39, &0x1234_ABCDu32.to_le_bytes());
+        put(&mut boot, 43, b"NO NAME    ");
+        put(&mut image, 0, &boot);
+
+        let fs = probe_bytes(&image).unwrap();
+        assert_eq!(fs.kind, "FAT12");
+        assert_eq!(fs.label, "");
+        assert_eq!(fs.uuid, "1234-ABCD");
+        assert_eq!(fs.size_bytes, 2880 * 512);
+    }
+
+    #[test]
+    fn fat16_label_from_the_boot_sector() {
+        let mut boot = fat_boot(1, 80, 512, 20000);
+        boot[38] = 0x29;
+        put(&mut boot, 39, &0x1A2B_3C4Du32.to_le_bytes());
+        put(&mut boot, 43, b"PHOTOS     ");
+
+        // The root directory is past the end, so only the boot sector
+        // has a label.
+        let fs = probe_bytes(&boot).unwrap();
+        assert_eq!(fs.kind, "FAT16");
+        assert_eq!(fs.label, "PHOTOS");
+        assert_eq!(fs.uuid, "1A2B-3C4D");
+        assert_eq!(fs.size_bytes, 20000 * 512);
+    }
+
+    #[test]
+    fn fat32_label_from_the_root_directory() {
+        // Data, and the root directory in cluster 2, start at sector
+        // 32 + 2 * 600.
+        let root = 1232 * 512;
+        let mut image = vec![0u8; root + 512];
+        let mut boot = fat_boot(32, 600, 0, 1232 + 70000);
+        put(&mut boot, 44, &2u32.to_le_bytes());
+        boot[66] = 0x29;
+        put(&mut boot, 67, &0xDEAD_BEEFu32.to_le_bytes());
+        put(&mut boot, 71, b"OLD LABEL  ");
+        put(&mut image, 0, &boot);
+        // A deleted label, a long file name fragment, then the label.
+        put(&mut image, root, b"\xE5ONE LABEL \x08");
+        put(&mut image, root + 32, b"Aa\0b\0c\0d\0e\0\x0F");
+        put(&mut image, root + 64, b"NEW LABEL  \x08");
+
+        let fs = probe_bytes(&image).unwrap();
+        assert_eq!(fs.kind, "FAT32");
+        assert_eq!(fs.label, "NEW LABEL");
+        assert_eq!(fs.uuid, "DEAD-BEEF");
+        assert_eq!(fs.size_bytes, (1232 + 70000) * 512);
+    }
+
+    #[test]
+    fn fat_needs_a_sane_boot_sector() {
+        let mut boot = fat_boot(1, 80, 512, 20000);
+        assert!(probe_bytes(&boot).is_some());
+        put(&mut boot, 11, &500u16.to_le_bytes());
+        assert_eq!(probe_bytes(&boot), None);
+
+        let mut boot = fat_boot(1, 80, 512, 20000);
+        boot[16] = 0;
+        assert_eq!(probe_bytes(&boot), None);
+
+        // More reserved and FAT sectors than the volume has.
+        let boot = fat_boot(1, 80, 512, 100);
+        assert_eq!(probe_bytes(&boot), None);
+        assert_eq!(probe_bytes(&[0u8; 512]), None);
+        assert_eq!(probe_bytes(&[0u8; 100]), None);
+    }
+
+    #[test]
+    fn exfat() {
+        // 512-byte sectors, 4 KB clusters, the heap at sector 128 and the
+        // root directory in cluster 4.
+        let root = 128 * 512 + 2 * 4096;
+        let mut image = vec![0u8; root + 4096];
+        put(&mut image, 3, b"EXFAT   ");
+        put(&mut image, 72, &1_000_000u64.to_le_bytes());
+        put(&mut image, 88, &128u32.to_le_bytes());
+        put(&mut image, 96, &4u32.to_le_bytes());
+        put(&mut image, 100, &0x0102_0304u32.to_le_bytes());
+        image[108] = 9;
+        image[109] = 3;
+        // A bitmap entry, then the label.
+        image[root] = 0x81;
+        put(&mut image, root + 32, &[0x83, 5]);
+        put(&mut image, root + 34, b"S\0T\0I\0C\0K\0");
+
+        let fs = probe_bytes(&image).unwrap();
+        assert_eq!(fs.kind, "exFAT");
+        assert_eq!(fs.label, "STICK");
+        assert_eq!(fs.uuid, "0102-0304");
+        assert_eq!(fs.size_bytes, 1_000_000 * 512);
+
+        image[108] = 13;
+        assert_eq!(probe_bytes(&image), None);
+    }
+
+    /// An NTFS volume with 4 KB clusters and 1 KB MFT records, the MFT at
+    /// cluster 4. `$Volume`'s record names it "USB DRIVE", and the name
+    /// straddles the first sector's update sequence bytes.
+    fn ntfs_image() -> Vec<u8> {
+        let record = 4 * 4096 + 3 * 1024;
+        let mut image = vec![0u8; record + 1024];
+        put(&mut image, 3, b"NTFS    ");
+        put(&mut image, 11, &512u16.to_le_bytes());
+        image[13] = 8;
+        put(&mut image, 40, &2_000_000u64.to_le_bytes());
+        put(&mut image, 48, &4u64.to_le_bytes());
+        image[64] = 0xF6;
+        put(&mut image, 72, &0x0123_4567_89AB_CDEFu64.to_le_bytes());
+
+        let rec = &mut image[record..];
+        put(rec, 0, b"FILE");
+        put(rec, 4, &48u16.to_le_bytes());
+        put(rec, 6, &3u16.to_le_bytes());
+        put(rec, 20, &56u16.to_le_bytes());
+        // $STANDARD_INFORMATION, skipped over.
+        put(rec, 56, &0x10u32.to_le_bytes());
+        put(rec, 60, &424u32.to_le_bytes());
+        // $VOLUME_NAME, resident, its value right after the header.
+        put(rec, 480, &0x60u32.to_le_bytes());
+        put(rec, 484, &48u32.to_le_bytes());
+        put(rec, 496, &18u32.to_le_bytes());
+        put(rec, 500, &24u16.to_le_bytes());
+        put(rec, 504, b"U\0S\0B\0 \0D\0R\0I\0V\0E\0");
+        put(rec, 528, &ATTR_END.to_le_bytes());
+        // Update sequence: the number, then the real ends of both sectors,
+        // which the number stands in for.
+        put(rec, 48, &[0x01, 0x00, b' ', 0x00, 0x00, 0x00]);
+        put(rec, 510, &[0x01, 0x00]);
+        put(rec, 1022, &[0x01, 0x00]);
+        image
+    }
+
+    #[test]
+    fn ntfs() {
+        let fs = probe_bytes(&ntfs_image()).unwrap();
+        assert_eq!(fs.kind, "NTFS");
+        assert_eq!(fs.label, "USB DRIVE");
+        assert_eq!(fs.uuid, "0123456789ABCDEF");
+        assert_eq!(fs.size_bytes, 2_000_000 * 512);
+    }
+
+    #[test]
+    fn ntfs_rejects_tiny_records_and_overflows() {
+        // 2^-8: 256-byte records.
+        let mut image = ntfs_image();
+        image[64] = 0xF8;
+        assert_eq!(probe_bytes(&image), None);
+        image[64] = 0;
+        assert_eq!(probe_bytes(&image), None);
+
+        let mut image = ntfs_image();
+        put(&mut image, 40, &u64::MAX.to_le_bytes());
+        assert_eq!(probe_bytes(&image), None);
+
+        // The MFT fits, but `$Volume` is past the end of the address space.
+        let mut image = ntfs_image();
+        put(&mut image, 48, &(u64::MAX / 4096).to_le_bytes());
+        let fs = probe_bytes(&image).unwrap();
+        assert_eq!((fs.kind, fs.label.as_str()), ("NTFS", ""));
+    }
+
+    #[test]
+    fn ntfs_records_that_are_cut_short() {
+        assert_eq!(ntfs_volume_name(Vec::new(), 512), None);
+        assert_eq!(ntfs_volume_name(b"FILE".to_vec(), 512), None);
+        assert_eq!(ntfs_volume_name(b"FILE0\0\0\0".repeat(3), 512), None);
+
+        // A value that runs off the end of the record.
+        let image = ntfs_image();
+        let at = 4 * 4096 + 3 * 1024;
+        let mut rec = image[at..at + 1024].to_vec();
+        put(&mut rec, 496, &u32::MAX.to_le_bytes());
+        assert_eq!(ntfs_volume_name(rec, 512), None);
+    }
+
+    /// An ext superblock with 4 KB blocks, labelled `rootfs`.
+    fn ext_image(compat: u32, incompat: u32, ro_compat: u32) -> Vec<u8> {
+        let mut image = vec![0u8; 4096];
+        let sb = &mut image[1024..2048];
+        put(sb, 4, &262_144u32.to_le_bytes());
+        put(sb, 24, &2u32.to_le_bytes());
+        put(sb, 56, &EXT_MAGIC.to_le_bytes());
+        put(sb, 92, &compat.to_le_bytes());
+        put(sb, 96, &incompat.to_le_bytes());
+        put(sb, 100, &ro_compat.to_le_bytes());
+        put(sb, 104, &(0x10..0x20).collect::<Vec<u8>>());
+        put(sb, 120, b"rootfs");
+        image
+    }
+
+    #[test]
+    fn ext_generations() {
+        let fs = probe_bytes(&ext_image(0, 0, 0)).unwrap();
+        assert_eq!(fs.kind, "ext2");
+        assert_eq!(fs.label, "rootfs");
+        assert_eq!(fs.uuid, "10111213-1415-1617-1819-1a1b1c1d1e1f");
+        assert_eq!(fs.size_bytes, 262_144 * 4096);
+
+        let ext3 = probe_bytes(&ext_image(EXT_COMPAT_JOURNAL, 0, 0)).unwrap();
+        assert_eq!(ext3.kind, "ext3");
+        let ext4 = probe_bytes(&ext_image(EXT_COMPAT_JOURNAL, 0x0040, 0)).unwrap();
+        assert_eq!(ext4.kind, "ext4");
+        let ext4 = probe_bytes(&ext_image(EXT_COMPAT_JOURNAL, 0, 0x0008)).unwrap();
+        assert_eq!(ext4.kind, "ext4");
+
+        // 64-bit block counts carry the high half further on.
+        let mut image = ext_image(0, EXT_INCOMPAT_64BIT, 0);
+        put(&mut image, 1024 + 0x150, &1u32.to_le_bytes());
+        let fs = probe_bytes(&image).unwrap();
+        assert_eq!(fs.size_bytes, ((1 << 32) + 262_144) * 4096);
+
+        let mut image = ext_image(0, 0, 0);
+        put(&mut image, 1024 + 24, &7u32.to_le_bytes());
+        assert_eq!(probe_bytes(&image), None);
+    }
+
+    #[test]
+    fn btrfs() {
+        let mut image = vec![0u8; BTRFS_SUPER as usize + 4096];
+        let sb = &mut image[BTRFS_SUPER as usize..];
+        put(sb, 0x20, &(0xA0..0xB0).collect::<Vec<u8>>());
+        put(sb, 0x40, b"_BHRfS_M");
+        put(sb, 0x70, &(64u64 << 30).to_le_bytes());
+        put(sb, 0x12B, b"backup");
+
+        let fs = probe_bytes(&image).unwrap();
+        assert_eq!(fs.kind, "btrfs");
+        assert_eq!(fs.label, "backup");
+        assert_eq!(fs.uuid, "a0a1a2a3-a4a5-a6a7-a8a9-aaabacadaeaf");
+        assert_eq!(fs.size_bytes, 64 << 30);
+    }
+
+    fn iso_image(created: &[u8; 16]) -> Vec<u8> {
+        let mut image = vec![0u8; ISO_PVD as usize + 2048];
+        let d = &mut image[ISO_PVD as usize..];
+        d[0] = 1;
+        put(d, 1, b"CD001");
+        put(d, 40, format!("{:<32}", "UBUNTU_24_04").as_bytes());
+        put(d, 80, &300_000u32.to_le_bytes());
+        put(d, 128, &2048u16.to_le_bytes());
+        put(d, 813, created);
+        image
+    }
+
+    #[test]
+    fn iso9660() {
+        let fs = probe_bytes(&iso_image(b"2024013112000000")).unwrap();
+        assert_eq!(fs.kind, "ISO9660");
+        assert_eq!(fs.label, "UBUNTU_24_04");
+        assert_eq!(fs.uuid, "2024-01-31-12-00-00-00");
+        assert_eq!(fs.size_bytes, 300_000 * 2048);
+
+        let fs = probe_bytes(&iso_image(b"0000000000000000")).unwrap();
+        assert_eq!(fs.uuid, "");
+    }
+
+    #[test]
+    fn hybrid_isos_are_isos() {
+        // A boot sector that also looks like FAT16.
+        let mut image = iso_image(b"2024013112000000");
+        let mut boot = fat_boot(1, 80, 512, 20000);
+        put(&mut boot, 43, b"PHOTOS     ");
+        put(&mut image, 0, &boot);
+        assert_eq!(probe_bytes(&image).unwrap().kind, "ISO9660");
+    }
+
+    #[test]
+    fn probes_at_an_offset() {
+        let mut image = vec![0u8; 1024 * 1024];
+        image.extend(ntfs_image());
+        let fs = probe(&mut Cursor::new(&image), 1024 * 1024).unwrap();
+        assert_eq!(fs.label, "USB DRIVE");
+    }
+}
@@ -0,0 +1,337 @@
+use super::partition::{link, partition, read_table_file};
+use super::probe::{format_serial, probe_file};
+use super::StorageProvider;
+use crate::instance_id::DeviceInstanceId;
+use crate::logging::log_to_file;
+use crate::types::{PartitionFlag, PartitionInfo, StorageInfo, VolumeInfo, WmiDiskDrive};
+use ::wmi::WMIConnection;
+use serde::Deserialize;
+use std::path::Path;
+
+/// One `Get-Partition` row, with its volume if it has one.
+#[derive(Deserialize)]
+#[allow(non_snake_case)]
+struct PsVolume {
+    DriveLetter: Option<String>,
+    Offset: Option<u64>,
+    PartitionSize: Option<u64>,
+    PartitionNumber: Option<u32>,
+    GptType: Option<String>,
+    MbrType: Option<u16>,
+    IsHidden: Option<bool>,
+    IsActive: Option<bool>,
+    Serial: Option<u32>,
+    Label: Option<String>,
+    Size: Option<u64>,
+    FreeSpace: Option<u64>,
+    FileSystem: Option<String>,
+}
+
+/// A drive's partition table and the volumes on it.
+#[derive(Default)]
+struct Layout {
+    table: String,
+    partitions: Vec<PartitionInfo>,
+    volumes: Vec<VolumeInfo>,
+}
+
+fn query_layout_for_drive(drive: &WmiDiskDrive) -> Layout {
+    let device_id = match drive.DeviceID.as_deref() {
+        Some(id) => id,
+        None => return Layout::default(),
+    };
+
+    let disk_index = match device_id
+        .to_uppercase()
+        .rsplit("PHYSICALDRIVE")
+        .next()
+        .and_then(|s| s.parse::<u32>().ok())
+    {
+        Some(idx) => idx,
+        None => {
+            log_to_file(&format!(
+                "ENRICH: can't extract disk index from {}",
+                device_id
+            ));
+            return Layout::default();
+        }
+    };
+
+    let ps_script = format!(
+        "$ErrorActionPreference='SilentlyContinue'; \
+         Get-Partition -DiskNumber {} | ForEach-Object {{ \
+           $v = $_ | Get-Volume; \
+           $w = if($v.Path){{Get-CimInstance Win32_Volume | Where-Object {{ $_.DeviceID -eq $v.Path }}}}; \
+           [PSCustomObject]@{{ \
+             DriveLetter=[string]$_.DriveLetter; \
+             Offset=$_.Offset; \
+             PartitionSize=$_.Size; \
+             PartitionNumber=$_.PartitionNumber; \
+             GptType=[string]$_.GptType; \
+             MbrType=$_.MbrType; \
+             IsHidden=[bool]$_.IsHidden; \
+             IsActive=[bool]$_.IsActive; \
+             Serial=if($w.SerialNumber){{$w.SerialNumber}}else{{0}}; \
+             Label=if($v.FileSystemLabel){{$v.FileSystemLabel}}else{{''}}; \
+             Size=if($v.Size){{$v.Size}}else{{0}}; \
+             FreeSpace=if($v.SizeRemaining){{$v.SizeRemaining}}else{{0}}; \
+             FileSystem=if($v.FileSystem){{$v.FileSystem}}else{{''}} \
+           }} \
+         }} | ConvertTo-Json -Compress",
+        disk_index
+    );
+
+    let output = match std::process::Command::new("powershell")
+        .args(["-NoProfile", "-Command", &ps_script])
+        .output()
+    {
+        Ok(o) => o,
+        Err(e) => {
+            log_to_file(&format!("ENRICH: PowerShell failed: {}", e));
+            return Layout::default();
+        }
+    };
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let trimmed = stdout.trim();
+    if trimmed.is_empty() {
+        log_to_file(&format!("ENRICH: no volumes for disk index {}", disk_index));
+        return Layout::default();
+    }
+
+    let ps_volumes: Vec<PsVolume> = match serde_json::from_str::<Vec<PsVolume>>(trimmed) {
+        Ok(v) => v,
+        Err(_) => match serde_json::from_str::<PsVolume>(trimmed) {
+            Ok(v) => vec![v],
+            Err(e) => {
+                log_to_file(&format!(
+                    "ENRICH: JSON parse failed: {} — raw: {}",
+                    e, trimmed
+                ));
+                return Layout::default();
+            }
+        },
+    };
+
+    log_to_file(&format!(
+        "ENRICH: disk index {} has {} partitions",
+        disk_index,
+        ps_volumes.len()
+    ));
+
+    let disk = format!(r"\\.\PHYSICALDRIVE{}", disk_index);
+    // The raw table has names and every partition; Get-Partition is the
+    // fallback when the app isn't elevated.
+    let (table, mut partitions) = match read_table_file(Path::new(&disk)) {
+        Some(table) => (table.scheme.to_string(), table.partitions),
+        None => ps_partitions(&ps_volumes),
+    };
+    let volumes: Vec<(u64, VolumeInfo)> = ps_volumes
+        .into_iter()
+        .filter_map(|pv| {
+            let letter = pv.DriveLetter.filter(|l| !l.is_empty() && l != "\0");
+            // Windows only recognises its own filesystems. Reading the
+            // raw disk needs elevation; without it, unlettered partitions
+            // Windows can't read are left out.
+            let offset = pv.Offset?;
+            let fs = probe_file(Path::new(&disk), offset);
+            if letter.is_none()
+                && fs.is_none()
+                && pv.FileSystem.as_deref().is_none_or(str::is_empty)
+            {
+                return None;
+            }
+            let fs = fs.unwrap_or_default();
+            let or =
+                |ps: Option<String>, probed: String| ps.filter(|s| !s.is_empty()).unwrap_or(probed);
+            let volume = VolumeInfo {
+                drive_letter: letter.map(|l| format!("{}:", l)).unwrap_or_default(),
+                volume_name: or(pv.Label, fs.label),
+                total_bytes: pv
+                    .Size
+                    .filter(|s| *s > 0)
+                    .or(Some(fs.size_bytes).filter(|s| *s > 0))
+                    .or(pv.PartitionSize)
+                    .unwrap_or(0),
+                free_bytes: pv.FreeSpace.unwrap_or(0),
+                file_system: or(pv.FileSystem, fs.kind.to_string()),
+                volume_serial: match pv.Serial {
+                    Some(serial) if serial != 0 => format_serial(serial),
+                    _ => fs.uuid,
+                },
+            };
+            Some((offset, volume))
+        })
+        .collect();
+    link(&mut partitions, &volumes);
+    Layout {
+        table,
+        partitions,
+        volumes: volumes.into_iter().map(|(_, vol)| vol).collect(),
+    }
+}
+
+/// Partitions as `Get-Partition` describes them. No GPT names, and
+/// Windows numbers them in disk order rather than by table slot.
+fn ps_partitions(ps_volumes: &[PsVolume]) -> (String, Vec<PartitionInfo>) {
+    let mut table = String::new();
+    let partitions = ps_volumes
+        .iter()
+        .filter_map(|pv| {
+            let type_id = match (pv.GptType.as_deref(), pv.MbrType) {
+                (Some(guid), _) if !guid.is_empty() => {
+                    table = "gpt".to_string();
+                    guid.trim_matches(['{', '}']).to_uppercase()
+                }
+                (_, Some(kind)) => {
+                    table = "mbr".to_string();
+                    format!("0x{:02x}", kind)
+                }
+                _ => String::new(),
+            };
+            let mut part = partition(
+                pv.PartitionNumber?,
+                type_id,
+                pv.Offset?,
+                pv.PartitionSize.unwrap_or(0),
+            );
+            if pv.IsActive == Some(true) && !part.flags.contains(&PartitionFlag::Bootable) {
+                part.flags.push(PartitionFlag::Bootable);
+            }
+            if pv.IsHidden == Some(true) && !part.flags.contains(&PartitionFlag::Hidden) {
+                part.flags.push(PartitionFlag::Hidden);
+            }
+            Some(part)
+        })
+        .collect();
+    (table, partitions)
+}
+
+/// Matches a USB device to its `Win32_DiskDrive` by serial, then lists
+/// the drive's partitions through `Get-Partition`, probing the raw disk
+/// for filesystems Windows doesn't know.
+pub struct WmiStorage<'a> {
+    wmi: &'a WMIConnection,
+}
+
+impl<'a> WmiStorage<'a> {
+    pub fn new(wmi: &'a WMIConnection) -> Self {
+        Self { wmi }
+    }
+}
+
+impl StorageProvider for WmiStorage<'_> {
+    fn query_storage_info(&mut self, device_id: &str) -> Option<StorageInfo> {
+        // Drives are matched by serial; a port-derived instance ID won't
+        // appear in any Win32_DiskDrive, so don't bother querying.
+        let Some(usb_serial) = DeviceInstanceId::parse(device_id)
+            .serial()
+            .map(str::to_uppercase)
+        else {
+            log_to_file(&format!("ENRICH SKIP: {} has no device serial", device_id));
+            return None;
+        };
+
+        let drives: Vec<WmiDiskDrive> = match self.wmi.raw_query(
+            "SELECT DeviceID, PNPDeviceID, Model, SerialNumber, Size, InterfaceType, \
+             MediaType, Partitions, FirmwareRevision, Status \
+             FROM Win32_DiskDrive",
+        ) {
+            Ok(d) => d,
+            Err(e) => {
+                log_to_file(&format!("ENRICH FAIL: WMI query error: {}", e));
+                return None;
+            }
+        };
+
+        log_to_file(&format!(
+            "ENRICH: usb_serial={}, found {} drives: [{}]",
+            usb_serial,
+            drives.len(),
+            drives
+                .iter()
+                .map(|d| format!(
+                    "{}|{}|{}",
+                    d.Model.as_deref().unwrap_or("?"),
+                    d.SerialNumber.as_deref().unwrap_or("?").trim(),
+                    d.InterfaceType.as_deref().unwrap_or("?")
+                ))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+
+        if drives.is_empty() {
+            return None;
+        }
+
+        let matched = drives.iter().find(|d| {
+            if let Some(serial) = &d.SerialNumber {
+                let s = serial.trim().replace(' ', "").to_uppercase();
+                if !s.is_empty() && (s.contains(&usb_serial) || usb_serial.contains(&s)) {
+                    return true;
+                }
+            }
+            if let Some(pnp) = &d.PNPDeviceID {
+                let p = pnp.to_uppercase();
+                if p.contains(&usb_serial) {
+                    return true;
+                }
+            }
+            false
+        });
+
+        if matched.is_none() {
+            log_to_file(&format!(
+                "ENRICH FAIL: no drive matched usb_serial={}",
+                usb_serial
+            ));
+            return None;
+        }
+        let matched = matched?;
+
+        let layout = query_layout_for_drive(matched);
+        log_to_file(&format!(
+            "ENRICH: matched drive={} serial={} → {} volumes [{}]",
+            matched.Model.as_deref().unwrap_or("?"),
+            matched.SerialNumber.as_deref().unwrap_or("?").trim(),
+            layout.volumes.len(),
+            layout
+                .volumes
+                .iter()
+                .map(|v| v.drive_letter.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+
+        Some(StorageInfo {
+            model: matched.Model.clone().unwrap_or_default(),
+            serial_number: matched
+                .SerialNumber
+                .clone()
+                .unwrap_or_default()
+                .trim()
+                .to_string(),
+            total_bytes: matched.Size.unwrap_or(0),
+            interface_type: matched.InterfaceType.clone().unwrap_or_default(),
+            media_type: matched.MediaType.clone().unwrap_or_default(),
+            removable: matched
+                .MediaType
+                .as_deref()
+                .is_some_and(|m| m.contains("Removable")),
+            // Win32_DiskDrive doesn't say; MSFT_PhysicalDisk would.
+            rotational: false,
+            device_path: matched.DeviceID.clone().unwrap_or_default(),
+            firmware: matched.FirmwareRevision.clone().unwrap_or_default(),
+            // Win32_DiskDrive only counts the partitions Windows mounts.
+            partition_count: match layout.partitions.len() {
+                0 => matched.Partitions.unwrap_or(0),
+                n => n as u32,
+            },
+            status: matched.Status.clone().unwrap_or_default(),
+            volumes: layout.volumes,
+            partition_table: layout.table,
+            partitions: layout.partitions,
+            warnings: Vec::new(),
+        })
+    }
+}
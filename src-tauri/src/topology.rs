@@ -0,0 +1,240 @@
+#[cfg(windows)]
+use crate::logging::log_to_file;
+use crate::types::{DeviceSnapshot, TopologyNode};
+#[cfg(windows)]
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// ── Tree ───────────────────────────────────────────────────────
+
+/// Arranges attached devices into the hub/port tree. Devices whose
+/// parent isn't attached (root hubs, or everything when the backend
+/// doesn't report parents) become roots. Siblings are ordered by port.
+pub fn build(devices: Vec<DeviceSnapshot>) -> Vec<TopologyNode> {
+    // Interfaces are folded into their device, so a parent naming one
+    // resolves to the device that owns it.
+    let mut owner: HashMap<String, String> = HashMap::new();
+    for d in &devices {
+        owner.insert(d.device_id.clone(), d.device_id.clone());
+        for iface in &d.interfaces {
+            owner.insert(iface.device_id.clone(), d.device_id.clone());
+        }
+    }
+
+    let mut children: HashMap<String, Vec<DeviceSnapshot>> = HashMap::new();
+    let mut roots = Vec::new();
+    for d in devices {
+        let parent = d
+            .parent_id
+            .as_ref()
+            .and_then(|p| owner.get(p))
+            .filter(|p| **p != d.device_id)
+            .cloned();
+        match parent {
+            Some(parent) => children.entry(parent).or_default().push(d),
+            None => roots.push(d),
+        }
+    }
+
+    let mut tree = attach(roots, &mut children);
+    // Whatever is left sits in a parent loop; show it rather than drop it.
+    let stranded: Vec<DeviceSnapshot> = children.into_values().flatten().collect();
+    tree.extend(attach(stranded, &mut HashMap::new()));
+    tree
+}
+
+fn attach(
+    mut devices: Vec<DeviceSnapshot>,
+    children: &mut HashMap<String, Vec<DeviceSnapshot>>,
+) -> Vec<TopologyNode> {
+    devices.sort_by_cached_key(|d| (location_key(d), d.name.to_lowercase()));
+    devices
+        .into_iter()
+        .map(|device| {
+            let below = children.remove(&device.device_id).unwrap_or_default();
+            TopologyNode {
+                children: attach(below, children),
+                device,
+            }
+        })
+        .collect()
+}
+
+/// Sorts `1-2.10` after `1-2.9`, and devices without a location last.
+fn location_key(d: &DeviceSnapshot) -> (bool, Vec<u64>) {
+    let Some(location) = &d.location_path else {
+        return (true, Vec::new());
+    };
+    let numbers = location
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|n| n.parse().ok())
+        .collect();
+    (false, numbers)
+}
+
+// ── Windows locations ──────────────────────────────────────────
+
+/// Fills in parent and location for devices enumerated over WMI, which
+/// has neither. It takes a PowerShell round trip, so it runs when the
+/// tree is asked for rather than on every poll.
+#[cfg(windows)]
+pub fn resolve_locations(devices: &mut [DeviceSnapshot]) {
+    if devices.is_empty() {
+        return;
+    }
+    let ids: Vec<String> = devices
+        .iter()
+        .map(|d| format!("'{}'", d.device_id.replace('\'', "''")))
+        .collect();
+    let ps_script = format!(
+        "$ErrorActionPreference='SilentlyContinue'; \
+         Get-PnpDeviceProperty -InstanceId @({}) \
+           -KeyName DEVPKEY_Device_Parent,DEVPKEY_Device_LocationPaths | ForEach-Object {{ \
+           [PSCustomObject]@{{ Id=$_.InstanceId; Key=$_.KeyName; Data=$_.Data }} \
+         }} | ConvertTo-Json -Compress",
+        ids.join(",")
+    );
+
+    let output = match std::process::Command::new("powershell")
+        .args(["-NoProfile", "-Command", &ps_script])
+        .output()
+    {
+        Ok(o) => o,
+        Err(e) => {
+            log_to_file(&format!("TOPOLOGY: PowerShell failed: {}", e));
+            return;
+        }
+    };
+
+    #[derive(Deserialize)]
+    #[allow(non_snake_case)]
+    struct PsProperty {
+        Id: String,
+        Key: String,
+        Data: Option<serde_json::Value>,
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let trimmed = stdout.trim();
+    let properties: Vec<PsProperty> = match serde_json::from_str::<Vec<PsProperty>>(trimmed) {
+        Ok(p) => p,
+        Err(_) => match serde_json::from_str::<PsProperty>(trimmed) {
+            Ok(p) => vec![p],
+            Err(e) => {
+                log_to_file(&format!("TOPOLOGY: JSON parse failed: {}", e));
+                return;
+            }
+        },
+    };
+
+    let mut by_id: HashMap<String, &mut DeviceSnapshot> = devices
+        .iter_mut()
+        .map(|d| (d.device_id.to_uppercase(), d))
+        .collect();
+    for prop in &properties {
+        let Some(device) = by_id.get_mut(&prop.Id.to_uppercase()) else {
+            continue;
+        };
+        // LocationPaths is a string array; the first entry is the
+        // PCIROOT(..)#USBROOT(..)#USB(..) path.
+        let value = match &prop.Data {
+            Some(serde_json::Value::String(s)) => Some(s.clone()),
+            Some(serde_json::Value::Array(a)) => {
+                a.first().and_then(|v| v.as_str()).map(String::from)
+            }
+            _ => None,
+        };
+        match prop.Key.as_str() {
+            "DEVPKEY_Device_Parent" => device.parent_id = value,
+            "DEVPKEY_Device_LocationPaths" => device.location_path = value,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Bus, DeviceCategory, DeviceInterface};
+
+    fn device(id: &str, parent: Option<&str>, location: Option<&str>) -> DeviceSnapshot {
+        DeviceSnapshot {
+            device_id: id.to_string(),
+            name: id.to_string(),
+            vid_pid: None,
+            manufacturer: None,
+            class: "USB".to_string(),
+            bus: Bus::Usb,
+            category: DeviceCategory::Other,
+            icon: None,
+            vendor_name: None,
+            product_name: None,
+            class_name: None,
+            interfaces: Vec::new(),
+            parent_id: parent.map(str::to_string),
+            location_path: location.map(str::to_string),
+            speed: None,
+            usb_version: None,
+            power: None,
+            hub_power: None,
+            enrichment: None,
+        }
+    }
+
+    /// The tree as indented names, depth first.
+    fn outline(nodes: &[TopologyNode]) -> Vec<String> {
+        fn walk(nodes: &[TopologyNode], depth: usize, lines: &mut Vec<String>) {
+            for node in nodes {
+                lines.push(format!("{}{}", "  ".repeat(depth), node.device.name));
+                walk(&node.children, depth + 1, lines);
+            }
+        }
+        let mut lines = Vec::new();
+        walk(nodes, 0, &mut lines);
+        lines
+    }
+
+    #[test]
+    fn nests_devices_under_their_hub_in_port_order() {
+        let mut hub = device("hub", Some("usb1"), Some("1-2"));
+        hub.interfaces.push(DeviceInterface {
+            device_id: "hub&MI_00".to_string(),
+            number: Some(0),
+            name: "Hub".to_string(),
+            class: "USB".to_string(),
+            category: DeviceCategory::Hub,
+        });
+        let tree = build(vec![
+            device("port 10", Some("hub"), Some("1-2.10")),
+            device("port 9", Some("hub&MI_00"), Some("1-2.9")),
+            hub,
+            device("unplaced", Some("hub"), None),
+            device("usb1", None, Some("1")),
+        ]);
+        assert_eq!(
+            outline(&tree),
+            ["usb1", "  hub", "    port 9", "    port 10", "    unplaced"]
+        );
+    }
+
+    #[test]
+    fn devices_with_a_missing_parent_become_roots() {
+        let tree = build(vec![
+            device("stick", Some("gone"), Some("1-3")),
+            device("mouse", None, Some("1-1")),
+        ]);
+        assert_eq!(outline(&tree), ["mouse", "stick"]);
+    }
+
+    #[test]
+    fn parent_loops_are_shown_rather_than_dropped() {
+        let tree = build(vec![
+            device("a", Some("b"), Some("1-1")),
+            device("b", Some("a"), Some("1-2")),
+            device("self", Some("self"), Some("1-3")),
+        ]);
+        // A device naming itself is a root; a loop's members follow the
+        // real roots, each once and flat.
+        assert_eq!(outline(&tree), ["self", "a", "b"]);
+    }
+}
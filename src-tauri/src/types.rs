@@ -1,9 +1,11 @@
+use crate::instance_id::DeviceInstanceId;
+use crate::storage::format_bytes;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 // ── WMI device struct ──────────────────────────────────────────
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[allow(non_snake_case)]
 pub struct UsbDevice {
     pub Name: Option<String>,
@@ -11,28 +13,355 @@ pub struct UsbDevice {
     pub Description: Option<String>,
     pub Manufacturer: Option<String>,
     pub PNPClass: Option<String>,
+    /// Driver the device is bound to: WMI's `Service` column, or on Linux
+    /// the `driver` link of the device or its first bound interface.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub Service: Option<String>,
+    /// e.g. `USB\Class_03&SubClass_01&Prot_01`. Linux backends build the
+    /// same strings from sysfs descriptors.
+    #[serde(default)]
+    pub CompatibleID: Option<Vec<String>>,
+    /// Interfaces of a composite device. Sysfs reads them directly; on
+    /// Windows each one is its own `MI_xx` entry until the engine folds
+    /// it into the parent.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub Interfaces: Vec<DeviceInterface>,
+    /// Device ID of the hub this device is plugged into. Only sysfs
+    /// reports it while polling; WMI has no such column.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub Parent: Option<String>,
+    /// Where the device is plugged in: `busnum-devpath` (`1-2.3`) on
+    /// Linux, the first `DEVPKEY_Device_LocationPaths` entry on Windows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub LocationPath: Option<String>,
+    /// Negotiated link speed. Only sysfs reports it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub Speed: Option<LinkSpeed>,
+    /// USB version the device supports (`bcdUSB`), e.g. "3.20".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub UsbVersion: Option<String>,
+    /// From the active configuration descriptor. Only sysfs reports it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub Power: Option<DevicePower>,
+    /// What the user's rules file changes about this device. Set by the
+    /// engine, never by a source.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub Rule: Option<RuleOverride>,
 }
 
 impl UsbDevice {
     pub fn display_name(&self) -> &str {
-        self.Name
-            .as_deref()
+        self.Rule
+            .as_ref()
+            .and_then(|r| r.name.as_deref())
+            .or(self.Name.as_deref())
             .or(self.Description.as_deref())
             .unwrap_or("Unknown Device")
     }
 
+    pub fn instance_id(&self) -> Option<DeviceInstanceId> {
+        Some(DeviceInstanceId::parse(self.DeviceID.as_ref()?))
+    }
+
     pub fn vid_pid(&self) -> Option<String> {
-        let id = self.DeviceID.as_ref()?;
-        let vid_start = id.find("VID_").map(|i| i + 4)?;
-        let vid = id.get(vid_start..vid_start + 4)?;
-        let pid_start = id.find("PID_").map(|i| i + 4)?;
-        let pid = id.get(pid_start..pid_start + 4)?;
-        Some(format!("{}:{}", vid, pid))
+        self.instance_id()?.vid_pid()
+    }
+
+    pub fn bus(&self) -> Bus {
+        self.instance_id()
+            .map_or(Bus::Other, |id| Bus::from_enumerator(&id.enumerator))
     }
 
     pub fn class(&self) -> &str {
         self.PNPClass.as_deref().unwrap_or("?")
     }
+
+    /// USB class, subclass and protocol codes from the first `Class_xx`
+    /// entry in `CompatibleID`.
+    pub fn usb_class(&self) -> Option<(u8, u8, u8)> {
+        self.CompatibleID.as_ref()?.iter().find_map(|id| {
+            let rest = &id[id.find("Class_")? + 6..];
+            let class = u8::from_str_radix(rest.get(..2)?, 16).ok()?;
+            let code = |key: &str| {
+                rest.find(key)
+                    .and_then(|i| rest.get(i + key.len()..i + key.len() + 2))
+                    .and_then(|s| u8::from_str_radix(s, 16).ok())
+                    .unwrap_or(0)
+            };
+            Some((class, code("SubClass_"), code("Prot_")))
+        })
+    }
+
+    /// What kind of device this is: from the user's rules, the device's
+    /// class codes, its interfaces' when the device defers to them, then
+    /// its PnP class.
+    pub fn category(&self) -> DeviceCategory {
+        if let Some(category) = self.Rule.as_ref().and_then(|r| r.category) {
+            return category;
+        }
+        if let Some(category) = self
+            .usb_class()
+            .and_then(|(class, sub, prot)| DeviceCategory::from_class_codes(class, sub, prot))
+        {
+            return category;
+        }
+        if let Some(category) = self
+            .Interfaces
+            .iter()
+            .map(|i| i.category)
+            .filter(|c| *c != DeviceCategory::Other)
+            .min()
+        {
+            return category;
+        }
+        if self
+            .instance_id()
+            .is_some_and(|id| id.enumerator == "USBSTOR")
+        {
+            return DeviceCategory::Storage;
+        }
+        DeviceCategory::from_pnp_class(self.class(), self.display_name())
+    }
+
+    /// Whether to look up drive and volume details for the device.
+    pub fn is_storage(&self) -> bool {
+        self.Rule
+            .as_ref()
+            .and_then(|r| r.storage)
+            .unwrap_or_else(|| self.category() == DeviceCategory::Storage)
+    }
+
+    pub fn icon(&self) -> Option<String> {
+        self.Rule.as_ref()?.icon.clone()
+    }
+}
+
+/// One interface of a composite device (`...&MI_01\...`).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeviceInterface {
+    pub device_id: String,
+    pub number: Option<u8>,
+    pub name: String,
+    pub class: String,
+    #[serde(default)]
+    pub category: DeviceCategory,
+}
+
+// ── Device category ────────────────────────────────────────────
+
+/// Broad device kind, shared by storage detection and the UI filters.
+/// Ordered by precedence: a composite device takes the first category
+/// any of its interfaces has, so a webcam's microphone doesn't make it
+/// an audio device.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceCategory {
+    Storage,
+    Bluetooth,
+    Network,
+    Video,
+    Audio,
+    Hid,
+    Hub,
+    #[default]
+    Other,
+}
+
+impl DeviceCategory {
+    /// From `bDeviceClass`, or `bInterfaceClass`/SubClass/Protocol. None
+    /// for codes that say nothing on their own: 0x00 (see interfaces),
+    /// 0xEF (miscellaneous) and 0xFF (vendor specific).
+    pub fn from_class_codes(class: u8, subclass: u8, protocol: u8) -> Option<Self> {
+        match (class, subclass, protocol) {
+            (0x00 | 0xFF, _, _) => None,
+            // RNDIS shows up under both wireless controller and misc.
+            (0xE0, 0x01, 0x03) | (0xEF, 0x04, 0x01) => Some(Self::Network),
+            (0xEF, _, _) => None,
+            (0x01, _, _) => Some(Self::Audio),
+            // CDC ECM, ATM, EEM, NCM and MBIM; ACM modems and serial stay Other.
+            (0x02, 0x06 | 0x07 | 0x0C..=0x0E, _) => Some(Self::Network),
+            (0x03, _, _) => Some(Self::Hid),
+            (0x08, _, _) => Some(Self::Storage),
+            (0x09, _, _) => Some(Self::Hub),
+            (0x0E | 0x10, _, _) => Some(Self::Video),
+            (0xE0, 0x01, 0x01 | 0x04) => Some(Self::Bluetooth),
+            _ => Some(Self::Other),
+        }
+    }
+
+    /// Fallback for entries without class codes (`USBSTOR\...`,
+    /// `HID\...`), from the Windows PnP class or the sysfs equivalent.
+    pub fn from_pnp_class(class: &str, name: &str) -> Self {
+        match class.to_ascii_lowercase().as_str() {
+            "diskdrive" | "cdrom" | "scsiadapter" => Self::Storage,
+            "hidclass" | "keyboard" | "mouse" => Self::Hid,
+            "media" | "audioendpoint" => Self::Audio,
+            "camera" | "image" => Self::Video,
+            "bluetooth" => Self::Bluetooth,
+            "net" => Self::Network,
+            _ if name.contains("Mass Storage") => Self::Storage,
+            _ if name.contains("Hub") => Self::Hub,
+            _ => Self::Other,
+        }
+    }
+}
+
+// ── Bus ────────────────────────────────────────────────────────
+
+/// The bus a device hangs off, going by its device ID's enumerator.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Bus {
+    #[default]
+    Usb,
+    Thunderbolt,
+    Bluetooth,
+    Pci,
+    Hid,
+    Other,
+}
+
+impl Bus {
+    pub const ALL: [Bus; 6] = [
+        Self::Usb,
+        Self::Thunderbolt,
+        Self::Bluetooth,
+        Self::Pci,
+        Self::Hid,
+        Self::Other,
+    ];
+
+    /// `USB`, `USBSTOR`, `PCI`, `BTHENUM`, ... Windows 11 lists USB4
+    /// routers under `USB4\`, which is Thunderbolt as far as we care.
+    pub fn from_enumerator(enumerator: &str) -> Self {
+        match enumerator.to_ascii_uppercase().as_str() {
+            "USB4" | "THUNDERBOLT" => Self::Thunderbolt,
+            "USB" | "USBSTOR" | "USBPRINT" => Self::Usb,
+            "PCI" => Self::Pci,
+            "BTH" | "BTHENUM" | "BTHLE" | "BTHLEDEVICE" => Self::Bluetooth,
+            "HID" => Self::Hid,
+            _ => Self::Other,
+        }
+    }
+
+    /// The name used on the command line and in serialized data.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Usb => "usb",
+            Self::Thunderbolt => "thunderbolt",
+            Self::Bluetooth => "bluetooth",
+            Self::Pci => "pci",
+            Self::Hid => "hid",
+            Self::Other => "other",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|b| b.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// Whether the VID/PID in the device ID are USB-IF codes, as opposed
+    /// to PCI-SIG vendor and device IDs.
+    pub fn has_usb_ids(self) -> bool {
+        !matches!(self, Self::Pci | Self::Thunderbolt)
+    }
+}
+
+// ── Rules ──────────────────────────────────────────────────────
+
+/// The fields a rule in `device-rules.toml` can override. Unset fields
+/// leave the device's own value alone.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuleOverride {
+    pub name: Option<String>,
+    pub category: Option<DeviceCategory>,
+    /// An emoji shown next to the device's name.
+    pub icon: Option<String>,
+    /// Treat as storage (or not) regardless of category.
+    pub storage: Option<bool>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RulesInfo {
+    pub source: String,
+    pub rules: usize,
+}
+
+// ── Link speed ─────────────────────────────────────────────────
+
+/// Negotiated USB link speed, ordered slowest first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkSpeed {
+    Low,
+    Full,
+    High,
+    Super,
+    SuperPlus,
+    SuperPlus2x2,
+}
+
+impl LinkSpeed {
+    /// Parses the sysfs `speed` attribute, which is in Mbps.
+    pub fn from_mbps(mbps: &str) -> Option<Self> {
+        match mbps.trim() {
+            "1.5" => Some(Self::Low),
+            "12" => Some(Self::Full),
+            "480" => Some(Self::High),
+            "5000" => Some(Self::Super),
+            "10000" => Some(Self::SuperPlus),
+            "20000" => Some(Self::SuperPlus2x2),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Low => "Low Speed (1.5 Mbps)",
+            Self::Full => "Full Speed (12 Mbps)",
+            Self::High => "High Speed (480 Mbps)",
+            Self::Super => "SuperSpeed (5 Gbps)",
+            Self::SuperPlus => "SuperSpeed+ (10 Gbps)",
+            Self::SuperPlus2x2 => "SuperSpeed+ 2x2 (20 Gbps)",
+        }
+    }
+
+    pub fn is_superspeed(self) -> bool {
+        self >= Self::Super
+    }
+}
+
+/// The speed a device came up at on one connection.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpeedSample {
+    pub connected_at: String,
+    pub speed: LinkSpeed,
+}
+
+// ── Power ──────────────────────────────────────────────────────
+
+/// Power figures from a device's active configuration.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DevicePower {
+    /// `bMaxPower`, already scaled to mA.
+    pub max_power_ma: u32,
+    pub self_powered: bool,
+    /// Downstream port count, for hubs.
+    pub hub_ports: Option<u8>,
+}
+
+/// What a hub's downstream ports draw against what it can supply.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HubPower {
+    pub budget_ma: u32,
+    pub used_ma: u32,
+    pub exceeded: bool,
 }
 
 // ── Storage info ───────────────────────────────────────────────
@@ -52,29 +381,124 @@ pub struct WmiDiskDrive {
     pub Status: Option<String>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct StorageInfo {
     pub model: String,
     pub serial_number: String,
     pub total_bytes: u64,
     pub interface_type: String,
     pub media_type: String,
+    pub removable: bool,
+    /// Spinning disk rather than flash. Only sysfs reports it.
+    pub rotational: bool,
+    /// `/dev/sdb`, or `\\.\PHYSICALDRIVE2` on Windows.
+    pub device_path: String,
     pub firmware: String,
     pub partition_count: u32,
     pub status: String,
     pub volumes: Vec<VolumeInfo>,
+    /// "gpt" or "mbr"; empty when the table couldn't be read.
+    pub partition_table: String,
+    /// Every partition in the table, hidden ones included.
+    pub partitions: Vec<PartitionInfo>,
+    /// Signs of a counterfeit or fake-capacity drive, one sentence each.
+    /// The engine fills these in.
+    pub warnings: Vec<String>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct VolumeInfo {
+    /// `E:` on Windows, the mount point on Linux. Empty for a partition
+    /// that isn't mounted.
     pub drive_letter: String,
     pub volume_name: String,
     pub total_bytes: u64,
     pub free_bytes: u64,
     pub file_system: String,
+    /// FAT/exFAT serial, NTFS serial or filesystem UUID.
     pub volume_serial: String,
 }
 
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PartitionInfo {
+    /// Numbered the way Linux numbers them: table slot for MBR primaries,
+    /// 5 and up for logical partitions, entry index + 1 for GPT.
+    pub number: u32,
+    /// GPT type GUID, or the MBR type byte as `0x0c`.
+    pub type_id: String,
+    /// "EFI System", "Linux filesystem", ...
+    pub type_name: String,
+    /// GPT partition name. MBR has none.
+    pub name: String,
+    pub start_bytes: u64,
+    pub size_bytes: u64,
+    pub flags: Vec<PartitionFlag>,
+    /// The filesystem in the partition, from its volume.
+    pub file_system: String,
+    pub label: String,
+    /// Where the partition's volume is mounted, if anywhere.
+    pub drive_letter: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PartitionFlag {
+    /// MBR active flag.
+    Bootable,
+    /// EFI system partition.
+    Esp,
+    Hidden,
+    Recovery,
+    ReadOnly,
+}
+
+/// A mounted volume's size and free space at one lookup.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VolumeSample {
+    pub timestamp: String,
+    /// Which filesystem this is, across drive letters and mount points.
+    pub volume_serial: String,
+    pub volume_name: String,
+    pub total_bytes: u64,
+    pub free_bytes: u64,
+}
+
+/// One volume's history and where it's heading, for `get_volume_history`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VolumeHistory {
+    pub volume_serial: String,
+    pub volume_name: String,
+    pub total_bytes: u64,
+    pub free_bytes: u64,
+    pub samples: Vec<VolumeSample>,
+    /// How fast free space has been shrinking lately. Negative while it
+    /// grows.
+    pub bytes_per_day: f64,
+    /// When the volume fills up at that rate, if it's filling at all.
+    pub days_until_full: Option<f64>,
+    /// Fuller than the device's `fill_alert`.
+    pub alert: bool,
+}
+
+/// Where the storage lookup for a drive stands.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum EnrichStatus {
+    /// Waiting for the drive to show up or mount. `attempts` lookups
+    /// have been started so far.
+    Pending {
+        attempts: u32,
+    },
+    Ok,
+    /// Gave up. Whatever was found before then is still reported.
+    Failed {
+        reason: String,
+    },
+}
+
 // ── Known device cache ─────────────────────────────────────────
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -85,6 +509,7 @@ pub struct KnownDevice {
     pub class: String,
     pub manufacturer: String,
     pub description: String,
+    /// RFC 3339 in UTC, like every timestamp the app stores.
     pub first_seen: String,
     pub last_seen: String,
     pub times_seen: u32,
@@ -93,8 +518,49 @@ pub struct KnownDevice {
     pub nickname: Option<String>,
     #[serde(default)]
     pub storage_info: Option<StorageInfo>,
+    #[serde(default)]
+    pub vendor_name: Option<String>,
+    #[serde(default)]
+    pub product_name: Option<String>,
+    #[serde(default)]
+    pub class_name: Option<String>,
+    /// The device's own serial, when its ID isn't port-derived.
+    #[serde(default)]
+    pub serial: Option<String>,
+    #[serde(default)]
+    pub bus: Bus,
+    #[serde(default)]
+    pub category: DeviceCategory,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub interfaces: Vec<DeviceInterface>,
+    #[serde(default)]
+    pub speed: Option<LinkSpeed>,
+    #[serde(default)]
+    pub usb_version: Option<String>,
+    /// Fastest the device has ever come up at.
+    #[serde(default)]
+    pub best_speed: Option<LinkSpeed>,
+    /// Every distinct capacity the drive has reported, in bytes.
+    #[serde(default)]
+    pub capacities_seen: Vec<u64>,
+    /// Speed at each recent connection, oldest first.
+    #[serde(default)]
+    pub speed_history: Vec<SpeedSample>,
+    /// Size and free space of each mounted volume over time, oldest
+    /// first.
+    #[serde(default)]
+    pub volume_history: Vec<VolumeSample>,
+    /// Warn when a volume on the drive is fuller than this percentage.
+    #[serde(default)]
+    pub fill_alert: Option<u8>,
 }
 
+/// Format of the cache file. Version 3 moved timestamps from local time
+/// with no offset to RFC 3339 in UTC; older files are migrated on load.
+pub const CACHE_VERSION: u32 = 3;
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct KnownDeviceCache {
     pub version: u32,
@@ -104,7 +570,7 @@ pub struct KnownDeviceCache {
 impl KnownDeviceCache {
     pub fn new() -> Self {
         Self {
-            version: 2,
+            version: CACHE_VERSION,
             devices: HashMap::new(),
         }
     }
@@ -114,15 +580,161 @@ impl KnownDeviceCache {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DeviceEvent {
+    /// RFC 3339 in UTC with milliseconds.
     pub timestamp: String,
-    pub kind: String, // "connect" or "disconnect"
+    /// Counts up by one per event, so events within the same millisecond
+    /// still sort in the order they happened.
+    pub seq: u64,
+    /// What happened, flattened in as a `kind` tag plus its own fields.
+    #[serde(flatten)]
+    pub kind: EventKind,
     pub name: String,
     pub vid_pid: Option<String>,
     pub manufacturer: Option<String>,
     pub class: String,
+    #[serde(default)]
+    pub bus: Bus,
+    #[serde(default)]
+    pub category: DeviceCategory,
     pub device_id: String,
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EventKind {
+    Connect,
+    /// A connect shortly after the same device left, as when a loose
+    /// cable or a failing port drops it.
+    Reconnect {
+        away_ms: u64,
+    },
+    Disconnect {
+        /// The nearest hub above the device that was over its power
+        /// budget when it left.
+        #[serde(default)]
+        over_budget: Option<OverBudget>,
+    },
+    /// An attached device was renamed, rebound or changed manufacturer.
+    AttributeChanged {
+        attribute: Attribute,
+        old: Option<String>,
+        new: Option<String>,
+    },
+    /// A drive's first lookup found its volumes.
+    StorageEnriched {
+        model: String,
+        total_bytes: u64,
+        /// Mount points or drive letters.
+        drives: Vec<String>,
+    },
+    VolumeChanged {
+        change: String,
+    },
+    /// A drive's first lookup gave up.
+    EnrichmentFailed {
+        reason: String,
+    },
+    /// Fake drives, slow ports, power budgets and fill alerts.
+    Warning {
+        message: String,
+    },
+    /// The source stopped answering. Not tied to a device.
+    MonitorError {
+        error: String,
+    },
+    /// The source answered again after a `MonitorError`.
+    MonitorResumed {
+        down_ms: u64,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Attribute {
+    Name,
+    Driver,
+    Manufacturer,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OverBudget {
+    pub hub: String,
+    pub used_ma: u32,
+    pub budget_ma: u32,
+}
+
+impl EventKind {
+    /// The serde tag: "connect", "volume_changed", ...
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::Connect => "connect",
+            EventKind::Reconnect { .. } => "reconnect",
+            EventKind::Disconnect { .. } => "disconnect",
+            EventKind::AttributeChanged { .. } => "attribute_changed",
+            EventKind::StorageEnriched { .. } => "storage_enriched",
+            EventKind::VolumeChanged { .. } => "volume_changed",
+            EventKind::EnrichmentFailed { .. } => "enrichment_failed",
+            EventKind::Warning { .. } => "warning",
+            EventKind::MonitorError { .. } => "monitor_error",
+            EventKind::MonitorResumed { .. } => "monitor_resumed",
+        }
+    }
+
+    /// One line on what happened beyond the kind itself, for the log and
+    /// the CLI. The frontend renders the same from the fields.
+    pub fn detail(&self) -> Option<String> {
+        match self {
+            EventKind::Connect | EventKind::Disconnect { over_budget: None } => None,
+            EventKind::Reconnect { away_ms } => {
+                Some(format!("Back after {:.1}s", *away_ms as f64 / 1000.0))
+            }
+            EventKind::Disconnect {
+                over_budget: Some(o),
+            } => Some(format!(
+                "{} was over its power budget ({} of {} mA)",
+                o.hub, o.used_ma, o.budget_ma
+            )),
+            EventKind::AttributeChanged {
+                attribute,
+                old,
+                new,
+            } => Some(format!(
+                "{} changed from {} to {}",
+                attribute.label(),
+                old.as_deref().unwrap_or("none"),
+                new.as_deref().unwrap_or("none")
+            )),
+            EventKind::StorageEnriched {
+                model,
+                total_bytes,
+                drives,
+            } => {
+                let mut parts = vec![model.clone(), format_bytes(*total_bytes)];
+                parts.extend(drives.iter().cloned());
+                parts.retain(|p| !p.is_empty());
+                Some(parts.join(", "))
+            }
+            EventKind::VolumeChanged { change } => Some(change.clone()),
+            EventKind::EnrichmentFailed { reason } => Some(reason.clone()),
+            EventKind::Warning { message } => Some(message.clone()),
+            EventKind::MonitorError { error } => Some(error.clone()),
+            EventKind::MonitorResumed { down_ms } => {
+                Some(format!("Back after {}s", down_ms / 1000))
+            }
+        }
+    }
+}
+
+impl Attribute {
+    pub fn label(self) -> &'static str {
+        match self {
+            Attribute::Name => "Name",
+            Attribute::Driver => "Driver",
+            Attribute::Manufacturer => "Manufacturer",
+        }
+    }
+}
+
 // ── Snapshot (sent to frontend) ────────────────────────────────
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -132,6 +744,31 @@ pub struct DeviceSnapshot {
     pub vid_pid: Option<String>,
     pub manufacturer: Option<String>,
     pub class: String,
+    pub bus: Bus,
+    pub category: DeviceCategory,
+    pub icon: Option<String>,
+    /// Names from the USB ID database, for devices that only report a
+    /// generic name like "USB Composite Device".
+    pub vendor_name: Option<String>,
+    pub product_name: Option<String>,
+    pub class_name: Option<String>,
+    pub interfaces: Vec<DeviceInterface>,
+    pub parent_id: Option<String>,
+    pub location_path: Option<String>,
+    pub speed: Option<LinkSpeed>,
+    pub usb_version: Option<String>,
+    pub power: Option<DevicePower>,
+    /// Set for hubs whose power figures are known.
+    pub hub_power: Option<HubPower>,
+    /// Set for storage devices.
+    pub enrichment: Option<EnrichStatus>,
+}
+
+/// A device and everything plugged into it, for `get_topology`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TopologyNode {
+    pub device: DeviceSnapshot,
+    pub children: Vec<TopologyNode>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -143,12 +780,27 @@ pub struct AppSnapshot {
     pub error: Option<String>,
 }
 
+// ── USB ID database ────────────────────────────────────────────
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UsbIdsInfo {
+    pub source: String,
+    pub vendors: usize,
+    pub products: usize,
+}
+
 // ── Preferences ────────────────────────────────────────────────
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Prefs {
     pub theme: String,
     pub active_tab: String,
+    /// List a composite device's interfaces under it.
+    pub expand_interfaces: bool,
+    /// Events up to this sequence number were cleared from the event log.
+    /// They stay in the journal, but aren't shown again.
+    #[serde(skip)]
+    pub events_cleared_through: u64,
 }
 
 impl Default for Prefs {
@@ -156,6 +808,8 @@ impl Default for Prefs {
         Self {
             theme: "neon".to_string(),
             active_tab: "monitor".to_string(),
+            expand_interfaces: false,
+            events_cleared_through: 0,
         }
     }
 }
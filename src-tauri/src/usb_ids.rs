@@ -0,0 +1,294 @@
+use crate::instance_id::DeviceInstanceId;
+use crate::types::Bus;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+/// A complete `usb.ids` placed here takes precedence over the bundled copy.
+const USER_IDS_FILE: &str = "usb.ids";
+
+/// Trimmed database shipped in the binary so lookups work offline.
+const BUNDLED_IDS: &str = include_str!("../usb.ids");
+
+// ── Database ───────────────────────────────────────────────────
+
+#[derive(Default)]
+struct Vendor {
+    name: String,
+    products: HashMap<u16, String>,
+}
+
+#[derive(Default)]
+struct Class {
+    name: String,
+    subclasses: HashMap<u8, String>,
+}
+
+/// Vendor, product and class names from a `usb.ids` file.
+#[derive(Default)]
+pub struct UsbIds {
+    vendors: HashMap<u16, Vendor>,
+    classes: HashMap<u8, Class>,
+    /// Where the data came from, for the UI ("bundled" or a path).
+    source: String,
+}
+
+impl UsbIds {
+    /// Parses the `usb.ids` format. Malformed lines are skipped rather
+    /// than rejected, as are the sections we don't use (AT, HID, HUT, ...).
+    pub fn parse(text: &str, source: &str) -> Self {
+        enum Section {
+            Vendors,
+            Classes,
+            Other,
+        }
+        let mut ids = Self {
+            source: source.to_string(),
+            ..Self::default()
+        };
+        let mut section = Section::Vendors;
+        let mut vendor: Option<u16> = None;
+        let mut class: Option<u8> = None;
+
+        for line in text.lines() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let depth = line.len() - line.trim_start_matches('\t').len();
+            let body = &line[depth..];
+
+            if depth == 0 {
+                vendor = None;
+                class = None;
+                if let Some(rest) = body.strip_prefix("C ") {
+                    section = Section::Classes;
+                    if let Some((code, name)) = split_entry(rest) {
+                        if let Ok(code) = u8::from_str_radix(code, 16) {
+                            ids.classes.insert(
+                                code,
+                                Class {
+                                    name: name.to_string(),
+                                    ..Class::default()
+                                },
+                            );
+                            class = Some(code);
+                        }
+                    }
+                    continue;
+                }
+                // Vendor lines are bare hex; every other section starts
+                // with a keyword (AT, HID, R, BIAS, PHY, HUT, L, HCC, VT).
+                let parsed = split_entry(body)
+                    .filter(|(code, _)| code.len() == 4)
+                    .and_then(|(code, name)| Some((u16::from_str_radix(code, 16).ok()?, name)));
+                match parsed {
+                    Some((code, name)) => {
+                        section = Section::Vendors;
+                        ids.vendors.insert(
+                            code,
+                            Vendor {
+                                name: name.to_string(),
+                                ..Vendor::default()
+                            },
+                        );
+                        vendor = Some(code);
+                    }
+                    None => section = Section::Other,
+                }
+                continue;
+            }
+
+            // Interfaces (vendors) and protocols (classes) sit at depth 2.
+            if depth != 1 {
+                continue;
+            }
+            let Some((code, name)) = split_entry(body) else {
+                continue;
+            };
+            match section {
+                Section::Vendors => {
+                    let (Some(v), Ok(pid)) = (vendor, u16::from_str_radix(code, 16)) else {
+                        continue;
+                    };
+                    if let Some(v) = ids.vendors.get_mut(&v) {
+                        v.products.insert(pid, name.to_string());
+                    }
+                }
+                Section::Classes => {
+                    let (Some(c), Ok(sub)) = (class, u8::from_str_radix(code, 16)) else {
+                        continue;
+                    };
+                    if let Some(c) = ids.classes.get_mut(&c) {
+                        c.subclasses.insert(sub, name.to_string());
+                    }
+                }
+                Section::Other => {}
+            }
+        }
+        ids
+    }
+
+    pub fn vendor_count(&self) -> usize {
+        self.vendors.len()
+    }
+
+    pub fn product_count(&self) -> usize {
+        self.vendors.values().map(|v| v.products.len()).sum()
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn vendor(&self, vid: u16) -> Option<&str> {
+        self.vendors.get(&vid).map(|v| v.name.as_str())
+    }
+
+    pub fn product(&self, vid: u16, pid: u16) -> Option<&str> {
+        self.vendors
+            .get(&vid)?
+            .products
+            .get(&pid)
+            .map(String::as_str)
+    }
+
+    /// "Human Interface Device / Boot Interface Subclass". Placeholder
+    /// subclass names ("?", "Unused") are left off.
+    pub fn class_name(&self, class: u8, subclass: u8) -> Option<String> {
+        let c = self.classes.get(&class)?;
+        match c.subclasses.get(&subclass).map(String::as_str) {
+            Some(sub) if !matches!(sub, "?" | "Unused" | "No Subclass") => {
+                Some(format!("{} / {}", c.name, sub))
+            }
+            _ => Some(c.name.clone()),
+        }
+    }
+
+    /// Vendor and product names for a device instance ID. PCI and
+    /// Thunderbolt IDs are from a different registry and aren't looked up.
+    pub fn lookup(&self, id: &DeviceInstanceId) -> (Option<String>, Option<String>) {
+        let (Some(vid), Some(pid)) = (id.vid, id.pid) else {
+            return (None, None);
+        };
+        if !Bus::from_enumerator(&id.enumerator).has_usb_ids() {
+            return (None, None);
+        }
+        (
+            self.vendor(vid).map(str::to_string),
+            self.product(vid, pid).map(str::to_string),
+        )
+    }
+}
+
+/// Splits `"046d  Logitech, Inc."` into code and name.
+fn split_entry(s: &str) -> Option<(&str, &str)> {
+    let (code, name) = s.split_once(char::is_whitespace)?;
+    let name = name.trim();
+    (!code.is_empty() && !name.is_empty()).then_some((code, name))
+}
+
+// ── Process-wide copy ──────────────────────────────────────────
+
+fn slot() -> &'static RwLock<Arc<UsbIds>> {
+    static IDS: OnceLock<RwLock<Arc<UsbIds>>> = OnceLock::new();
+    IDS.get_or_init(|| {
+        let ids = std::fs::read_to_string(USER_IDS_FILE)
+            .ok()
+            .map(|text| UsbIds::parse(&text, USER_IDS_FILE))
+            .filter(|ids| ids.vendor_count() > 0)
+            .unwrap_or_else(|| UsbIds::parse(BUNDLED_IDS, "bundled"));
+        RwLock::new(Arc::new(ids))
+    })
+}
+
+/// The database currently in use. Cheap to call; callers can compare the
+/// returned `Arc`s to notice a reload.
+pub fn current() -> Arc<UsbIds> {
+    slot().read().clone()
+}
+
+/// Replaces the database with the file at `path`, or re-reads the user
+/// file when no path is given. A file from elsewhere is copied next to
+/// the app so it's picked up again on the next start.
+pub fn reload(path: Option<&Path>) -> Result<Arc<UsbIds>, String> {
+    let path = path.unwrap_or(Path::new(USER_IDS_FILE));
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+    let ids = UsbIds::parse(&text, &path.display().to_string());
+    if ids.vendor_count() == 0 {
+        return Err(format!("{} has no vendor entries", path.display()));
+    }
+    if path != Path::new(USER_IDS_FILE) {
+        std::fs::write(USER_IDS_FILE, &text)
+            .map_err(|e| format!("Can't save {}: {}", USER_IDS_FILE, e))?;
+    }
+    let ids = Arc::new(ids);
+    *slot().write() = ids.clone();
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDS: &str = "\
+# usb.ids excerpt
+#\tsyntax: vendor  vendor_name
+046d  Logitech, Inc.
+\tc52b  Unifying Receiver
+\t\t00  Keyboard interface
+# a comment between products
+\tc534  Nano Receiver
+0781  SanDisk Corp.
+\t5581  Ultra
+
+C 03  Human Interface Device
+\t01  Boot Interface Subclass
+\t\t01  Keyboard
+\t00  No Subclass
+C 08  Mass Storage
+\t06  SCSI
+AT 0409  English (US)
+HUT 01  Generic Desktop Controls
+\t002  Mouse
+";
+
+    #[test]
+    fn parses_vendors_products_and_classes() {
+        let ids = UsbIds::parse(IDS, "test");
+        assert_eq!(ids.source(), "test");
+        assert_eq!(ids.vendor_count(), 2);
+        assert_eq!(ids.product_count(), 3);
+        assert_eq!(ids.vendor(0x046d), Some("Logitech, Inc."));
+        assert_eq!(ids.product(0x046d, 0xc52b), Some("Unifying Receiver"));
+        assert_eq!(ids.product(0x046d, 0xc534), Some("Nano Receiver"));
+        assert_eq!(ids.product(0x0781, 0x5581), Some("Ultra"));
+        // Interfaces nest a level deeper and aren't products.
+        assert_eq!(ids.product(0x046d, 0x0000), None);
+
+        assert_eq!(
+            ids.class_name(0x03, 0x01).as_deref(),
+            Some("Human Interface Device / Boot Interface Subclass")
+        );
+        assert_eq!(
+            ids.class_name(0x03, 0x00).as_deref(),
+            Some("Human Interface Device")
+        );
+        assert_eq!(
+            ids.class_name(0x08, 0x06).as_deref(),
+            Some("Mass Storage / SCSI")
+        );
+        assert_eq!(ids.class_name(0x09, 0x00), None);
+    }
+
+    #[test]
+    fn later_sections_are_not_vendors() {
+        let ids = UsbIds::parse(IDS, "test");
+        // "HUT 01" and "AT 0409" start sections of their own, and their
+        // entries don't land on the last vendor or class.
+        assert_eq!(ids.vendor(0x0409), None);
+        assert_eq!(ids.product(0x0781, 0x0002), None);
+        assert_eq!(ids.class_name(0x08, 0x02).as_deref(), Some("Mass Storage"));
+    }
+}
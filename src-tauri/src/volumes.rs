@@ -0,0 +1,461 @@
+use crate::engine::{format_iso, parse_iso};
+use crate::storage::format_bytes;
+use crate::types::{KnownDevice, VolumeHistory, VolumeInfo, VolumeSample};
+use chrono::{DateTime, Local, TimeDelta};
+
+/// Samples of a volume are kept at least this far apart. Lookups in
+/// between update the newest one, so a drive left plugged in adds one
+/// sample an hour.
+const SAMPLE_SPACING: TimeDelta = TimeDelta::hours(1);
+
+/// Samples kept per device, across all its volumes.
+const HISTORY_LEN: usize = 2000;
+
+/// How far back the fill rate is worked out from.
+const TREND_WINDOW: TimeDelta = TimeDelta::days(30);
+
+/// What changed between two lookups of the same drive's volumes, one
+/// sentence each for the event log. Free space on its own isn't a change.
+pub fn changes(old: &[VolumeInfo], new: &[VolumeInfo]) -> Vec<String> {
+    let mut old: Vec<&VolumeInfo> = old.iter().collect();
+    let mut added = Vec::new();
+    let mut found = Vec::new();
+    for vol in new {
+        match old.iter().position(|o| same_volume(o, vol)) {
+            Some(i) => found.extend(compare(old.remove(i), vol)),
+            None => added.push(vol),
+        }
+    }
+    let mut changes: Vec<String> = old
+        .into_iter()
+        .map(|o| format!("Volume removed: {}", name(o)))
+        .collect();
+    changes.extend(added.into_iter().map(|v| {
+        format!(
+            "Volume added: {}, {} {}",
+            name(v),
+            v.file_system,
+            format_bytes(v.total_bytes)
+        )
+    }));
+    changes.extend(found);
+    changes
+}
+
+/// The serial says which filesystem it is; failing that, a volume
+/// mounted where one was before, or one the same size and type, is taken
+/// to be the same partition.
+fn same_volume(old: &VolumeInfo, new: &VolumeInfo) -> bool {
+    if !new.volume_serial.is_empty() && old.volume_serial == new.volume_serial {
+        return true;
+    }
+    if !new.drive_letter.is_empty() && old.drive_letter == new.drive_letter {
+        return true;
+    }
+    old.total_bytes == new.total_bytes && old.file_system == new.file_system
+}
+
+fn compare(old: &VolumeInfo, new: &VolumeInfo) -> Vec<String> {
+    let mut changes = Vec::new();
+    let reformatted = old.file_system != new.file_system
+        || (!old.volume_serial.is_empty()
+            && !new.volume_serial.is_empty()
+            && old.volume_serial != new.volume_serial);
+    if reformatted {
+        changes.push(if old.file_system == new.file_system {
+            format!("{} reformatted ({})", name(new), new.file_system)
+        } else {
+            format!(
+                "{} reformatted from {} to {}",
+                name(new),
+                old.file_system,
+                new.file_system
+            )
+        });
+    } else if old.volume_name != new.volume_name {
+        changes.push(format!(
+            "{} relabelled from {} to {}",
+            place(&new.drive_letter),
+            label(&old.volume_name),
+            label(&new.volume_name)
+        ));
+    }
+    if old.drive_letter != new.drive_letter {
+        let what = match new.volume_name.as_str() {
+            "" => format!("{} volume", new.file_system),
+            name => format!("\"{}\"", name),
+        };
+        changes.push(format!(
+            "{} moved from {} to {}",
+            what,
+            place(&old.drive_letter),
+            place(&new.drive_letter)
+        ));
+    }
+    changes
+}
+
+/// `E: "BACKUP"`, or whichever half of that the volume has.
+fn name(vol: &VolumeInfo) -> String {
+    match (vol.drive_letter.is_empty(), vol.volume_name.is_empty()) {
+        (false, false) => format!("{} \"{}\"", vol.drive_letter, vol.volume_name),
+        (false, true) => vol.drive_letter.clone(),
+        (true, false) => format!("\"{}\"", vol.volume_name),
+        (true, true) => format!("unlabelled {} volume", vol.file_system),
+    }
+}
+
+fn place(drive_letter: &str) -> &str {
+    if drive_letter.is_empty() {
+        "unmounted"
+    } else {
+        drive_letter
+    }
+}
+
+fn label(name: &str) -> String {
+    if name.is_empty() {
+        "no label".to_string()
+    } else {
+        format!("\"{}\"", name)
+    }
+}
+
+// ── Free space history ─────────────────────────────────────────
+
+/// Adds a sample to `history` for each mounted volume that has a serial
+/// to tell it by.
+pub fn record(history: &mut Vec<VolumeSample>, volumes: &[VolumeInfo], now: DateTime<Local>) {
+    let mounted = volumes
+        .iter()
+        .filter(|v| !v.drive_letter.is_empty() && !v.volume_serial.is_empty() && v.total_bytes > 0);
+    for vol in mounted {
+        let sample = VolumeSample {
+            timestamp: format_iso(now),
+            volume_serial: vol.volume_serial.clone(),
+            volume_name: vol.volume_name.clone(),
+            total_bytes: vol.total_bytes,
+            free_bytes: vol.free_bytes,
+        };
+        let mut earlier = history
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, s)| s.volume_serial == vol.volume_serial)
+            .map(|(i, _)| i);
+        let (newest, before) = (earlier.next(), earlier.next());
+        let recent = before
+            .and_then(|i| parse_iso(&history[i].timestamp))
+            .is_some_and(|t| now - t < SAMPLE_SPACING);
+        match newest {
+            Some(i) if recent => history[i] = sample,
+            _ => history.push(sample),
+        }
+    }
+    if history.len() > HISTORY_LEN {
+        history.drain(..history.len() - HISTORY_LEN);
+    }
+}
+
+/// Each volume the device has had, most recently seen first, with its
+/// fill rate and how long until it's full.
+pub fn history(kd: &KnownDevice) -> Vec<VolumeHistory> {
+    let mut serials: Vec<&str> = Vec::new();
+    for sample in kd.volume_history.iter().rev() {
+        if !serials.contains(&sample.volume_serial.as_str()) {
+            serials.push(&sample.volume_serial);
+        }
+    }
+    serials
+        .into_iter()
+        .filter_map(|serial| {
+            let samples: Vec<VolumeSample> = kd
+                .volume_history
+                .iter()
+                .filter(|s| s.volume_serial == serial)
+                .cloned()
+                .collect();
+            let latest = samples.last()?.clone();
+            let bytes_per_day = fill_rate(&samples);
+            Some(VolumeHistory {
+                volume_serial: latest.volume_serial,
+                volume_name: latest.volume_name,
+                total_bytes: latest.total_bytes,
+                free_bytes: latest.free_bytes,
+                days_until_full: (bytes_per_day > 0.0)
+                    .then(|| latest.free_bytes as f64 / bytes_per_day),
+                bytes_per_day,
+                alert: kd.fill_alert.is_some_and(|p| {
+                    used_percent(latest.total_bytes, latest.free_bytes) >= p as f64
+                }),
+                samples,
+            })
+        })
+        .collect()
+}
+
+/// Bytes of free space lost per day, from a least-squares line through
+/// the samples in the trend window. Zero until the samples span an hour.
+fn fill_rate(samples: &[VolumeSample]) -> f64 {
+    let points: Vec<(DateTime<Local>, f64)> = samples
+        .iter()
+        .filter_map(|s| Some((parse_iso(&s.timestamp)?, s.free_bytes as f64)))
+        .collect();
+    let Some(&(latest, _)) = points.last() else {
+        return 0.0;
+    };
+    let points: Vec<(f64, f64)> = points
+        .into_iter()
+        .filter(|(t, _)| latest - *t <= TREND_WINDOW)
+        .map(|(t, free)| ((t - latest).num_seconds() as f64 / 86_400.0, free))
+        .collect();
+    let span = points.first().map_or(0.0, |(days, _)| -days);
+    if span * 24.0 < 1.0 {
+        return 0.0;
+    }
+    let n = points.len() as f64;
+    let mean_t = points.iter().map(|(t, _)| t).sum::<f64>() / n;
+    let mean_free = points.iter().map(|(_, f)| f).sum::<f64>() / n;
+    let (mut cov, mut var) = (0.0, 0.0);
+    for (t, free) in &points {
+        cov += (t - mean_t) * (free - mean_free);
+        var += (t - mean_t) * (t - mean_t);
+    }
+    -cov / var
+}
+
+/// Mounted volumes that have just gone over `percent` full, one sentence
+/// each. A volume that was already over it at the last lookup isn't
+/// reported again.
+pub fn fill_alerts(old: &[VolumeInfo], new: &[VolumeInfo], percent: u8) -> Vec<String> {
+    let over = |v: &VolumeInfo| {
+        !v.drive_letter.is_empty()
+            && v.total_bytes > 0
+            && used_percent(v.total_bytes, v.free_bytes) >= percent as f64
+    };
+    new.iter()
+        .filter(|v| over(v) && !old.iter().any(|o| same_volume(o, v) && over(o)))
+        .map(|v| {
+            format!(
+                "{} is {:.0}% full (alert at {}%)",
+                name(v),
+                used_percent(v.total_bytes, v.free_bytes),
+                percent
+            )
+        })
+        .collect()
+}
+
+fn used_percent(total: u64, free: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    total.saturating_sub(free) as f64 * 100.0 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    const GIB: u64 = 1 << 30;
+
+    fn at(hours: f64) -> DateTime<Local> {
+        let start = Utc.with_ymd_and_hms(2024, 3, 9, 14, 0, 0).unwrap();
+        (start + TimeDelta::seconds((hours * 3600.0) as i64)).with_timezone(&Local)
+    }
+
+    fn volume(letter: &str, label: &str, fs: &str, serial: &str) -> VolumeInfo {
+        VolumeInfo {
+            drive_letter: letter.to_string(),
+            volume_name: label.to_string(),
+            total_bytes: 32 * GIB,
+            free_bytes: 16 * GIB,
+            file_system: fs.to_string(),
+            volume_serial: serial.to_string(),
+        }
+    }
+
+    fn sample(hours: f64, serial: &str, free: u64) -> VolumeSample {
+        VolumeSample {
+            timestamp: format_iso(at(hours)),
+            volume_serial: serial.to_string(),
+            volume_name: String::new(),
+            total_bytes: 32 * GIB,
+            free_bytes: free,
+        }
+    }
+
+    #[test]
+    fn free_space_alone_is_no_change() {
+        let old = [volume("E:", "STICK", "FAT32", "1A2B-3C4D")];
+        let mut new = old.clone();
+        new[0].free_bytes = GIB;
+        assert!(changes(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn reformats() {
+        let old = [volume("E:", "STICK", "FAT32", "1A2B-3C4D")];
+        let new = [volume("E:", "STICK", "exFAT", "5E6F-7A8B")];
+        assert_eq!(
+            changes(&old, &new),
+            ["E: \"STICK\" reformatted from FAT32 to exFAT"]
+        );
+
+        // Same filesystem, new serial, and a new label that isn't
+        // reported separately.
+        let new = [volume("E:", "NEW", "FAT32", "5E6F-7A8B")];
+        assert_eq!(changes(&old, &new), ["E: \"NEW\" reformatted (FAT32)"]);
+    }
+
+    #[test]
+    fn relabels() {
+        let old = [volume("E:", "STICK", "FAT32", "1A2B-3C4D")];
+        let new = [volume("E:", "", "FAT32", "1A2B-3C4D")];
+        assert_eq!(
+            changes(&old, &new),
+            ["E: relabelled from \"STICK\" to no label"]
+        );
+        assert_eq!(
+            changes(&new, &old),
+            ["E: relabelled from no label to \"STICK\""]
+        );
+    }
+
+    #[test]
+    fn moves() {
+        let old = [volume("E:", "STICK", "FAT32", "1A2B-3C4D")];
+        let new = [volume("F:", "STICK", "FAT32", "1A2B-3C4D")];
+        assert_eq!(changes(&old, &new), ["\"STICK\" moved from E: to F:"]);
+
+        // Unmounted, and without a serial to go by: the size and type
+        // still match.
+        let old = [volume("/media/stick", "", "ext4", "")];
+        let new = [volume("", "", "ext4", "")];
+        assert_eq!(
+            changes(&old, &new),
+            ["ext4 volume moved from /media/stick to unmounted"]
+        );
+    }
+
+    #[test]
+    fn volumes_added_and_removed() {
+        let old = [
+            volume("E:", "BOOT", "FAT32", "1111-1111"),
+            volume("F:", "", "NTFS", "2222222222222222"),
+        ];
+        let mut data = volume("G:", "DATA", "exFAT", "3333-3333");
+        data.total_bytes = 64 * GIB;
+        let new = [old[0].clone(), data];
+        assert_eq!(
+            changes(&old, &new),
+            [
+                "Volume removed: F:",
+                "Volume added: G: \"DATA\", exFAT 64.00 GB"
+            ]
+        );
+    }
+
+    #[test]
+    fn samples_are_an_hour_apart() {
+        let mut vol = volume("E:", "STICK", "FAT32", "1A2B-3C4D");
+        let mut history = Vec::new();
+        let mut lookup = |hours: f64, free: u64, history: &mut Vec<VolumeSample>| {
+            vol.free_bytes = free;
+            record(history, std::slice::from_ref(&vol), at(hours));
+        };
+        lookup(0.0, 20 * GIB, &mut history);
+        lookup(0.25, 19 * GIB, &mut history);
+        // Within the hour: the newest sample is updated instead.
+        lookup(0.5, 18 * GIB, &mut history);
+        lookup(1.1, 17 * GIB, &mut history);
+        lookup(3.0, 16 * GIB, &mut history);
+        let kept: Vec<(String, u64)> = history
+            .iter()
+            .map(|s| (s.timestamp.clone(), s.free_bytes / GIB))
+            .collect();
+        assert_eq!(
+            kept,
+            [
+                (format_iso(at(0.0)), 20),
+                (format_iso(at(0.5)), 18),
+                (format_iso(at(1.1)), 17),
+                (format_iso(at(3.0)), 16),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_mounted_volumes_with_serials_are_sampled() {
+        let mut history = Vec::new();
+        let mut empty = volume("E:", "", "FAT32", "0000-0001");
+        empty.total_bytes = 0;
+        let volumes = [
+            volume("E:", "", "FAT32", "1A2B-3C4D"),
+            volume("", "", "FAT32", "5E6F-7A8B"),
+            volume("F:", "", "ext4", ""),
+            empty,
+        ];
+        record(&mut history, &volumes, at(0.0));
+        let serials: Vec<&str> = history.iter().map(|s| s.volume_serial.as_str()).collect();
+        assert_eq!(serials, ["1A2B-3C4D"]);
+    }
+
+    #[test]
+    fn history_is_capped() {
+        let vol = volume("E:", "", "FAT32", "1A2B-3C4D");
+        let mut history: Vec<VolumeSample> = (0..HISTORY_LEN)
+            .map(|i| sample(i as f64, "1A2B-3C4D", GIB))
+            .collect();
+        record(&mut history, &[vol], at(HISTORY_LEN as f64));
+        assert_eq!(history.len(), HISTORY_LEN);
+        assert_eq!(history[0].timestamp, format_iso(at(1.0)));
+    }
+
+    #[test]
+    fn fill_rates() {
+        // A gigabyte a day, sampled every six hours.
+        let filling: Vec<VolumeSample> = (0..=12)
+            .map(|i| sample(i as f64 * 6.0, "A", 20 * GIB - i * GIB / 4))
+            .collect();
+        let rate = fill_rate(&filling);
+        assert!((rate - GIB as f64).abs() < 1.0, "{}", rate);
+
+        // Freeing space is a negative rate.
+        let freeing: Vec<VolumeSample> = (0..=4)
+            .map(|i| sample(i as f64 * 24.0, "A", GIB + i * GIB))
+            .collect();
+        assert!((fill_rate(&freeing) + GIB as f64).abs() < 1.0);
+
+        // Under an hour of samples says nothing yet.
+        assert_eq!(fill_rate(&[]), 0.0);
+        assert_eq!(fill_rate(&[sample(0.0, "A", GIB)]), 0.0);
+        let short = [sample(0.0, "A", 2 * GIB), sample(0.5, "A", GIB)];
+        assert_eq!(fill_rate(&short), 0.0);
+    }
+
+    #[test]
+    fn fill_rate_looks_at_the_last_month() {
+        // Filling fast two months ago, steady for the last month.
+        let mut samples: Vec<VolumeSample> = (0..10)
+            .map(|i| sample(i as f64 * 24.0, "A", 30 * GIB - i * GIB))
+            .collect();
+        samples.extend((0..=30).map(|day| sample((30.0 + day as f64) * 24.0, "A", 10 * GIB)));
+        assert_eq!(fill_rate(&samples), 0.0);
+    }
+
+    #[test]
+    fn fill_alerts_fire_once() {
+        let mut low = volume("E:", "STICK", "FAT32", "1A2B-3C4D");
+        low.free_bytes = 8 * GIB;
+        let mut full = low.clone();
+        full.free_bytes = 2 * GIB;
+        assert_eq!(
+            fill_alerts(&[low.clone()], &[full.clone()], 90),
+            ["E: \"STICK\" is 94% full (alert at 90%)"]
+        );
+        assert!(fill_alerts(&[full.clone()], &[full.clone()], 90).is_empty());
+        assert!(fill_alerts(&[], &[low], 90).is_empty());
+    }
+}
@@ -1,5 +1,5 @@
 import { invoke } from "@tauri-apps/api/core";
-import type { AppSnapshot, Prefs } from "../types";
+import type { AppSnapshot, DeviceDetails, Prefs, RulesInfo, TopologyNode, UsbIdsInfo, VolumeHistory } from "../types";
 
 export async function getSnapshot(): Promise<AppSnapshot> {
   return invoke("get_snapshot");
@@ -13,10 +13,38 @@ export async function forgetDevice(deviceId: string): Promise<void> {
   return invoke("forget_device", { deviceId });
 }
 
+export async function refreshStorage(deviceId: string): Promise<void> {
+  return invoke("refresh_storage", { deviceId });
+}
+
+export async function getVolumeHistory(deviceId: string): Promise<VolumeHistory[]> {
+  return invoke("get_volume_history", { deviceId });
+}
+
+export async function setFillAlert(deviceId: string, percent: number | null): Promise<void> {
+  return invoke("set_fill_alert", { deviceId, percent });
+}
+
 export async function clearEvents(): Promise<void> {
   return invoke("clear_events");
 }
 
+export async function getTopology(): Promise<TopologyNode[]> {
+  return invoke("get_topology");
+}
+
+export async function getDeviceDetails(deviceId: string): Promise<DeviceDetails> {
+  return invoke("get_device_details", { deviceId });
+}
+
+export async function reloadUsbIds(path?: string): Promise<UsbIdsInfo> {
+  return invoke("reload_usb_ids", { path: path ?? null });
+}
+
+export async function reloadRules(): Promise<RulesInfo> {
+  return invoke("reload_rules");
+}
+
 export async function getPrefs(): Promise<Prefs> {
   return invoke("get_prefs");
 }
@@ -29,6 +57,10 @@ export async function setTab(tab: string): Promise<void> {
   return invoke("set_tab", { tab });
 }
 
+export async function setExpandInterfaces(expand: boolean): Promise<void> {
+  return invoke("set_expand_interfaces", { expand });
+}
+
 export async function checkForUpdates(): Promise<string | null> {
   return invoke("check_for_updates");
 }
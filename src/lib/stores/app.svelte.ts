@@ -1,12 +1,14 @@
 import { listen } from "@tauri-apps/api/event";
 import * as cmd from "../commands";
-import { deviceClassCategory, type DeviceClassFilter } from "../utils";
+import { EVENT_TONES, eventDetail } from "../utils";
+import type { DeviceClassFilter } from "../utils";
 import type {
   AppSnapshot,
   DeviceEvent,
   DeviceSnapshot,
   KnownDevice,
   StorageInfo,
+  VolumeHistory,
 } from "../types";
 
 export type SortMode = "status" | "name" | "last_seen" | "times_seen" | "first_seen";
@@ -24,9 +26,14 @@ class AppState {
   activeTab = $state<"monitor" | "known">("monitor");
   classFilter = $state<DeviceClassFilter>("All");
   soundEnabled = $state(false);
+  expandInterfaces = $state(false);
   isLoading = $state(true);
   searchQuery = $state("");
   sortMode = $state<SortMode>("status");
+  /** `lsusb -v` text for the selected device, once asked for. */
+  deviceDetails = $state<string | null>(null);
+  /** Free space history of the selected drive's volumes. */
+  volumeHistory = $state<VolumeHistory[]>([]);
   sortAscending = $state(true);
   selectedDevice = $state<string | null>(null);
   nicknameBuf = $state("");
@@ -41,7 +48,7 @@ class AppState {
   // Derived: filtered events by class
   get filteredEvents(): DeviceEvent[] {
     if (this.classFilter === "All") return this.events;
-    return this.events.filter(e => deviceClassCategory(e.class) === this.classFilter);
+    return this.events.filter(e => e.category === this.classFilter);
   }
 
   // Derived: filtered + sorted known devices
@@ -56,13 +63,15 @@ class AppState {
           d.device_id.toLowerCase().includes(q) ||
           d.class.toLowerCase().includes(q) ||
           d.manufacturer.toLowerCase().includes(q) ||
+          (d.vendor_name ?? "").toLowerCase().includes(q) ||
+          (d.product_name ?? "").toLowerCase().includes(q) ||
           d.vid_pid.toLowerCase().includes(q) ||
           (d.nickname ?? "").toLowerCase().includes(q)
       );
     }
 
     if (this.classFilter !== "All") {
-      list = list.filter(d => deviceClassCategory(d.class) === this.classFilter);
+      list = list.filter(d => d.category === this.classFilter);
     }
 
     const mode = this.sortMode;
@@ -124,6 +133,7 @@ class AppState {
       const validThemes = ["neon", "dracula", "mocha"];
       this.theme = validThemes.includes(prefs.theme) ? prefs.theme : "neon";
       this.activeTab = prefs.active_tab === "known" ? "known" : "monitor";
+      this.expandInterfaces = prefs.expand_interfaces;
     } catch (e) {
       console.error("Failed to load prefs:", e);
     }
@@ -159,9 +169,21 @@ class AppState {
         } catch {}
       }
       for (const evt of newEvents.slice(-3)) {
-        const icon = evt.kind === "connect" ? "\u{1F50C}" : "\u23CF\uFE0F";
-        const verb = evt.kind === "connect" ? "Connected" : "Disconnected";
-        this.notify(`${icon} ${verb}: ${evt.name || "USB Device"}`, evt.kind === "connect" ? "success" : "error");
+        const name = evt.name || "USB Device";
+        switch (EVENT_TONES[evt.kind]) {
+          case "warning":
+            this.notify(`\u26A0\uFE0F ${name}: ${eventDetail(evt)}`, "warning");
+            break;
+          case "info":
+            this.notify(`${name}: ${eventDetail(evt)}`, "info");
+            break;
+          case "connect":
+            this.notify(`\u{1F50C} ${evt.kind === "reconnect" ? "Reconnected" : "Connected"}: ${name}`, "success");
+            break;
+          case "disconnect":
+            this.notify(`\u23CF\uFE0F Disconnected: ${name}`, "error");
+            break;
+        }
       }
     });
   }
@@ -181,6 +203,24 @@ class AppState {
       this.nicknameBuf = "";
     }
     this.selectedDevice = id;
+    this.deviceDetails = null;
+  }
+
+  async loadDeviceDetails(id: string) {
+    try {
+      const details = await cmd.getDeviceDetails(id);
+      this.deviceDetails = details.text;
+    } catch (e) {
+      this.notify(`${e}`, "error");
+    }
+  }
+
+  async loadVolumeHistory(id: string) {
+    try {
+      this.volumeHistory = await cmd.getVolumeHistory(id);
+    } catch {
+      this.volumeHistory = [];
+    }
   }
 
   async saveNickname() {
@@ -207,11 +247,49 @@ class AppState {
     this.notify("Device forgotten", "info");
   }
 
+  async refreshStorage(id: string) {
+    await cmd.refreshStorage(id);
+    this.notify("Reading drive details again", "info");
+  }
+
+  async setFillAlert(id: string, percent: number | null) {
+    try {
+      await cmd.setFillAlert(id, percent);
+    } catch (e) {
+      this.notify(`${e}`, "error");
+      return;
+    }
+    const dev = this.knownDevices[id];
+    if (dev) {
+      dev.fill_alert = percent;
+      this.knownDevices = { ...this.knownDevices };
+    }
+    this.notify(percent === null ? "Fill alert off" : `Alert when ${percent}% full`, "success");
+  }
+
   async clearEvents() {
     await cmd.clearEvents();
     this.events = [];
   }
 
+  async reloadUsbIds() {
+    try {
+      const info = await cmd.reloadUsbIds();
+      this.notify(`USB IDs reloaded: ${info.vendors} vendors, ${info.products} products`, "success");
+    } catch (e) {
+      this.notify(`${e}`, "error");
+    }
+  }
+
+  async reloadRules() {
+    try {
+      const info = await cmd.reloadRules();
+      this.notify(`Rules reloaded: ${info.rules} from ${info.source}`, "success");
+    } catch (e) {
+      this.notify(`${e}`, "error");
+    }
+  }
+
   async setTheme(id: string) {
     this.theme = id;
     await cmd.setTheme(id);
@@ -240,10 +318,15 @@ class AppState {
     this.soundEnabled = !this.soundEnabled;
   }
 
+  async toggleExpandInterfaces() {
+    this.expandInterfaces = !this.expandInterfaces;
+    await cmd.setExpandInterfaces(this.expandInterfaces);
+  }
+
   exportEventsCSV() {
-    const header = "Timestamp,Event,Name,VID:PID,Class,Manufacturer,DeviceID";
+    const header = "Timestamp,Event,Name,VID:PID,Class,Manufacturer,DeviceID,Detail";
     const rows = this.events.map(e =>
-      `"${e.timestamp}","${e.kind}","${e.name || ""}","${e.vid_pid || ""}","${e.class || ""}","${e.manufacturer || ""}","${e.device_id}"`
+      `"${e.timestamp}","${e.kind}","${e.name || ""}","${e.vid_pid || ""}","${e.class || ""}","${e.manufacturer || ""}","${e.device_id}","${(eventDetail(e) ?? "").replaceAll('"', '""')}"`
     );
     const csv = [header, ...rows].join("\n");
     const blob = new Blob([csv], { type: "text/csv" });
@@ -13,10 +13,38 @@ export interface StorageInfo {
   total_bytes: number;
   interface_type: string;
   media_type: string;
+  removable: boolean;
+  /** Spinning disk rather than flash; only known on Linux. */
+  rotational: boolean;
+  /** `/dev/sdb` or `\\.\PHYSICALDRIVE2`. */
+  device_path: string;
   firmware: string;
   partition_count: number;
   status: string;
   volumes: VolumeInfo[];
+  /** "gpt" or "mbr"; empty when the table couldn't be read. */
+  partition_table: string;
+  partitions: PartitionInfo[];
+  /** Signs of a counterfeit or fake-capacity drive. */
+  warnings: string[];
+}
+
+export type PartitionFlag = "bootable" | "esp" | "hidden" | "recovery" | "read_only";
+
+export interface PartitionInfo {
+  number: number;
+  /** GPT type GUID or MBR type byte (`0x0c`). */
+  type_id: string;
+  type_name: string;
+  /** GPT partition name. */
+  name: string;
+  start_bytes: number;
+  size_bytes: number;
+  flags: PartitionFlag[];
+  file_system: string;
+  label: string;
+  /** Mount point or drive letter of its volume, if mounted. */
+  drive_letter: string;
 }
 
 export interface KnownDevice {
@@ -24,25 +52,109 @@ export interface KnownDevice {
   name: string;
   vid_pid: string;
   class: string;
+  bus: Bus;
+  category: DeviceCategory;
+  /** Set by a rule in `device-rules.toml`. */
+  icon: string | null;
   manufacturer: string;
   description: string;
+  /** RFC 3339 in UTC, like every timestamp from the backend. */
   first_seen: string;
   last_seen: string;
   times_seen: number;
   currently_connected: boolean;
   nickname: string | null;
   storage_info: StorageInfo | null;
+  vendor_name: string | null;
+  product_name: string | null;
+  class_name: string | null;
+  serial: string | null;
+  interfaces: DeviceInterface[];
+  speed: LinkSpeed | null;
+  usb_version: string | null;
+  best_speed: LinkSpeed | null;
+  /** Every distinct capacity the drive has reported, in bytes. */
+  capacities_seen: number[];
+  speed_history: SpeedSample[];
+  /** Size and free space of each mounted volume over time, oldest first. */
+  volume_history: VolumeSample[];
+  /** Warn when a volume is fuller than this percentage. */
+  fill_alert: number | null;
 }
 
-export interface DeviceEvent {
+export interface VolumeSample {
   timestamp: string;
-  kind: "connect" | "disconnect";
+  volume_serial: string;
+  volume_name: string;
+  total_bytes: number;
+  free_bytes: number;
+}
+
+export interface VolumeHistory {
+  volume_serial: string;
+  volume_name: string;
+  total_bytes: number;
+  free_bytes: number;
+  samples: VolumeSample[];
+  /** Negative while free space grows. */
+  bytes_per_day: number;
+  days_until_full: number | null;
+  alert: boolean;
+}
+
+/** The bus a device is on, from its device ID's enumerator. */
+export type Bus = "usb" | "thunderbolt" | "bluetooth" | "pci" | "hid" | "other";
+
+/** Broad device kind, from USB class codes with the PnP class as fallback. */
+export type DeviceCategory = "storage" | "bluetooth" | "network" | "video" | "audio" | "hid" | "hub" | "other";
+
+export type LinkSpeed = "low" | "full" | "high" | "super" | "super_plus" | "super_plus2x2";
+
+export interface SpeedSample {
+  connected_at: string;
+  speed: LinkSpeed;
+}
+
+export interface DeviceInterface {
+  device_id: string;
+  number: number | null;
+  name: string;
+  class: string;
+  category: DeviceCategory;
+}
+
+/** What happened, tagged by `kind`; each kind brings its own fields. */
+export type EventKind =
+  | { kind: "connect" }
+  | { kind: "reconnect"; away_ms: number }
+  | { kind: "disconnect"; over_budget: OverBudget | null }
+  | { kind: "attribute_changed"; attribute: "name" | "driver" | "manufacturer"; old: string | null; new: string | null }
+  | { kind: "storage_enriched"; model: string; total_bytes: number; drives: string[] }
+  | { kind: "volume_changed"; change: string }
+  | { kind: "enrichment_failed"; reason: string }
+  | { kind: "warning"; message: string }
+  | { kind: "monitor_error"; error: string }
+  | { kind: "monitor_resumed"; down_ms: number };
+
+export interface OverBudget {
+  hub: string;
+  used_ma: number;
+  budget_ma: number;
+}
+
+/** Monitor events have an empty `device_id`. */
+export type DeviceEvent = EventKind & {
+  timestamp: string;
+  /** Counts up by one per event. */
+  seq: number;
   name: string;
   vid_pid: string | null;
   manufacturer: string | null;
   class: string;
+  bus: Bus;
+  category: DeviceCategory;
   device_id: string;
-}
+};
 
 export interface DeviceSnapshot {
   device_id: string;
@@ -50,6 +162,120 @@ export interface DeviceSnapshot {
   vid_pid: string | null;
   manufacturer: string | null;
   class: string;
+  bus: Bus;
+  category: DeviceCategory;
+  icon: string | null;
+  vendor_name: string | null;
+  product_name: string | null;
+  class_name: string | null;
+  interfaces: DeviceInterface[];
+  parent_id: string | null;
+  location_path: string | null;
+  speed: LinkSpeed | null;
+  usb_version: string | null;
+  power: DevicePower | null;
+  hub_power: HubPower | null;
+  enrichment: EnrichStatus | null;
+}
+
+export type EnrichStatus =
+  | { state: "pending"; attempts: number }
+  | { state: "ok" }
+  | { state: "failed"; reason: string };
+
+export interface DevicePower {
+  max_power_ma: number;
+  self_powered: boolean;
+  hub_ports: number | null;
+}
+
+export interface HubPower {
+  budget_ma: number;
+  used_ma: number;
+  exceeded: boolean;
+}
+
+export interface TopologyNode {
+  device: DeviceSnapshot;
+  children: TopologyNode[];
+}
+
+export interface RawDescriptor {
+  descriptor_type: number;
+  data: number[];
+}
+
+export interface EndpointDescriptor {
+  address: number;
+  attributes: number;
+  max_packet_size: number;
+  interval: number;
+  companion: { max_burst: number; attributes: number; bytes_per_interval: number } | null;
+}
+
+export interface InterfaceDescriptor {
+  number: number;
+  alternate: number;
+  num_endpoints: number;
+  class: number;
+  subclass: number;
+  protocol: number;
+  i_interface: number;
+  endpoints: EndpointDescriptor[];
+  extra: RawDescriptor[];
+}
+
+export interface ConfigDescriptor {
+  total_length: number;
+  num_interfaces: number;
+  value: number;
+  i_configuration: number;
+  attributes: number;
+  max_power_ma: number;
+  interfaces: InterfaceDescriptor[];
+  extra: RawDescriptor[];
+}
+
+export type Capability =
+  | { type: "usb2_extension"; attributes: number }
+  | {
+      type: "super_speed";
+      attributes: number;
+      speeds_supported: number;
+      functionality_support: number;
+      u1_exit_latency: number;
+      u2_exit_latency: number;
+    }
+  | { type: "container_id"; uuid: string }
+  | { type: "super_speed_plus"; attributes: number; functionality_support: number; sublink_speeds: number[] }
+  | { type: "other"; capability_type: number; data: number[] };
+
+export interface DeviceDescriptors {
+  device: {
+    bcd_usb: number;
+    class: number;
+    subclass: number;
+    protocol: number;
+    max_packet_size0: number;
+    vendor_id: number;
+    product_id: number;
+    bcd_device: number;
+    i_manufacturer: number;
+    i_product: number;
+    i_serial: number;
+    num_configurations: number;
+  };
+  configurations: ConfigDescriptor[];
+  bos: { capabilities: Capability[] } | null;
+  strings: Record<number, string>;
+  /** Offset of a cut-short descriptor; it and the rest are left out. */
+  truncated_at: number | null;
+}
+
+export interface DeviceDetails {
+  descriptors: DeviceDescriptors;
+  /** Rendered like `lsusb -v`. */
+  text: string;
 }
 
 export interface AppSnapshot {
@@ -60,7 +286,19 @@ export interface AppSnapshot {
   error: string | null;
 }
 
+export interface UsbIdsInfo {
+  source: string;
+  vendors: number;
+  products: number;
+}
+
+export interface RulesInfo {
+  source: string;
+  rules: number;
+}
+
 export interface Prefs {
   theme: string;
   active_tab: string;
+  expand_interfaces: boolean;
 }
@@ -1,3 +1,5 @@
+import type { Bus, DeviceCategory, EventKind, LinkSpeed, PartitionFlag } from "./types";
+
 export function formatBytes(bytes: number): string {
   const KB = 1024;
   const MB = 1024 * KB;
@@ -10,8 +12,13 @@ export function formatBytes(bytes: number): string {
   return `${bytes} B`;
 }
 
+/** A stored RFC 3339 timestamp in local time: `14:05:22` today, `Mar 9 14:05:22` before. */
 export function formatTimestamp(ts: string): string {
-  return ts;
+  const date = new Date(ts);
+  if (isNaN(date.getTime())) return ts;
+  const time = date.toLocaleTimeString("en-GB", { hour12: false });
+  if (date.toDateString() === new Date().toDateString()) return time;
+  return `${date.toLocaleDateString("en-US", { month: "short", day: "numeric" })} ${time}`;
 }
 
 export function usedPercent(total: number, free: number): number {
@@ -39,17 +46,103 @@ export function relativeDate(isoString: string): string {
   return date.toLocaleDateString("en-US", { month: "short", day: "numeric" });
 }
 
-export function deviceClassCategory(className: string | undefined): string {
-  if (!className) return "Other";
-  const c = className.toLowerCase();
-  if (c.includes("disk") || c.includes("storage") || c.includes("cdrom")) return "Storage";
-  if (c.includes("hid") || c.includes("keyboard") || c.includes("mouse")) return "HID";
-  if (c.includes("audio") || c.includes("sound")) return "Audio";
-  if (c.includes("bluetooth")) return "Bluetooth";
-  if (c.includes("net") || c.includes("wireless")) return "Network";
-  return "Other";
+/** How an event is coloured: green, red, orange or accent. */
+export type EventTone = "connect" | "disconnect" | "warning" | "info";
+
+export const EVENT_TONES: Record<EventKind["kind"], EventTone> = {
+  connect: "connect",
+  reconnect: "connect",
+  disconnect: "disconnect",
+  attribute_changed: "info",
+  storage_enriched: "info",
+  volume_changed: "info",
+  enrichment_failed: "warning",
+  warning: "warning",
+  monitor_error: "warning",
+  monitor_resumed: "info",
+};
+
+export const EVENT_BADGES: Record<EventKind["kind"], string> = {
+  connect: "\u25B2 CONNECT",
+  reconnect: "\u25B2 RECONNECT",
+  disconnect: "\u25BC DISCONNECT",
+  attribute_changed: "\u25C6 CHANGED",
+  storage_enriched: "\u25C6 STORAGE",
+  volume_changed: "\u25C6 VOLUME",
+  enrichment_failed: "\u26A0 NO DETAILS",
+  warning: "\u26A0 WARNING",
+  monitor_error: "\u26A0 MONITOR",
+  monitor_resumed: "\u25C6 RESUMED",
+};
+
+/** One line on what happened beyond the kind itself; matches the CLI. */
+export function eventDetail(evt: EventKind): string | null {
+  switch (evt.kind) {
+    case "connect":
+      return null;
+    case "reconnect":
+      return `Back after ${(evt.away_ms / 1000).toFixed(1)}s`;
+    case "disconnect":
+      if (!evt.over_budget) return null;
+      return `${evt.over_budget.hub} was over its power budget (${evt.over_budget.used_ma} of ${evt.over_budget.budget_ma} mA)`;
+    case "attribute_changed": {
+      const label = evt.attribute[0].toUpperCase() + evt.attribute.slice(1);
+      return `${label} changed from ${evt.old ?? "none"} to ${evt.new ?? "none"}`;
+    }
+    case "storage_enriched":
+      return [evt.model, formatBytes(evt.total_bytes), ...evt.drives].filter(Boolean).join(", ");
+    case "volume_changed":
+      return evt.change;
+    case "enrichment_failed":
+      return evt.reason;
+    case "warning":
+      return evt.message;
+    case "monitor_error":
+      return evt.error;
+    case "monitor_resumed":
+      return `Back after ${Math.floor(evt.down_ms / 1000)}s`;
+  }
 }
 
-export type DeviceClassFilter = "All" | "Storage" | "HID" | "Audio" | "Bluetooth" | "Network" | "Other";
+export function linkSpeedLabel(speed: LinkSpeed): string {
+  switch (speed) {
+    case "low": return "Low Speed (1.5 Mbps)";
+    case "full": return "Full Speed (12 Mbps)";
+    case "high": return "High Speed (480 Mbps)";
+    case "super": return "SuperSpeed (5 Gbps)";
+    case "super_plus": return "SuperSpeed+ (10 Gbps)";
+    case "super_plus2x2": return "SuperSpeed+ 2x2 (20 Gbps)";
+  }
+}
+
+export const CATEGORY_LABELS: Record<DeviceCategory, string> = {
+  storage: "Storage",
+  hid: "HID",
+  audio: "Audio",
+  video: "Video",
+  bluetooth: "Bluetooth",
+  network: "Network",
+  hub: "Hub",
+  other: "Other",
+};
+
+export const BUS_LABELS: Record<Bus, string> = {
+  usb: "USB",
+  thunderbolt: "Thunderbolt",
+  bluetooth: "Bluetooth",
+  pci: "PCI",
+  hid: "HID",
+  other: "Other",
+};
+
+export const PARTITION_FLAG_LABELS: Record<PartitionFlag, string> = {
+  bootable: "Boot",
+  esp: "ESP",
+  hidden: "Hidden",
+  recovery: "Recovery",
+  read_only: "Read-only",
+};
+
+export type DeviceClassFilter = "All" | DeviceCategory;
 
-export const CLASS_FILTERS: DeviceClassFilter[] = ["All", "Storage", "HID", "Audio", "Bluetooth", "Network", "Other"];
+export const CLASS_FILTERS: DeviceClassFilter[] = ["All", "storage", "hid", "audio", "video", "bluetooth", "network", "hub", "other"];